@@ -1,9 +1,11 @@
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
-use types::Version;
+use types::{BucketConfig, LogSettings, Version};
 
 #[derive(CandidType, Serialize, Deserialize, Debug)]
 pub struct Args {
     pub wasm_version: Version,
     pub test_mode: bool,
+    pub config: BucketConfig,
+    pub log_settings: LogSettings,
 }
@@ -0,0 +1,60 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use types::{AccessorId, FileId, FileRejectedReason, TimestampMillis, UserId};
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct Args {
+    // If both are set, only records matching both are returned
+    pub file_id: Option<FileId>,
+    pub user_id: Option<UserId>,
+    // Records are returned newest first, so pass the `next_cursor` of the previous page to get the
+    // records before it
+    pub before: Option<u64>,
+    pub max_results: u32,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success(SuccessResult),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct SuccessResult {
+    pub records: Vec<AuditRecord>,
+    pub next_cursor: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct AuditRecord {
+    pub index: u64,
+    pub timestamp: TimestampMillis,
+    pub file_id: FileId,
+    pub owner: UserId,
+    pub operation: AuditOperation,
+    #[serde(default)]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum AuditOperation {
+    Uploaded,
+    Forwarded(ForwardedFrom),
+    Deleted(Principal),
+    // The owner was removed by the index canister, so each of their files was removed
+    OwnerRemoved,
+    AccessorRemoved(AccessorRemoved),
+    Rejected(FileRejectedReason),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ForwardedFrom {
+    pub file_id: FileId,
+    pub owner: UserId,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct AccessorRemoved {
+    pub accessor_id: AccessorId,
+    // Files are removed once their last accessor is removed
+    pub file_removed: bool,
+}
@@ -0,0 +1,31 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use types::{FileId, Hash, UserId};
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct Args {
+    pub start_after: Option<FileId>,
+    pub max_results: u32,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success(SuccessResult),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct SuccessResult {
+    // Ordered by file id and includes files which are still being uploaded
+    pub files: Vec<FileSummary>,
+    pub index_sync_pending: bool,
+    pub last_sequence_number_sent: u64,
+    pub last_sequence_number_received: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FileSummary {
+    pub file_id: FileId,
+    pub owner: UserId,
+    pub hash: Hash,
+    pub size: u64,
+}
@@ -1,2 +1,5 @@
+pub mod c2c_accessors;
+pub mod c2c_audit_log;
+pub mod c2c_files;
 pub mod file_info;
 pub mod file_status;
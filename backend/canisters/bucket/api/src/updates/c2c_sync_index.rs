@@ -1,21 +1,33 @@
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
-use types::{AccessorId, FileRemoved, UserId};
+use types::{AccessorId, BucketConfig, FileRemoved, LogSettings, UserId, UserLimits};
 
-#[derive(CandidType, Serialize, Deserialize, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct Args {
     pub users_added: Vec<UserId>,
     pub users_removed: Vec<UserId>,
     pub accessors_removed: Vec<AccessorId>,
     pub user_ids_updated: Vec<(UserId, UserId)>,
+    #[serde(default)]
+    pub config: Option<BucketConfig>,
+    #[serde(default)]
+    pub user_limits_updated: Vec<(UserId, UserLimits)>,
+    #[serde(default)]
+    pub sequence_number: u64,
+    #[serde(default)]
+    pub log_settings: Option<LogSettings>,
+    // Retries of the batch keep the same correlation id
+    #[serde(default)]
+    pub correlation_id: Option<String>,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Debug)]
 pub enum Response {
     Success(SuccessResult),
+    OutOfOrder(u64),
 }
 
-#[derive(CandidType, Serialize, Deserialize, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct SuccessResult {
     pub files_removed: Vec<FileRemoved>,
 }
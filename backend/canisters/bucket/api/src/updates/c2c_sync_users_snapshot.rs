@@ -0,0 +1,23 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use types::UserId;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Args {
+    // The time at which the index took the snapshot, which also serves as an increasing id
+    pub snapshot_id: u64,
+    pub users: Vec<UserId>,
+    pub is_last_page: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success(SuccessResult),
+}
+
+// The counts are only populated in response to the last page
+#[derive(CandidType, Serialize, Deserialize, Debug, Default)]
+pub struct SuccessResult {
+    pub users_added: u32,
+    pub users_removed: u32,
+}
@@ -13,4 +13,5 @@ pub enum Response {
     Success(FileId),
     NotAuthorized,
     NotFound,
+    TryAgainLater,
 }
@@ -1,4 +1,5 @@
 pub mod c2c_sync_index;
+pub mod c2c_sync_users_snapshot;
 pub mod delete_file;
 pub mod delete_files;
 pub mod forward_file;
@@ -28,6 +28,9 @@ pub enum Response {
     Full,
     HashMismatch,
     UserNotFound,
+    TryAgainLater,
+    FileCountExceeded,
+    MimeTypeNotAllowed,
 }
 
 impl Debug for Args {
@@ -1,4 +1,7 @@
-use crate::read_state;
+use crate::model::user_lookups::TryStartResult;
+use crate::{mutate_state, read_state};
+use candid::Principal;
+use index_canister::c2c_lookup_user;
 
 pub fn caller_is_index_canister() -> Result<(), String> {
     if read_state(|state| state.is_caller_index_canister()) {
@@ -15,3 +18,58 @@ pub fn caller_is_known_user() -> Result<(), String> {
         Err("Caller not recognised as a user".to_owned())
     }
 }
+
+// Buckets aren't told about users up front, so the first time a user interacts with a bucket, the
+// bucket checks with the index canister that they are a valid user and then caches the result. If
+// the caller can't be looked up straight away (eg. because a lookup of them is already in progress
+// for another of their calls) they are told to try again rather than being treated as unknown.
+pub async fn ensure_caller_is_known_user() -> KnownUserCheck {
+    let (caller, index_canister_id, is_known) =
+        read_state(|state| (state.env.caller(), state.data.index_canister_id, state.is_caller_known_user()));
+
+    if is_known {
+        return KnownUserCheck::Known;
+    } else if caller == Principal::anonymous() {
+        return KnownUserCheck::NotFound;
+    }
+
+    match mutate_state(|state| {
+        let now = state.env.now();
+        state.data.user_lookups.try_start(caller, now)
+    }) {
+        TryStartResult::Started => {}
+        TryStartResult::NotFound => return KnownUserCheck::NotFound,
+        TryStartResult::Busy => return KnownUserCheck::TryAgainLater,
+    }
+
+    let args = c2c_lookup_user::Args { user_id: caller };
+    match index_canister_c2c_client::c2c_lookup_user(index_canister_id, &args).await {
+        Ok(c2c_lookup_user::Response::Success) => {
+            mutate_state(|state| {
+                state.data.user_lookups.mark_found(&caller);
+                if !state.data.users.exists(&caller) {
+                    let now = state.env.now();
+                    state.data.users.add(caller, now);
+                }
+            });
+            KnownUserCheck::Known
+        }
+        Ok(c2c_lookup_user::Response::UserNotFound) => {
+            mutate_state(|state| {
+                let now = state.env.now();
+                state.data.user_lookups.mark_not_found(caller, now);
+            });
+            KnownUserCheck::NotFound
+        }
+        Err(_) => {
+            mutate_state(|state| state.data.user_lookups.mark_failed(&caller));
+            KnownUserCheck::TryAgainLater
+        }
+    }
+}
+
+pub enum KnownUserCheck {
+    Known,
+    NotFound,
+    TryAgainLater,
+}
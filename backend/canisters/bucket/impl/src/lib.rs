@@ -1,14 +1,22 @@
-use crate::model::files::Files;
+use crate::model::audit_log::AuditLog;
+use crate::model::files::{Files, RemoveFileResult};
 use crate::model::index_sync_state::IndexSyncState;
+use crate::model::pending_removals::{FileToRemove, PendingRemovals};
+use crate::model::user_lookups::UserLookups;
 use crate::model::users::Users;
+use bucket_canister::c2c_audit_log::{AccessorRemoved, AuditOperation};
+use bucket_canister::c2c_sync_index;
 use candid::CandidType;
-use canister_logger::LogMessagesWrapper;
+use canister_logger::{current_correlation_id, LogMessagesWrapper};
 use canister_state_macros::canister_state;
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
-use types::{CanisterId, Cycles, FileId, TimestampMillis, Timestamped, Version};
+use types::{BucketConfig, CanisterId, Cycles, FileId, FileRemoved, LogSettings, TimestampMillis, Timestamped, Version};
 use utils::env::Environment;
+use utils::instructions;
 use utils::memory;
+use utils::metrics::MetricsRegistry;
+use utils::sync_receiver::SyncReceiver;
 
 mod guards;
 mod lifecycle;
@@ -16,11 +24,6 @@ mod model;
 mod queries;
 mod updates;
 
-const DATA_LIMIT_BYTES: u64 = 1 << 30; // 1Gb
-const MAX_BLOB_SIZE_BYTES: u64 = 100 * (1 << 20); // 100Mb
-const MAX_EVENTS_TO_SYNC_PER_BATCH: usize = 1000;
-const MIN_CYCLES_BALANCE: Cycles = 2_000_000_000_000; // 2T
-
 #[derive(CandidType, Serialize, Deserialize)]
 enum StateVersion {
     V1,
@@ -36,11 +39,13 @@ canister_state!(RuntimeState);
 struct RuntimeState {
     pub env: Box<dyn Environment>,
     pub data: Data,
+    // Held in stable memory rather than as part of the data
+    pub audit_log: AuditLog,
 }
 
 impl RuntimeState {
-    pub fn new(env: Box<dyn Environment>, data: Data) -> RuntimeState {
-        RuntimeState { env, data }
+    pub fn new(env: Box<dyn Environment>, data: Data, audit_log: AuditLog) -> RuntimeState {
+        RuntimeState { env, data, audit_log }
     }
 
     pub fn is_caller_index_canister(&self) -> bool {
@@ -78,6 +83,9 @@ impl RuntimeState {
             file_count: file_metrics.file_count,
             blob_count: file_metrics.blob_count,
             index_sync_queue_length: self.data.index_sync_state.queue_len(),
+            pending_file_removals: self.data.pending_removals.len(),
+            config: self.data.config.clone(),
+            operations: self.data.operations.clone(),
         }
     }
 }
@@ -86,23 +94,86 @@ impl RuntimeState {
 struct Data {
     index_canister_id: CanisterId,
     users: Users,
+    #[serde(default)]
+    user_lookups: UserLookups,
     files: Files,
     index_sync_state: IndexSyncState,
     created: TimestampMillis,
     test_mode: bool,
+    #[serde(default)]
+    config: BucketConfig,
+    #[serde(default)]
+    index_sync_receiver: SyncReceiver<c2c_sync_index::SuccessResult>,
+    #[serde(default)]
+    pending_removals: PendingRemovals,
+    #[serde(default)]
+    operations: MetricsRegistry,
+    #[serde(default)]
+    log_settings: LogSettings,
 }
 
 impl Data {
-    pub fn new(index_canister_id: CanisterId, now: TimestampMillis, test_mode: bool) -> Data {
+    pub fn new(
+        index_canister_id: CanisterId,
+        now: TimestampMillis,
+        test_mode: bool,
+        config: BucketConfig,
+        log_settings: LogSettings,
+    ) -> Data {
         Data {
             index_canister_id,
             users: Users::default(),
+            user_lookups: UserLookups::default(),
             files: Files::default(),
             index_sync_state: IndexSyncState::default(),
             created: now,
             test_mode,
+            config,
+            index_sync_receiver: SyncReceiver::default(),
+            pending_removals: PendingRemovals::default(),
+            operations: MetricsRegistry::default(),
+            log_settings,
         }
     }
+
+    // Removes pending files until either there are none left or the instruction count for the current
+    // message reaches the limit given. Each removal is recorded in the audit log.
+    pub fn process_pending_removals(
+        &mut self,
+        instruction_limit: u64,
+        audit_log: &mut AuditLog,
+        now: TimestampMillis,
+    ) -> Vec<FileRemoved> {
+        let mut files_removed = Vec::new();
+
+        while instructions::count() < instruction_limit {
+            let file_removed = match self.pending_removals.pop() {
+                Some(FileToRemove::OwnedBy(user_id, file_id)) => match self.files.remove(user_id, file_id) {
+                    RemoveFileResult::Success(f) => {
+                        audit_log.record(now, file_id, f.owner, AuditOperation::OwnerRemoved, f.correlation_id.clone());
+                        Some(f)
+                    }
+                    _ => None,
+                },
+                Some(FileToRemove::AccessibleBy(accessor_id, file_id)) => {
+                    let owner = self.files.owner(&file_id);
+                    let file_removed = self.files.remove_accessor_from_file(&accessor_id, file_id);
+                    if let Some(owner) = owner {
+                        let operation = AuditOperation::AccessorRemoved(AccessorRemoved {
+                            accessor_id,
+                            file_removed: file_removed.is_some(),
+                        });
+                        audit_log.record(now, file_id, owner, operation, current_correlation_id());
+                    }
+                    file_removed
+                }
+                None => break,
+            };
+            files_removed.extend(file_removed);
+        }
+
+        files_removed
+    }
 }
 
 #[derive(CandidType, Serialize, Debug)]
@@ -114,6 +185,9 @@ pub struct Metrics {
     pub file_count: u32,
     pub blob_count: u32,
     pub index_sync_queue_length: u32,
+    pub pending_file_removals: u32,
+    pub config: BucketConfig,
+    pub operations: MetricsRegistry,
 }
 
 pub fn calc_chunk_count(chunk_size: u32, total_size: u64) -> u32 {
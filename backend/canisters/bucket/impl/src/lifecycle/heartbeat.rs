@@ -1,81 +0,0 @@
-use crate::model::users::FileStatusInternal;
-use crate::{mutate_state, RuntimeState, MIN_CYCLES_BALANCE};
-use ic_cdk_macros::heartbeat;
-use index_canister::c2c_sync_bucket::{Args, Response, SuccessResult};
-use types::CanisterId;
-
-#[heartbeat]
-fn heartbeat() {
-    sync_index::run();
-    check_cycles_balance::run();
-}
-
-mod sync_index {
-    use super::*;
-
-    pub fn run() {
-        if let Some((index_canister_id, args)) = mutate_state(next_batch) {
-            ic_cdk::spawn(send_to_index(index_canister_id, args));
-        }
-    }
-
-    fn next_batch(runtime_state: &mut RuntimeState) -> Option<(CanisterId, Args)> {
-        let bytes_remaining = runtime_state.data.files.bytes_remaining();
-        runtime_state
-            .data
-            .index_sync_state
-            .pop_args_for_next_sync(bytes_remaining)
-            .map(|args| (runtime_state.data.index_canister_id, args))
-    }
-
-    async fn send_to_index(index_canister_id: CanisterId, args: Args) {
-        match index_canister_c2c_client::c2c_sync_bucket(index_canister_id, &args).await {
-            Ok(Response::Success(result)) => {
-                mutate_state(|state| handle_success(result, state));
-            }
-            Err(_) => {
-                mutate_state(|state| handle_error(args, state));
-            }
-        }
-    }
-
-    fn handle_success(result: SuccessResult, runtime_state: &mut RuntimeState) {
-        // For each file that is rejected by the index canister we want to do 2 things -
-        // 1. Record the reason against the user so that they can determine what happened
-        // 2. Delete any additional data we have held for that file
-        for file in result.files_rejected {
-            let file_id = file.file_id;
-            let reason = file.reason.into();
-
-            if let Some(user_id) = runtime_state.data.files.owner(&file.file_id) {
-                if let Some(user) = runtime_state.data.users.get_mut(&user_id) {
-                    let old_status = user.set_file_status(file_id, FileStatusInternal::Rejected(reason));
-
-                    if let Some(FileStatusInternal::Uploading(_)) = old_status {
-                        runtime_state.data.files.remove_pending_file(&file_id);
-                    } else {
-                        runtime_state.data.files.remove(user_id, file_id);
-                    }
-                }
-            }
-        }
-
-        runtime_state.data.index_sync_state.mark_sync_completed();
-    }
-
-    fn handle_error(args: Args, runtime_state: &mut RuntimeState) {
-        runtime_state.data.index_sync_state.mark_sync_failed(args);
-    }
-}
-
-mod check_cycles_balance {
-    use super::*;
-
-    pub fn run() {
-        mutate_state(|state| {
-            let index_canister_id = state.data.index_canister_id;
-            let now = state.env.now();
-            utils::cycles::check_cycles_balance(MIN_CYCLES_BALANCE, index_canister_id, now);
-        })
-    }
-}
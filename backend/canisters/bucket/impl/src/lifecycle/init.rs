@@ -12,13 +12,13 @@ use utils::env::Environment;
 #[trace]
 fn init(args: Args) {
     set_panic_hook();
-    init_logger(args.test_mode);
+    init_logger(args.test_mode, &args.log_settings);
 
     let env = Box::new(CanisterEnv::new());
 
     let index_canister_id = env.caller();
 
-    let data = Data::new(index_canister_id, env.now(), args.test_mode);
+    let data = Data::new(index_canister_id, env.now(), args.test_mode, args.config, args.log_settings);
 
     init_state(env, data, args.wasm_version);
 
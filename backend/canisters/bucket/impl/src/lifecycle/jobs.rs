@@ -0,0 +1,196 @@
+use crate::model::index_sync_state::EventToSync;
+use crate::model::users::FileStatusInternal;
+use crate::{mutate_state, read_state, RuntimeState};
+use bucket_canister::c2c_audit_log::AuditOperation;
+use index_canister::c2c_sync_bucket::{Args, Response, SuccessResult};
+use tracing::error;
+use types::{CanisterId, Milliseconds};
+use utils::metrics::CallMetricNames;
+use utils::scheduler;
+use utils::time::MINUTE_IN_MS;
+
+const SYNC_RETRY_DELAY: Milliseconds = MINUTE_IN_MS;
+const CHECK_CYCLES_BALANCE_INTERVAL: Milliseconds = 5 * MINUTE_IN_MS;
+
+pub fn register_jobs() {
+    scheduler::register(sync_index::NAME, None, sync_index::run);
+    scheduler::register(process_pending_removals::NAME, None, process_pending_removals::run);
+    scheduler::register(
+        check_cycles_balance::NAME,
+        Some(CHECK_CYCLES_BALANCE_INTERVAL),
+        check_cycles_balance::run,
+    );
+}
+
+#[export_name = "canister_global_timer"]
+extern "C" fn global_timer() {
+    scheduler::run_due_jobs();
+}
+
+// Triggered whenever events are queued to be synced to the index
+pub mod sync_index {
+    use super::*;
+
+    const CALL_METRICS: CallMetricNames = CallMetricNames {
+        calls: "index_sync_batches_sent",
+        failures: "index_sync_batches_failed",
+        latency_ms: "index_sync_latency_ms",
+    };
+
+    pub const NAME: &str = "sync_index";
+
+    pub fn trigger() {
+        scheduler::run_now(NAME);
+    }
+
+    pub fn run() {
+        if let Some((index_canister_id, args)) = mutate_state(next_batch) {
+            let correlation_id = args.correlation_id.clone();
+            ic_cdk::spawn(canister_logger::with_correlation_id_async(
+                correlation_id,
+                send_to_index(index_canister_id, args),
+            ));
+        }
+    }
+
+    fn next_batch(runtime_state: &mut RuntimeState) -> Option<(CanisterId, Args)> {
+        let bytes_remaining = runtime_state
+            .data
+            .files
+            .bytes_remaining(runtime_state.data.config.data_limit_bytes);
+        let max_events = runtime_state.data.config.max_events_to_sync_per_batch as usize;
+        for accessor_id in runtime_state.data.files.take_accessors_unlinked() {
+            runtime_state
+                .data
+                .index_sync_state
+                .enqueue(EventToSync::AccessorUnlinked(accessor_id));
+        }
+        runtime_state
+            .data
+            .index_sync_state
+            .pop_args_for_next_sync(bytes_remaining, max_events)
+            .map(|mut args| {
+                // Retried batches already have a correlation id
+                args.correlation_id.get_or_insert_with(canister_logger::new_correlation_id);
+                (runtime_state.data.index_canister_id, args)
+            })
+    }
+
+    async fn send_to_index(index_canister_id: CanisterId, args: Args) {
+        let start = read_state(|state| state.env.now());
+        let response = index_canister_c2c_client::c2c_sync_bucket(index_canister_id, &args).await;
+        mutate_state(|state| {
+            let latency = state.env.now().saturating_sub(start);
+            state.data.operations.record_call(&CALL_METRICS, response.is_ok(), latency);
+        });
+
+        match response {
+            Ok(Response::Success(result)) => {
+                mutate_state(|state| handle_success(result, state));
+            }
+            Ok(Response::OutOfOrder(last_applied)) => {
+                mutate_state(|state| handle_out_of_order(args, last_applied, state));
+            }
+            Err(_) => {
+                mutate_state(|state| handle_error(args, state));
+            }
+        }
+    }
+
+    fn handle_success(result: SuccessResult, runtime_state: &mut RuntimeState) {
+        // For each file that is rejected by the index canister we want to do 2 things -
+        // 1. Record the reason against the user so that they can determine what happened
+        // 2. Delete any additional data we have held for that file
+        let now = runtime_state.env.now();
+        for file in result.files_rejected {
+            let file_id = file.file_id;
+            let reason = file.reason.clone().into();
+
+            if let Some(user_id) = runtime_state.data.files.owner(&file.file_id) {
+                let operation = AuditOperation::Rejected(file.reason);
+                runtime_state
+                    .audit_log
+                    .record(now, file_id, user_id, operation, canister_logger::current_correlation_id());
+
+                if let Some(user) = runtime_state.data.users.get_mut(&user_id) {
+                    let old_status = user.set_file_status(file_id, FileStatusInternal::Rejected(reason));
+
+                    if let Some(FileStatusInternal::Uploading(_)) = old_status {
+                        runtime_state.data.files.remove_pending_file(&file_id);
+                    } else {
+                        runtime_state.data.files.remove(user_id, file_id);
+                    }
+                }
+            }
+        }
+
+        runtime_state.data.index_sync_state.mark_sync_completed();
+
+        // Pick up any events which were queued while this batch was being synced
+        trigger();
+    }
+
+    // The index expected a different sequence number, so the events are requeued to be resent in a
+    // batch numbered from the last one the index applied
+    fn handle_out_of_order(args: Args, last_applied: u64, runtime_state: &mut RuntimeState) {
+        error!(
+            sequence_number = args.sequence_number,
+            last_applied, "Index rejected out of order sync batch"
+        );
+        runtime_state.data.index_sync_state.mark_sync_out_of_order(args, last_applied);
+        trigger();
+    }
+
+    fn handle_error(args: Args, runtime_state: &mut RuntimeState) {
+        runtime_state.data.index_sync_state.mark_sync_failed(args);
+        scheduler::run_after(NAME, SYNC_RETRY_DELAY);
+    }
+}
+
+// Triggered whenever there are files still to be removed after a user or accessor was removed. Each
+// run removes as many files as it can within its instruction budget then triggers itself again.
+pub mod process_pending_removals {
+    use super::*;
+
+    const INSTRUCTION_LIMIT: u64 = 3_000_000_000;
+
+    pub const NAME: &str = "process_pending_removals";
+
+    pub fn trigger() {
+        scheduler::run_now(NAME);
+    }
+
+    pub fn run() {
+        mutate_state(|state| {
+            let now = state.env.now();
+            let files_removed = state
+                .data
+                .process_pending_removals(INSTRUCTION_LIMIT, &mut state.audit_log, now);
+            if !files_removed.is_empty() {
+                for file in files_removed {
+                    state.data.index_sync_state.enqueue(EventToSync::FileRemoved(file));
+                }
+                sync_index::trigger();
+            }
+
+            if !state.data.pending_removals.is_empty() {
+                trigger();
+            }
+        })
+    }
+}
+
+mod check_cycles_balance {
+    use super::*;
+
+    pub const NAME: &str = "check_cycles_balance";
+
+    pub fn run() {
+        mutate_state(|state| {
+            let index_canister_id = state.data.index_canister_id;
+            let min_cycles_balance = state.data.config.min_cycles_balance;
+            let now = state.env.now();
+            utils::cycles::check_cycles_balance(min_cycles_balance, index_canister_id, now);
+        })
+    }
+}
@@ -1,24 +1,43 @@
+use crate::model::audit_log::{self, AuditLog};
 use crate::{init_state as set_state, Data, RuntimeState, LOG_MESSAGES, WASM_VERSION};
-use types::{Timestamped, Version};
+use types::{LogSettings, Timestamped, Version};
 use utils::env::Environment;
+use utils::stable_memory::StableMemory;
 
-mod heartbeat;
 mod init;
+pub mod jobs;
 mod post_upgrade;
 mod pre_upgrade;
 
 const BUFFER_SIZE: usize = 16 * 1024 * 1024; // 16MB
+const LOG_BUFFER_SIZE_MB: u32 = 32;
+const AUDIT_LOG_CAPACITY: u64 = 32 * 1024; // 16MB
 
-fn init_logger(enable_trace: bool) {
-    let log_messages = canister_logger::init_logger(enable_trace, None, ic_cdk::api::time);
+fn init_logger(trace_always: bool, log_settings: &LogSettings) {
+    let log_messages =
+        canister_logger::init_logger(trace_always, log_settings, None, Some(LOG_BUFFER_SIZE_MB), ic_cdk::api::time);
 
     LOG_MESSAGES.with(|c| *c.borrow_mut() = log_messages);
 }
 
+// Stable memory holds the log messages, followed by the audit log, followed by the serialized state.
+// The audit log is recreated (dropping its records) if the size of the log region changes.
 fn init_state(env: Box<dyn Environment>, data: Data, wasm_version: Version) {
     let now = env.now();
-    let runtime_state = RuntimeState::new(env, data);
+    let audit_log = AuditLog::init(
+        Box::new(StableMemory),
+        canister_logger::stable_state_offset(),
+        AUDIT_LOG_CAPACITY,
+    );
+    let runtime_state = RuntimeState::new(env, data, audit_log);
 
     set_state(runtime_state);
     WASM_VERSION.with(|v| *v.borrow_mut() = Timestamped::new(wasm_version, now));
+
+    // The global timer is cleared on upgrade so the jobs must be registered again each time
+    jobs::register_jobs();
+}
+
+fn stable_state_offset() -> u64 {
+    audit_log::region_end(&StableMemory, canister_logger::stable_state_offset())
 }
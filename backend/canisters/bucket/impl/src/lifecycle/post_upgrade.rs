@@ -1,12 +1,13 @@
-use crate::lifecycle::{init_logger, init_state, BUFFER_SIZE};
+use crate::lifecycle::{init_logger, init_state, stable_state_offset, BUFFER_SIZE};
 use crate::{Data, LOG_MESSAGES};
 use bucket_canister::post_upgrade::Args;
 use canister_api_macros::trace;
 use canister_logger::{set_panic_hook, LogMessage, LogMessagesWrapper};
-use ic_cdk::api::stable::BufferedStableReader;
 use ic_cdk_macros::post_upgrade;
+use std::io::BufReader;
 use tracing::info;
 use utils::env::canister::CanisterEnv;
+use utils::stable_memory::StableReader;
 
 #[post_upgrade]
 #[trace]
@@ -14,12 +15,12 @@ fn post_upgrade(args: Args) {
     set_panic_hook();
 
     let env = Box::new(CanisterEnv::new());
-    let reader = BufferedStableReader::new(BUFFER_SIZE);
+    let reader = BufReader::with_capacity(BUFFER_SIZE, StableReader::new(stable_state_offset()));
 
     let (data, log_messages, trace_messages): (Data, Vec<LogMessage>, Vec<LogMessage>) =
         serializer::deserialize(reader).unwrap();
 
-    init_logger(data.test_mode);
+    init_logger(data.test_mode, &data.log_settings);
     init_state(env, data, args.wasm_version);
 
     if !log_messages.is_empty() || !trace_messages.is_empty() {
@@ -0,0 +1,224 @@
+use bucket_canister::c2c_audit_log::{AuditOperation, AuditRecord};
+use types::{FileId, TimestampMillis, UserId};
+use utils::stable_memory::Memory;
+
+// The audit log is held in stable memory directly after the log region and is laid out as -
+// [header][slot 0][slot 1]...
+// Each record is written to a fixed size slot so that any record can be read directly from its index.
+// Once every slot has been used the oldest records are overwritten. The canister's serialized state
+// must come after the audit log, starting at `region_end`.
+const MAGIC: &[u8; 4] = b"AUDT";
+const LAYOUT_VERSION: u8 = 1;
+const HEADER_SIZE: u64 = 32;
+const SLOT_SIZE: u64 = 512;
+const LENGTH_PREFIX_SIZE: u64 = 2;
+
+pub struct AuditLog {
+    memory: Box<dyn Memory>,
+    start: u64,
+    capacity: u64,
+    next_index: u64,
+}
+
+pub struct AuditLogPage {
+    pub records: Vec<AuditRecord>,
+    pub next_cursor: Option<u64>,
+}
+
+// Returns the offset at which the audit log starting at `start` ends, or `start` if there is no audit
+// log there
+pub fn region_end(memory: &dyn Memory, start: u64) -> u64 {
+    read_header(memory, start).map_or(start, |(capacity, _)| start + HEADER_SIZE + capacity * SLOT_SIZE)
+}
+
+impl AuditLog {
+    // Opens the existing audit log if there is one with the same capacity, otherwise the audit log is
+    // recreated, dropping any records it held
+    pub fn init(mut memory: Box<dyn Memory>, start: u64, capacity: u64) -> AuditLog {
+        let next_index = match read_header(memory.as_ref(), start) {
+            Some((c, next_index)) if c == capacity => next_index,
+            _ => {
+                memory.grow_to(start + HEADER_SIZE + capacity * SLOT_SIZE);
+                0
+            }
+        };
+
+        let mut audit_log = AuditLog {
+            memory,
+            start,
+            capacity,
+            next_index,
+        };
+        audit_log.write_header();
+        audit_log
+    }
+
+    pub fn record(
+        &mut self,
+        now: TimestampMillis,
+        file_id: FileId,
+        owner: UserId,
+        operation: AuditOperation,
+        correlation_id: Option<String>,
+    ) {
+        let record = AuditRecord {
+            index: self.next_index,
+            timestamp: now,
+            file_id,
+            owner,
+            operation,
+            correlation_id,
+        };
+
+        let mut bytes = Vec::new();
+        serializer::serialize(&record, &mut bytes).unwrap();
+        if bytes.len() as u64 > SLOT_SIZE - LENGTH_PREFIX_SIZE {
+            return;
+        }
+
+        let offset = self.slot_offset(record.index);
+        self.memory.write(offset, &(bytes.len() as u16).to_le_bytes());
+        self.memory.write(offset + LENGTH_PREFIX_SIZE, &bytes);
+        self.next_index += 1;
+        self.write_header();
+    }
+
+    // Returns the records, newest first, involving the given file and/or user
+    pub fn query(
+        &self,
+        file_id: Option<FileId>,
+        user_id: Option<UserId>,
+        before: Option<u64>,
+        max_results: usize,
+    ) -> AuditLogPage {
+        let oldest_index = self.next_index.saturating_sub(self.capacity);
+        let end = before.map_or(self.next_index, |b| b.min(self.next_index));
+
+        let mut records = Vec::new();
+        let mut next_cursor = None;
+        for index in (oldest_index..end).rev() {
+            if let Some(record) = self.read(index) {
+                if file_id.map_or(true, |f| involves_file(&record, f)) && user_id.map_or(true, |u| involves_user(&record, u)) {
+                    if records.len() == max_results {
+                        next_cursor = records.last().map(|r| r.index);
+                        break;
+                    }
+                    records.push(record);
+                }
+            }
+        }
+
+        AuditLogPage { records, next_cursor }
+    }
+
+    fn read(&self, index: u64) -> Option<AuditRecord> {
+        let offset = self.slot_offset(index);
+        let mut length_bytes = [0; LENGTH_PREFIX_SIZE as usize];
+        self.memory.read(offset, &mut length_bytes);
+
+        let mut bytes = vec![0; u16::from_le_bytes(length_bytes) as usize];
+        self.memory.read(offset + LENGTH_PREFIX_SIZE, &mut bytes);
+        serializer::deserialize(bytes.as_slice()).ok()
+    }
+
+    fn slot_offset(&self, index: u64) -> u64 {
+        self.start + HEADER_SIZE + (index % self.capacity) * SLOT_SIZE
+    }
+
+    fn write_header(&mut self) {
+        let mut bytes = [0; HEADER_SIZE as usize];
+        bytes[0..4].copy_from_slice(MAGIC);
+        bytes[4] = LAYOUT_VERSION;
+        bytes[8..16].copy_from_slice(&self.capacity.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.next_index.to_le_bytes());
+        self.memory.write(self.start, &bytes);
+    }
+}
+
+// Returns the capacity and next index of the audit log starting at `start`, if there is one
+fn read_header(memory: &dyn Memory, start: u64) -> Option<(u64, u64)> {
+    if memory.size_bytes() < start + HEADER_SIZE {
+        return None;
+    }
+
+    let mut bytes = [0; HEADER_SIZE as usize];
+    memory.read(start, &mut bytes);
+    if &bytes[0..4] != MAGIC || bytes[4] != LAYOUT_VERSION {
+        return None;
+    }
+
+    Some((
+        u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+        u64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+    ))
+}
+
+fn involves_file(record: &AuditRecord, file_id: FileId) -> bool {
+    record.file_id == file_id || matches!(&record.operation, AuditOperation::Forwarded(f) if f.file_id == file_id)
+}
+
+fn involves_user(record: &AuditRecord, user_id: UserId) -> bool {
+    record.owner == user_id
+        || match &record.operation {
+            AuditOperation::Forwarded(f) => f.owner == user_id,
+            AuditOperation::Deleted(deleted_by) => *deleted_by == user_id,
+            AuditOperation::AccessorRemoved(a) => a.accessor_id == user_id,
+            _ => false,
+        }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bucket_canister::c2c_audit_log::ForwardedFrom;
+    use candid::Principal;
+    use utils::stable_memory::VecMemory;
+
+    #[test]
+    fn query_by_file_and_user_with_paging() {
+        let memory = VecMemory::default();
+        let mut audit_log = AuditLog::init(Box::new(memory.clone()), 100, 10);
+        let alice = Principal::from_slice(&[1]);
+        let bob = Principal::from_slice(&[2]);
+
+        audit_log.record(1, 1, alice, AuditOperation::Uploaded, None);
+        let forwarded_from = ForwardedFrom {
+            file_id: 1,
+            owner: alice,
+        };
+        audit_log.record(2, 2, bob, AuditOperation::Forwarded(forwarded_from), None);
+        audit_log.record(3, 3, alice, AuditOperation::Uploaded, None);
+        audit_log.record(4, 1, alice, AuditOperation::Deleted(bob), Some("abc".to_string()));
+
+        let page = audit_log.query(Some(1), None, None, 2);
+        assert_eq!(page.records.iter().map(|r| r.index).collect::<Vec<_>>(), vec![3, 1]);
+        assert_eq!(page.next_cursor, Some(1));
+
+        let page = audit_log.query(Some(1), None, page.next_cursor, 2);
+        assert_eq!(page.records.len(), 1);
+        assert!(matches!(page.records[0].operation, AuditOperation::Uploaded));
+        assert!(page.next_cursor.is_none());
+
+        let page = audit_log.query(None, Some(bob), None, 10);
+        assert_eq!(page.records.iter().map(|r| r.index).collect::<Vec<_>>(), vec![3, 1]);
+    }
+
+    #[test]
+    fn oldest_records_overwritten_and_records_survive_reopening() {
+        let memory = VecMemory::default();
+        let mut audit_log = AuditLog::init(Box::new(memory.clone()), 100, 3);
+        let user = Principal::from_slice(&[1]);
+        for file_id in 0..5 {
+            audit_log.record(0, file_id, user, AuditOperation::Uploaded, None);
+        }
+        assert_eq!(region_end(&memory, 100), 100 + HEADER_SIZE + 3 * SLOT_SIZE);
+
+        let audit_log = AuditLog::init(Box::new(memory.clone()), 100, 3);
+        let page = audit_log.query(None, None, None, 10);
+        assert_eq!(page.records.iter().map(|r| r.file_id).collect::<Vec<_>>(), vec![4, 3, 2]);
+
+        // Changing the capacity resets the audit log
+        let audit_log = AuditLog::init(Box::new(memory), 100, 4);
+        assert!(audit_log.query(None, None, None, 10).records.is_empty());
+    }
+}
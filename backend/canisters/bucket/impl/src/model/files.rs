@@ -1,20 +1,29 @@
-use crate::{calc_chunk_count, DATA_LIMIT_BYTES, MAX_BLOB_SIZE_BYTES};
+use crate::calc_chunk_count;
+use bucket_canister::c2c_files::FileSummary;
 use bucket_canister::upload_chunk_v2::Args as UploadChunkArgs;
 use candid::Principal;
+use canister_logger::current_correlation_id;
 use serde::{Deserialize, Serialize};
 use serde_bytes::ByteBuf;
 use std::cmp::Ordering;
-use std::collections::hash_map::Entry::{Occupied, Vacant};
-use std::collections::{HashMap, HashSet};
+use std::collections::btree_map::Entry::{Occupied, Vacant};
+use std::collections::hash_map;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Bound::{Excluded, Unbounded};
 use types::{AccessorId, FileAdded, FileId, FileRemoved, Hash, TimestampMillis, UserId};
 use utils::hasher::hash_bytes;
 
 #[derive(Serialize, Deserialize, Default)]
 pub struct Files {
-    files: HashMap<FileId, File>,
-    pending_files: HashMap<FileId, PendingFile>,
+    // Ordered by file id so that they can be paged through efficiently
+    files: BTreeMap<FileId, File>,
+    pending_files: BTreeMap<FileId, PendingFile>,
     reference_counts: ReferenceCounts,
     accessors_map: AccessorsMap,
+    // Accessors whose last file in this bucket has been removed, which are yet to be reported to the
+    // index so that it can stop sending events about them to this bucket
+    #[serde(default)]
+    accessors_unlinked: HashSet<AccessorId>,
     // TODO move this to stable memory
     blobs: HashMap<Hash, ByteBuf>,
     bytes_used: u64,
@@ -56,9 +65,9 @@ impl Files {
             .or_else(|| self.pending_files.get(file_id).map(|f| f.owner))
     }
 
-    pub fn put_chunk(&mut self, args: PutChunkArgs) -> PutChunkResult {
-        if args.total_size > MAX_BLOB_SIZE_BYTES {
-            return PutChunkResult::FileTooBig(MAX_BLOB_SIZE_BYTES);
+    pub fn put_chunk(&mut self, args: PutChunkArgs, max_blob_size_bytes: u64) -> PutChunkResult {
+        if args.total_size > max_blob_size_bytes {
+            return PutChunkResult::FileTooBig(max_blob_size_bytes);
         }
 
         if self.files.contains_key(&args.file_id) {
@@ -76,6 +85,9 @@ impl Files {
                     file_id,
                     hash: args.hash,
                     size: args.total_size,
+                    accessors: args.accessors.clone(),
+                    correlation_id: current_correlation_id(),
+                    mime_type: Some(args.mime_type.clone()),
                 });
                 let pending_file: PendingFile = args.into();
                 if pending_file.is_completed() {
@@ -126,7 +138,9 @@ impl Files {
             if e.get().can_be_removed_by(caller) {
                 let file = e.remove();
                 for accessor_id in file.accessors.iter() {
-                    self.accessors_map.unlink(*accessor_id, &file_id);
+                    if self.accessors_map.unlink(*accessor_id, &file_id) {
+                        self.accessors_unlinked.insert(*accessor_id);
+                    }
                 }
 
                 let mut blob_deleted = false;
@@ -140,6 +154,8 @@ impl Files {
                     owner: file.owner,
                     hash: file.hash,
                     blob_deleted,
+                    correlation_id: current_correlation_id(),
+                    removed_by: Some(caller),
                 })
             } else {
                 RemoveFileResult::NotAuthorized
@@ -168,12 +184,13 @@ impl Files {
             self.accessors_map.link_many(caller, accessors.iter().copied(), new_file_id);
             self.reference_counts.incr(hash);
 
+            let accessors_added = accessors.iter().copied().collect();
             let new_file = File {
                 owner: caller,
                 created: now,
                 accessors,
                 hash,
-                mime_type: file.mime_type,
+                mime_type: file.mime_type.clone(),
             };
 
             if self.files.insert(new_file_id, new_file).is_none() {
@@ -182,6 +199,9 @@ impl Files {
                     owner: caller,
                     hash,
                     size,
+                    accessors: accessors_added,
+                    correlation_id: current_correlation_id(),
+                    mime_type: Some(file.mime_type),
                 })
             } else {
                 // There should never be a file_id clash
@@ -196,37 +216,67 @@ impl Files {
         self.pending_files.remove(file_id).is_some()
     }
 
-    pub fn remove_accessor(&mut self, accessor_id: &AccessorId) -> Vec<FileRemoved> {
-        let mut files_removed = Vec::new();
-
-        if let Some(file_ids) = self.accessors_map.remove(accessor_id) {
-            for file_id in file_ids {
-                let mut blob_to_delete = None;
-                if let Occupied(mut e) = self.files.entry(file_id) {
-                    let file = e.get_mut();
-                    file.accessors.remove(accessor_id);
-                    if file.accessors.is_empty() {
-                        let delete_blob = self.reference_counts.decr(file.hash) == 0;
-                        if delete_blob {
-                            blob_to_delete = Some(file.hash);
-                        }
-                        let file = e.remove();
-                        files_removed.push(FileRemoved {
-                            file_id,
-                            owner: file.owner,
-                            hash: file.hash,
-                            blob_deleted: delete_blob,
-                        });
-                    }
-                }
-
-                if let Some(blob_to_delete) = blob_to_delete {
-                    self.remove_blob(&blob_to_delete);
+    // Accessors which have since been linked to new files are skipped since the index will already
+    // have been told that they have files in this bucket
+    pub fn take_accessors_unlinked(&mut self) -> Vec<AccessorId> {
+        let accessors_unlinked = std::mem::take(&mut self.accessors_unlinked);
+        accessors_unlinked
+            .into_iter()
+            .filter(|a| !self.accessors_map.map.contains_key(a))
+            .collect()
+    }
+
+    pub fn accessors(&self, start_after: Option<AccessorId>, max_results: usize) -> Vec<AccessorId> {
+        let mut accessors: Vec<_> = self
+            .accessors_map
+            .map
+            .keys()
+            .copied()
+            .filter(|a| start_after.map_or(true, |s| *a > s))
+            .collect();
+        accessors.sort_unstable();
+        accessors.truncate(max_results);
+        accessors
+    }
+
+    // Unlinks the accessor from all of their files and returns the ids of those files, each of which
+    // must then be passed to `remove_accessor_from_file`
+    pub fn take_accessor_file_ids(&mut self, accessor_id: &AccessorId) -> Vec<FileId> {
+        self.accessors_map
+            .remove(accessor_id)
+            .map(|file_ids| file_ids.into_iter().collect())
+            .unwrap_or_default()
+    }
+
+    // Removes the accessor from the file, then if the file has no accessors remaining, removes the file
+    pub fn remove_accessor_from_file(&mut self, accessor_id: &AccessorId, file_id: FileId) -> Option<FileRemoved> {
+        let mut file_removed = None;
+        let mut blob_to_delete = None;
+        if let Occupied(mut e) = self.files.entry(file_id) {
+            let file = e.get_mut();
+            file.accessors.remove(accessor_id);
+            if file.accessors.is_empty() {
+                let delete_blob = self.reference_counts.decr(file.hash) == 0;
+                if delete_blob {
+                    blob_to_delete = Some(file.hash);
                 }
+                let file = e.remove();
+                file_removed = Some(FileRemoved {
+                    file_id,
+                    owner: file.owner,
+                    hash: file.hash,
+                    blob_deleted: delete_blob,
+                    correlation_id: current_correlation_id(),
+                    removed_by: None,
+                });
             }
         }
 
-        files_removed
+        if let Some(blob_to_delete) = blob_to_delete {
+            self.remove_blob(&blob_to_delete);
+        }
+
+        file_removed
     }
 
     pub fn update_owner(&mut self, file_id: &FileId, new_owner: UserId) -> bool {
@@ -260,8 +310,35 @@ impl Files {
         self.blobs.get(hash).map(|b| b.len() as u64)
     }
 
-    pub fn bytes_remaining(&self) -> i64 {
-        (DATA_LIMIT_BYTES as i64) - (self.bytes_used as i64)
+    // Returns up to `max_results` files, including pending files, ordered by file id
+    pub fn summaries(&self, start_after: Option<FileId>, max_results: usize) -> Vec<FileSummary> {
+        let range = (start_after.map_or(Unbounded, Excluded), Unbounded);
+
+        let completed = self.files.range(range).take(max_results).map(|(file_id, file)| FileSummary {
+            file_id: *file_id,
+            owner: file.owner,
+            hash: file.hash,
+            size: self.data_size(&file.hash).unwrap_or_default(),
+        });
+        let pending = self
+            .pending_files
+            .range(range)
+            .take(max_results)
+            .map(|(file_id, file)| FileSummary {
+                file_id: *file_id,
+                owner: file.owner,
+                hash: file.hash,
+                size: file.total_size,
+            });
+
+        let mut summaries: Vec<_> = completed.chain(pending).collect();
+        summaries.sort_unstable_by_key(|f| f.file_id);
+        summaries.truncate(max_results);
+        summaries
+    }
+
+    pub fn bytes_remaining(&self, data_limit_bytes: u64) -> i64 {
+        (data_limit_bytes as i64) - (self.bytes_used as i64)
     }
 
     pub fn metrics(&self) -> Metrics {
@@ -291,7 +368,7 @@ impl Files {
     }
 
     fn add_blob_if_not_exists(&mut self, hash: Hash, bytes: ByteBuf) {
-        if let Vacant(e) = self.blobs.entry(hash) {
+        if let hash_map::Entry::Vacant(e) = self.blobs.entry(hash) {
             self.bytes_used = self
                 .bytes_used
                 .checked_add(bytes.len() as u64)
@@ -335,7 +412,7 @@ impl ReferenceCounts {
     }
 
     pub fn decr(&mut self, hash: Hash) -> u32 {
-        if let Occupied(mut e) = self.counts.entry(hash) {
+        if let hash_map::Entry::Occupied(mut e) = self.counts.entry(hash) {
             let count = e.get_mut();
             if *count > 1 {
                 *count -= 1;
@@ -366,14 +443,17 @@ impl AccessorsMap {
         self.map.entry(accessor_id).or_default().insert(file_id);
     }
 
-    pub fn unlink(&mut self, accessor_id: AccessorId, file_id: &FileId) {
-        if let Occupied(mut e) = self.map.entry(accessor_id) {
+    // Returns true if the accessor no longer has access to any files
+    pub fn unlink(&mut self, accessor_id: AccessorId, file_id: &FileId) -> bool {
+        if let hash_map::Entry::Occupied(mut e) = self.map.entry(accessor_id) {
             let entry = e.get_mut();
             entry.remove(file_id);
             if entry.is_empty() {
                 e.remove();
+                return true;
             }
         }
+        false
     }
 
     pub fn remove(&mut self, accessor_id: &AccessorId) -> Option<HashSet<FileId>> {
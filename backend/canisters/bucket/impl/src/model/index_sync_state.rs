@@ -1,17 +1,21 @@
-use crate::MAX_EVENTS_TO_SYNC_PER_BATCH;
 use index_canister::c2c_sync_bucket::Args;
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
-use types::{FileAdded, FileRemoved};
+use types::{AccessorId, FileAdded, FileRemoved};
 
 // We want to send events to the index in order, so while a sync is in progress we avoid sending
 // more events in case the first batch fails and the second succeeds. If a sync fails, the args that
-// were sent are stored so that they can be retried again.
+// were sent are stored so that they can be retried again. Each batch is given a new sequence number
+// (retries reuse the original) so that the index can detect batches it has already applied. If the
+// index rejects a batch as being out of order, the batch's events are requeued and numbering restarts
+// from the last batch the index applied.
 #[derive(Serialize, Deserialize, Default)]
 pub struct IndexSyncState {
     queue: VecDeque<EventToSync>,
     in_progress: bool,
     args_to_retry: Option<Args>,
+    #[serde(default)]
+    last_sequence_number: u64,
 }
 
 impl IndexSyncState {
@@ -19,7 +23,7 @@ impl IndexSyncState {
         self.queue.push_back(event);
     }
 
-    pub fn pop_args_for_next_sync(&mut self, bytes_remaining: i64) -> Option<Args> {
+    pub fn pop_args_for_next_sync(&mut self, bytes_remaining: i64, max_events: usize) -> Option<Args> {
         if self.in_progress {
             None
         } else if let Some(args) = self.args_to_retry.take() {
@@ -32,13 +36,18 @@ impl IndexSyncState {
                 bytes_remaining,
                 files_added: Vec::new(),
                 files_removed: Vec::new(),
+                accessors_unlinked: Vec::new(),
+                sequence_number: self.last_sequence_number + 1,
+                correlation_id: None,
             };
+            self.last_sequence_number += 1;
 
-            for _ in 0..MAX_EVENTS_TO_SYNC_PER_BATCH {
+            for _ in 0..max_events {
                 if let Some(event) = self.queue.pop_front() {
                     match event {
                         EventToSync::FileAdded(a) => args.files_added.push(a),
                         EventToSync::FileRemoved(r) => args.files_removed.push(r),
+                        EventToSync::AccessorUnlinked(a) => args.accessors_unlinked.push(a),
                     }
                 } else {
                     break;
@@ -58,6 +67,28 @@ impl IndexSyncState {
         self.args_to_retry = Some(args);
     }
 
+    pub fn mark_sync_out_of_order(&mut self, args: Args, last_applied: u64) {
+        self.in_progress = false;
+        let events = args
+            .files_added
+            .into_iter()
+            .map(EventToSync::FileAdded)
+            .chain(args.files_removed.into_iter().map(EventToSync::FileRemoved))
+            .chain(args.accessors_unlinked.into_iter().map(EventToSync::AccessorUnlinked));
+        for event in events.rev() {
+            self.queue.push_front(event);
+        }
+        self.last_sequence_number = last_applied;
+    }
+
+    pub fn is_idle(&self) -> bool {
+        !self.in_progress && self.args_to_retry.is_none() && self.queue.is_empty()
+    }
+
+    pub fn last_sequence_number(&self) -> u64 {
+        self.last_sequence_number
+    }
+
     pub fn queue_len(&self) -> u32 {
         self.queue.len() as u32
     }
@@ -67,4 +98,5 @@ impl IndexSyncState {
 pub enum EventToSync {
     FileAdded(FileAdded),
     FileRemoved(FileRemoved),
+    AccessorUnlinked(AccessorId),
 }
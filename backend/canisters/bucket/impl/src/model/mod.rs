@@ -1,3 +1,6 @@
+pub mod audit_log;
 pub mod files;
 pub mod index_sync_state;
+pub mod pending_removals;
+pub mod user_lookups;
 pub mod users;
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use types::{AccessorId, FileId, UserId};
+
+// Files which are waiting to be removed due to their owner or accessor being removed. Removing all
+// of a user's files in a single message could exceed the instruction limit, so instead the files
+// are removed in batches across multiple messages.
+#[derive(Serialize, Deserialize, Default)]
+pub struct PendingRemovals {
+    queue: VecDeque<PendingRemoval>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum PendingRemoval {
+    UserFiles(UserId, Vec<FileId>),
+    AccessorFiles(AccessorId, Vec<FileId>),
+}
+
+pub enum FileToRemove {
+    OwnedBy(UserId, FileId),
+    AccessibleBy(AccessorId, FileId),
+}
+
+impl PendingRemovals {
+    pub fn enqueue_user_files(&mut self, user_id: UserId, file_ids: Vec<FileId>) {
+        if !file_ids.is_empty() {
+            self.queue.push_back(PendingRemoval::UserFiles(user_id, file_ids));
+        }
+    }
+
+    pub fn enqueue_accessor_files(&mut self, accessor_id: AccessorId, file_ids: Vec<FileId>) {
+        if !file_ids.is_empty() {
+            self.queue.push_back(PendingRemoval::AccessorFiles(accessor_id, file_ids));
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<FileToRemove> {
+        let next = self.queue.front_mut()?;
+        let (file_to_remove, is_empty) = match next {
+            PendingRemoval::UserFiles(user_id, file_ids) => {
+                let file_id = file_ids.pop()?;
+                (FileToRemove::OwnedBy(*user_id, file_id), file_ids.is_empty())
+            }
+            PendingRemoval::AccessorFiles(accessor_id, file_ids) => {
+                let file_id = file_ids.pop()?;
+                (FileToRemove::AccessibleBy(*accessor_id, file_id), file_ids.is_empty())
+            }
+        };
+
+        if is_empty {
+            self.queue.pop_front();
+        }
+        Some(file_to_remove)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn len(&self) -> u32 {
+        self.queue
+            .iter()
+            .map(|r| match r {
+                PendingRemoval::UserFiles(_, file_ids) => file_ids.len(),
+                PendingRemoval::AccessorFiles(_, file_ids) => file_ids.len(),
+            })
+            .sum::<usize>() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+
+    #[test]
+    fn files_popped_in_order_of_removals_queued() {
+        let user_id = Principal::from_slice(&[1]);
+        let accessor_id = Principal::from_slice(&[2]);
+
+        let mut pending_removals = PendingRemovals::default();
+        pending_removals.enqueue_user_files(user_id, vec![1, 2]);
+        pending_removals.enqueue_accessor_files(accessor_id, Vec::new());
+        pending_removals.enqueue_accessor_files(accessor_id, vec![3]);
+
+        assert_eq!(pending_removals.len(), 3);
+        assert!(matches!(pending_removals.pop(), Some(FileToRemove::OwnedBy(u, 2)) if u == user_id));
+        assert!(matches!(pending_removals.pop(), Some(FileToRemove::OwnedBy(u, 1)) if u == user_id));
+        assert!(matches!(pending_removals.pop(), Some(FileToRemove::AccessibleBy(a, 3)) if a == accessor_id));
+        assert!(pending_removals.pop().is_none());
+        assert!(pending_removals.is_empty());
+    }
+}
@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use types::{Milliseconds, TimestampMillis, UserId};
+use utils::time::MINUTE_IN_MS;
+
+const NOT_FOUND_EXPIRY: Milliseconds = 5 * MINUTE_IN_MS;
+const MAX_NOT_FOUND: usize = 10_000;
+const MAX_LOOKUPS_IN_PROGRESS: usize = 50;
+
+// Each lookup of an unknown caller costs an inter-canister call to the index, so callers which the
+// index doesn't recognise are remembered for a while, only one lookup per caller can be in progress
+// at a time and the total number of lookups in progress is capped.
+#[derive(Serialize, Deserialize, Default)]
+pub struct UserLookups {
+    #[serde(skip)]
+    in_progress: HashSet<UserId>,
+    // The time until which each caller is treated as not found without asking the index again
+    not_found: HashMap<UserId, TimestampMillis>,
+}
+
+impl UserLookups {
+    pub fn try_start(&mut self, caller: UserId, now: TimestampMillis) -> TryStartResult {
+        if self.not_found.get(&caller).map_or(false, |expires| now < *expires) {
+            TryStartResult::NotFound
+        } else if self.in_progress.len() >= MAX_LOOKUPS_IN_PROGRESS || !self.in_progress.insert(caller) {
+            TryStartResult::Busy
+        } else {
+            TryStartResult::Started
+        }
+    }
+
+    pub fn mark_found(&mut self, caller: &UserId) {
+        self.in_progress.remove(caller);
+        self.not_found.remove(caller);
+    }
+
+    pub fn mark_not_found(&mut self, caller: UserId, now: TimestampMillis) {
+        self.in_progress.remove(&caller);
+
+        if self.not_found.len() >= MAX_NOT_FOUND {
+            self.not_found.retain(|_, expires| now < *expires);
+        }
+        if self.not_found.len() < MAX_NOT_FOUND {
+            self.not_found.insert(caller, now + NOT_FOUND_EXPIRY);
+        }
+    }
+
+    // Nothing is remembered if the lookup failed, so the next call from the caller will retry it
+    pub fn mark_failed(&mut self, caller: &UserId) {
+        self.in_progress.remove(caller);
+    }
+}
+
+pub enum TryStartResult {
+    Started,
+    NotFound,
+    // Either a lookup of the caller is already in progress or the cap on lookups has been reached
+    Busy,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+
+    #[test]
+    fn not_found_callers_are_not_looked_up_again_until_expiry() {
+        let caller = Principal::from_slice(&[1]);
+        let mut lookups = UserLookups::default();
+
+        assert!(matches!(lookups.try_start(caller, 0), TryStartResult::Started));
+        assert!(matches!(lookups.try_start(caller, 0), TryStartResult::Busy));
+
+        lookups.mark_not_found(caller, 0);
+        assert!(matches!(
+            lookups.try_start(caller, NOT_FOUND_EXPIRY - 1),
+            TryStartResult::NotFound
+        ));
+        assert!(matches!(lookups.try_start(caller, NOT_FOUND_EXPIRY), TryStartResult::Started));
+
+        lookups.mark_found(&caller);
+        assert!(matches!(lookups.try_start(caller, NOT_FOUND_EXPIRY), TryStartResult::Started));
+    }
+
+    #[test]
+    fn lookups_in_progress_are_capped() {
+        let mut lookups = UserLookups::default();
+
+        for i in 0..MAX_LOOKUPS_IN_PROGRESS {
+            assert!(matches!(
+                lookups.try_start(Principal::from_slice(&[i as u8]), 0),
+                TryStartResult::Started
+            ));
+        }
+        assert!(matches!(
+            lookups.try_start(Principal::from_slice(&[255]), 0),
+            TryStartResult::Busy
+        ));
+
+        lookups.mark_failed(&Principal::from_slice(&[0]));
+        assert!(matches!(
+            lookups.try_start(Principal::from_slice(&[255]), 0),
+            TryStartResult::Started
+        ));
+    }
+}
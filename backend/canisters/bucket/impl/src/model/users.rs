@@ -1,21 +1,103 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use types::{FileId, RejectedReason, UserId};
+use std::collections::hash_map::Entry::Vacant;
+use std::collections::{HashMap, HashSet};
+use types::{FileId, Milliseconds, RejectedReason, TimestampMillis, UserId, UserLimits};
+use utils::time::MINUTE_IN_MS;
+
+// Allows for the clocks of the index and the bucket differing slightly
+const SNAPSHOT_CLOCK_SKEW_MARGIN: Milliseconds = 5 * MINUTE_IN_MS;
 
 #[derive(Serialize, Deserialize, Default)]
 pub struct Users {
     users: HashMap<UserId, UserRecord>,
+    // A snapshot of the users the index believes this bucket should know about, which is received
+    // over multiple pages and then applied once complete
+    #[serde(default)]
+    snapshot: Option<UsersSnapshot>,
+    #[serde(default)]
+    last_applied_snapshot_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct UsersSnapshot {
+    id: u64,
+    users: HashSet<UserId>,
 }
 
 impl Users {
-    pub fn add(&mut self, user_id: UserId) -> bool {
-        self.users.insert(user_id, UserRecord::default()).is_none()
+    pub fn add(&mut self, user_id: UserId, now: TimestampMillis) -> bool {
+        // Users added while a snapshot is being received must not be removed when it is applied
+        if let Some(snapshot) = self.snapshot.as_mut() {
+            snapshot.users.insert(user_id);
+        }
+        let user = UserRecord {
+            added: now,
+            ..Default::default()
+        };
+        self.users.insert(user_id, user).is_none()
     }
 
     pub fn remove(&mut self, user_id: &UserId) -> Option<UserRecord> {
+        if let Some(snapshot) = self.snapshot.as_mut() {
+            snapshot.users.remove(user_id);
+        }
         self.users.remove(user_id)
     }
 
+    // Returns false if the snapshot has already been applied, in which case this page is a retry
+    pub fn add_to_snapshot(&mut self, snapshot_id: u64, users: Vec<UserId>) -> bool {
+        if self.last_applied_snapshot_id.map_or(false, |id| snapshot_id <= id) {
+            return false;
+        }
+
+        match self.snapshot.as_mut() {
+            Some(snapshot) if snapshot.id == snapshot_id => snapshot.users.extend(users),
+            _ => {
+                self.snapshot = Some(UsersSnapshot {
+                    id: snapshot_id,
+                    users: users.into_iter().collect(),
+                })
+            }
+        }
+        true
+    }
+
+    // Adds any users in the snapshot who are missing, then removes and returns any users who are not
+    // in the snapshot
+    pub fn apply_snapshot(&mut self, snapshot_id: u64) -> (Vec<UserId>, Vec<(UserId, UserRecord)>) {
+        let snapshot = match self.snapshot.take() {
+            Some(s) if s.id == snapshot_id => s,
+            other => {
+                self.snapshot = other;
+                return (Vec::new(), Vec::new());
+            }
+        };
+        self.last_applied_snapshot_id = Some(snapshot_id);
+
+        // Users added since the index took the snapshot may be missing from it, even if they were added
+        // before the first page arrived, so they are kept
+        let users_to_remove: Vec<_> = self
+            .users
+            .iter()
+            .filter(|(u, r)| !snapshot.users.contains(u) && r.added + SNAPSHOT_CLOCK_SKEW_MARGIN < snapshot_id)
+            .map(|(u, _)| *u)
+            .collect();
+        let users_removed = users_to_remove
+            .into_iter()
+            .filter_map(|u| self.users.remove(&u).map(|r| (u, r)))
+            .collect();
+
+        let mut users_added = Vec::new();
+        for user_id in snapshot.users {
+            if let Vacant(e) = self.users.entry(user_id) {
+                e.insert(UserRecord::default());
+                users_added.push(user_id);
+            }
+        }
+
+        (users_added, users_removed)
+    }
+
     pub fn exists(&self, user_id: &UserId) -> bool {
         self.users.contains_key(user_id)
     }
@@ -30,6 +112,11 @@ impl Users {
 
     pub fn update_user_id(&mut self, old_user_id: UserId, new_user_id: UserId) -> bool {
         if let Some(user) = self.users.remove(&old_user_id) {
+            if let Some(snapshot) = self.snapshot.as_mut() {
+                if snapshot.users.remove(&old_user_id) {
+                    snapshot.users.insert(new_user_id);
+                }
+            }
             self.users.insert(new_user_id, user);
             true
         } else {
@@ -42,6 +129,10 @@ impl Users {
 pub struct UserRecord {
     #[serde(alias = "files_uploaded")]
     files_owned: HashMap<FileId, FileStatusInternal>,
+    #[serde(default)]
+    added: TimestampMillis,
+    #[serde(default)]
+    limits: UserLimits,
 }
 
 impl UserRecord {
@@ -49,6 +140,22 @@ impl UserRecord {
         self.files_owned.keys().copied().collect()
     }
 
+    // The number of files in this bucket which the user owns or is uploading
+    pub fn file_count(&self) -> u64 {
+        self.files_owned
+            .values()
+            .filter(|s| !matches!(s, FileStatusInternal::Rejected(_)))
+            .count() as u64
+    }
+
+    pub fn limits(&self) -> &UserLimits {
+        &self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: UserLimits) {
+        self.limits = limits;
+    }
+
     pub fn file_status(&self, file_id: &FileId) -> Option<&FileStatusInternal> {
         self.files_owned.get(file_id)
     }
@@ -70,3 +177,55 @@ pub enum IndexSyncComplete {
     Yes,
     No,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+
+    #[test]
+    fn applying_snapshot_adds_missing_and_removes_stale_users() {
+        let user1 = Principal::from_slice(&[1]);
+        let user2 = Principal::from_slice(&[2]);
+        let user3 = Principal::from_slice(&[3]);
+        let user4 = Principal::from_slice(&[4]);
+
+        let snapshot_id = SNAPSHOT_CLOCK_SKEW_MARGIN + 1;
+        let mut users = Users::default();
+        users.add(user1, 0);
+        users.add(user2, 0);
+
+        assert!(users.add_to_snapshot(snapshot_id, vec![user1]));
+        // Users added while the snapshot is in progress are kept
+        users.add(user4, snapshot_id);
+        assert!(users.add_to_snapshot(snapshot_id, vec![user3]));
+
+        let (added, removed) = users.apply_snapshot(snapshot_id);
+
+        assert_eq!(added, vec![user3]);
+        assert_eq!(removed.into_iter().map(|(u, _)| u).collect::<Vec<_>>(), vec![user2]);
+        assert!(users.exists(&user1) && users.exists(&user3) && users.exists(&user4));
+
+        // Retries of pages from a snapshot which has already been applied are ignored
+        assert!(!users.add_to_snapshot(snapshot_id, vec![user2]));
+    }
+
+    #[test]
+    fn users_looked_up_after_snapshot_taken_are_kept() {
+        let user1 = Principal::from_slice(&[1]);
+        let user2 = Principal::from_slice(&[2]);
+        let snapshot_id = SNAPSHOT_CLOCK_SKEW_MARGIN + 1000;
+
+        let mut users = Users::default();
+        users.add(user1, 0);
+
+        // The user is looked up after the index took the snapshot but before its first page arrives
+        users.add(user2, snapshot_id + 10);
+        assert!(users.add_to_snapshot(snapshot_id, vec![user1]));
+
+        let (_, removed) = users.apply_snapshot(snapshot_id);
+
+        assert!(removed.is_empty());
+        assert!(users.exists(&user1) && users.exists(&user2));
+    }
+}
@@ -0,0 +1,20 @@
+use crate::guards::caller_is_index_canister;
+use crate::{read_state, RuntimeState};
+use bucket_canister::c2c_accessors::{Response::*, *};
+use canister_api_macros::trace;
+use ic_cdk_macros::query;
+
+#[query(guard = "caller_is_index_canister")]
+#[trace]
+fn c2c_accessors(args: Args) -> Response {
+    read_state(|state| c2c_accessors_impl(args, state))
+}
+
+fn c2c_accessors_impl(args: Args, runtime_state: &RuntimeState) -> Response {
+    let accessors = runtime_state
+        .data
+        .files
+        .accessors(args.start_after, args.max_results as usize);
+
+    Success(SuccessResult { accessors })
+}
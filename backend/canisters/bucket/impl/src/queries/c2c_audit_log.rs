@@ -0,0 +1,25 @@
+use crate::guards::caller_is_index_canister;
+use crate::{read_state, RuntimeState};
+use bucket_canister::c2c_audit_log::{Response::*, *};
+use canister_api_macros::trace;
+use ic_cdk_macros::query;
+
+const MAX_RESULTS_LIMIT: u32 = 1000;
+
+#[query(guard = "caller_is_index_canister")]
+#[trace]
+fn c2c_audit_log(args: Args) -> Response {
+    read_state(|state| c2c_audit_log_impl(args, state))
+}
+
+fn c2c_audit_log_impl(args: Args, runtime_state: &RuntimeState) -> Response {
+    let max_results = args.max_results.min(MAX_RESULTS_LIMIT) as usize;
+    let page = runtime_state
+        .audit_log
+        .query(args.file_id, args.user_id, args.before, max_results);
+
+    Success(SuccessResult {
+        records: page.records,
+        next_cursor: page.next_cursor,
+    })
+}
@@ -0,0 +1,22 @@
+use crate::guards::caller_is_index_canister;
+use crate::{read_state, RuntimeState};
+use bucket_canister::c2c_files::{Response::*, *};
+use canister_api_macros::trace;
+use ic_cdk_macros::query;
+
+#[query(guard = "caller_is_index_canister")]
+#[trace]
+fn c2c_files(args: Args) -> Response {
+    read_state(|state| c2c_files_impl(args, state))
+}
+
+fn c2c_files_impl(args: Args, runtime_state: &RuntimeState) -> Response {
+    let data = &runtime_state.data;
+
+    Success(SuccessResult {
+        files: data.files.summaries(args.start_after, args.max_results as usize),
+        index_sync_pending: !data.index_sync_state.is_idle(),
+        last_sequence_number_sent: data.index_sync_state.last_sequence_number(),
+        last_sequence_number_received: data.index_sync_receiver.last_sequence_number(),
+    })
+}
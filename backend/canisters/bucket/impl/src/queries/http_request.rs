@@ -1,39 +1,86 @@
-use crate::{calc_chunk_count, read_state, RuntimeState, LOG_MESSAGES};
+use crate::{calc_chunk_count, read_state, Metrics, RuntimeState, LOG_MESSAGES};
 use candid::Func;
 use canister_logger::LogMessagesContainer;
 use http_request::{
-    encode_logs, extract_route, get_metrics, HeaderField, HttpRequest, HttpResponse, Route, StreamingCallbackHttpResponse,
-    StreamingStrategy, Token,
+    accepts_prometheus_metrics, encode_logs, extract_route, get_metrics, get_prometheus_metrics, HeaderField, HttpRequest,
+    HttpResponse, LogQuery, PrometheusEncoder, PrometheusMetrics, Route, StreamingCallbackHttpResponse, StreamingStrategy,
+    Token,
 };
 use ic_cdk_macros::query;
 use num_traits::cast::ToPrimitive;
 use serde_bytes::ByteBuf;
 use std::borrow::Cow;
 use std::cmp::min;
-use types::{FileId, TimestampMillis};
+use types::FileId;
 
 const BLOB_RESPONSE_CHUNK_SIZE_BYTES: u32 = 1 << 19; // 1/2 MB
 const CACHE_HEADER_VALUE: &str = "public, max-age=100000000, immutable";
 
 #[query]
 fn http_request(request: HttpRequest) -> HttpResponse {
-    fn get_logs_impl(since: Option<TimestampMillis>, messages_container: &LogMessagesContainer) -> HttpResponse {
-        encode_logs(messages_container.get(since.unwrap_or(0)))
+    fn get_logs_impl(query: LogQuery, messages_container: &LogMessagesContainer) -> HttpResponse {
+        encode_logs(messages_container.query(&query.filter, query.after, query.limit()))
     }
 
     fn get_metrics_impl(runtime_state: &RuntimeState) -> HttpResponse {
         get_metrics(&runtime_state.metrics())
     }
 
+    fn get_prometheus_metrics_impl(runtime_state: &RuntimeState) -> HttpResponse {
+        get_prometheus_metrics(&runtime_state.metrics(), "bucket")
+    }
+
     match extract_route(&request.url) {
         Route::File(file_id) => read_state(|state| start_streaming_file(file_id, state)),
-        Route::Logs(since) => LOG_MESSAGES.with(|l| get_logs_impl(since, &l.borrow().logs)),
-        Route::Traces(since) => LOG_MESSAGES.with(|l| get_logs_impl(since, &l.borrow().traces)),
+        Route::Logs(query) => LOG_MESSAGES.with(|l| get_logs_impl(query, &l.borrow().logs)),
+        Route::Traces(query) => LOG_MESSAGES.with(|l| get_logs_impl(query, &l.borrow().traces)),
+        Route::Metrics if accepts_prometheus_metrics(&request) => read_state(get_prometheus_metrics_impl),
         Route::Metrics => read_state(get_metrics_impl),
+        Route::PrometheusMetrics => read_state(get_prometheus_metrics_impl),
+        Route::BadRequest(message) => HttpResponse::bad_request(&message),
         _ => HttpResponse::not_found(),
     }
 }
 
+impl PrometheusMetrics for Metrics {
+    fn encode(&self, encoder: &mut PrometheusEncoder) {
+        encoder
+            .gauge("memory_used_bytes", "Memory used by the canister", self.memory_used as f64)
+            .gauge("cycles_balance", "Cycles balance of the canister", self.cycles_balance as f64)
+            .gauge_with_labels(
+                "wasm_version_info",
+                "Wasm version of the canister",
+                &[("version", &self.wasm_version.to_string())],
+                1.0,
+            )
+            .gauge("file_count", "Number of files", self.file_count as f64)
+            .gauge("blob_count", "Number of distinct blobs", self.blob_count as f64)
+            .gauge(
+                "index_sync_queue_length",
+                "Number of events waiting to be synced to the index",
+                self.index_sync_queue_length as f64,
+            )
+            .gauge(
+                "pending_file_removals",
+                "Number of files waiting to be removed",
+                self.pending_file_removals as f64,
+            );
+
+        for (name, value) in self.operations.counters() {
+            encoder.counter(&format!("{}_total", name), name, value as f64);
+        }
+        for (name, histogram) in self.operations.histograms() {
+            encoder.histogram(
+                name,
+                name,
+                &histogram.bucket_bounds,
+                &histogram.bucket_counts,
+                histogram.sum as f64,
+            );
+        }
+    }
+}
+
 #[query]
 fn http_request_streaming_callback(token: Token) -> StreamingCallbackHttpResponse {
     read_state(|state| continue_streaming_file(token, state))
@@ -1,3 +1,6 @@
+mod c2c_accessors;
+mod c2c_audit_log;
+mod c2c_files;
 mod file_info;
 mod file_status;
 mod http_request;
@@ -1,47 +1,90 @@
 use crate::guards::caller_is_index_canister;
-use crate::model::files::RemoveFileResult;
+use crate::lifecycle::jobs;
 use crate::model::index_sync_state::EventToSync;
-use crate::{mutate_state, RuntimeState, MAX_EVENTS_TO_SYNC_PER_BATCH};
+use crate::{mutate_state, RuntimeState};
 use bucket_canister::c2c_sync_index::{Response::*, *};
 use canister_api_macros::trace;
 use ic_cdk_macros::update;
-use types::FileRemoved;
+use utils::sync_receiver::SyncCheckResult;
+
+const INSTRUCTION_LIMIT: u64 = 1_000_000_000;
 
 #[update(guard = "caller_is_index_canister")]
 #[trace]
 fn c2c_sync_index(args: Args) -> Response {
-    mutate_state(|state| c2c_sync_index_impl(args, state))
+    // Any files removed as a result of the batch are given the batch's correlation id
+    canister_logger::with_correlation_id(args.correlation_id.clone(), || {
+        mutate_state(|state| c2c_sync_index_impl(args, state))
+    })
 }
 
 fn c2c_sync_index_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
+    let sequence_number = args.sequence_number;
+
+    match runtime_state.data.index_sync_receiver.check(sequence_number) {
+        SyncCheckResult::Apply => {}
+        SyncCheckResult::Duplicate(result) => return Success(result),
+        SyncCheckResult::OutOfOrder(last_applied) => return OutOfOrder(last_applied),
+    }
+
+    if let Some(config) = args.config {
+        runtime_state.data.config = config;
+    }
+
+    if let Some(log_settings) = args.log_settings {
+        canister_logger::apply_settings(&log_settings);
+        runtime_state.data.log_settings = log_settings;
+    }
+
+    let now = runtime_state.env.now();
+
     for user_id in args.users_added {
-        runtime_state.data.users.add(user_id);
+        runtime_state.data.users.add(user_id, now);
     }
 
-    let mut files_removed: Vec<FileRemoved> = Vec::new();
+    for (user_id, limits) in args.user_limits_updated {
+        if let Some(user) = runtime_state.data.users.get_mut(&user_id) {
+            user.set_limits(limits);
+        }
+    }
 
     for user_id in args.users_removed {
         if let Some(user) = runtime_state.data.users.remove(&user_id) {
-            for file_id in user.files_owned() {
-                if let RemoveFileResult::Success(b) = runtime_state.data.files.remove(user_id, file_id) {
-                    files_removed.push(b)
-                }
-            }
+            runtime_state
+                .data
+                .pending_removals
+                .enqueue_user_files(user_id, user.files_owned());
         }
     }
 
     for accessor_id in args.accessors_removed {
-        files_removed.extend(runtime_state.data.files.remove_accessor(&accessor_id));
+        let file_ids = runtime_state.data.files.take_accessor_file_ids(&accessor_id);
+        runtime_state
+            .data
+            .pending_removals
+            .enqueue_accessor_files(accessor_id, file_ids);
+    }
+
+    // Removing a large number of files can exceed the instruction limit, so only as many files as fit
+    // within the budget are removed now, the rest are removed by the 'process_pending_removals' job
+    let now = runtime_state.env.now();
+    let mut files_removed = runtime_state
+        .data
+        .process_pending_removals(INSTRUCTION_LIMIT, &mut runtime_state.audit_log, now);
+    if !runtime_state.data.pending_removals.is_empty() {
+        jobs::process_pending_removals::trigger();
     }
 
-    if files_removed.len() > MAX_EVENTS_TO_SYNC_PER_BATCH {
+    let max_events = runtime_state.data.config.max_events_to_sync_per_batch as usize;
+    if files_removed.len() > max_events {
         // If there are too many events to sync in a single batch, queue the excess events to be
-        // synced later via heartbeat
-        let excess = files_removed.split_off(MAX_EVENTS_TO_SYNC_PER_BATCH);
+        // synced later by the 'sync_index' job
+        let excess = files_removed.split_off(max_events);
 
         for removed in excess {
             runtime_state.data.index_sync_state.enqueue(EventToSync::FileRemoved(removed));
         }
+        jobs::sync_index::trigger();
     }
 
     for (old_user_id, new_user_id) in args.user_ids_updated {
@@ -50,9 +93,12 @@ fn c2c_sync_index_impl(args: Args, runtime_state: &mut RuntimeState) -> Response
             for file_id in user.files_owned() {
                 runtime_state.data.files.update_owner(&file_id, new_user_id);
             }
-            runtime_state.data.files.update_accessor_id(old_user_id, new_user_id);
         }
+        // The user may only be an accessor of files in this bucket without ever having used it
+        runtime_state.data.files.update_accessor_id(old_user_id, new_user_id);
     }
 
-    Success(SuccessResult { files_removed })
+    let result = SuccessResult { files_removed };
+    runtime_state.data.index_sync_receiver.record(sequence_number, result.clone());
+    Success(result)
 }
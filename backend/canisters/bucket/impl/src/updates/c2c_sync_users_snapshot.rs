@@ -0,0 +1,40 @@
+use crate::guards::caller_is_index_canister;
+use crate::lifecycle::jobs;
+use crate::{mutate_state, RuntimeState};
+use bucket_canister::c2c_sync_users_snapshot::{Response::*, *};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+
+#[update(guard = "caller_is_index_canister")]
+#[trace]
+fn c2c_sync_users_snapshot(args: Args) -> Response {
+    mutate_state(|state| c2c_sync_users_snapshot_impl(args, state))
+}
+
+fn c2c_sync_users_snapshot_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
+    let users = &mut runtime_state.data.users;
+    if !users.add_to_snapshot(args.snapshot_id, args.users) || !args.is_last_page {
+        return Success(SuccessResult::default());
+    }
+
+    let (users_added, users_removed) = users.apply_snapshot(args.snapshot_id);
+
+    let result = SuccessResult {
+        users_added: users_added.len() as u32,
+        users_removed: users_removed.len() as u32,
+    };
+
+    // The files of removed users are removed by the 'process_pending_removals' job which then syncs
+    // the removals to the index
+    for (user_id, user) in users_removed {
+        runtime_state
+            .data
+            .pending_removals
+            .enqueue_user_files(user_id, user.files_owned());
+    }
+    if !runtime_state.data.pending_removals.is_empty() {
+        jobs::process_pending_removals::trigger();
+    }
+
+    Success(result)
+}
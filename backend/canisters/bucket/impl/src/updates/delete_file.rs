@@ -1,6 +1,8 @@
+use crate::lifecycle::jobs;
 use crate::model::files::RemoveFileResult;
 use crate::model::index_sync_state::EventToSync;
 use crate::{mutate_state, RuntimeState};
+use bucket_canister::c2c_audit_log::AuditOperation;
 use bucket_canister::delete_file::{Response::*, *};
 use canister_api_macros::trace;
 use ic_cdk_macros::update;
@@ -13,10 +15,20 @@ fn delete_file(args: Args) -> Response {
 
 fn delete_file_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
     let caller = runtime_state.env.caller();
+    let now = runtime_state.env.now();
 
     match runtime_state.data.files.remove(caller, args.file_id) {
         RemoveFileResult::Success(f) => {
+            runtime_state.audit_log.record(
+                now,
+                f.file_id,
+                f.owner,
+                AuditOperation::Deleted(caller),
+                f.correlation_id.clone(),
+            );
             runtime_state.data.index_sync_state.enqueue(EventToSync::FileRemoved(f));
+            runtime_state.data.operations.increment("files_deleted");
+            jobs::sync_index::trigger();
 
             Success
         }
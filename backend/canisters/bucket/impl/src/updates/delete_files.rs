@@ -1,6 +1,8 @@
+use crate::lifecycle::jobs;
 use crate::model::files::RemoveFileResult;
 use crate::model::index_sync_state::EventToSync;
 use crate::{mutate_state, RuntimeState};
+use bucket_canister::c2c_audit_log::AuditOperation;
 use bucket_canister::delete_files::*;
 use canister_api_macros::trace;
 use ic_cdk_macros::update;
@@ -13,6 +15,7 @@ fn delete_files(args: Args) -> Response {
 
 fn delete_files_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
     let caller = runtime_state.env.caller();
+    let now = runtime_state.env.now();
 
     let mut success = Vec::new();
     let mut failures = Vec::new();
@@ -20,7 +23,16 @@ fn delete_files_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
     for file_id in args.file_ids {
         match runtime_state.data.files.remove(caller, file_id) {
             RemoveFileResult::Success(f) => {
+                runtime_state.audit_log.record(
+                    now,
+                    f.file_id,
+                    f.owner,
+                    AuditOperation::Deleted(caller),
+                    f.correlation_id.clone(),
+                );
                 runtime_state.data.index_sync_state.enqueue(EventToSync::FileRemoved(f));
+                runtime_state.data.operations.increment("files_deleted");
+                jobs::sync_index::trigger();
                 success.push(file_id);
             }
             RemoveFileResult::NotAuthorized => {
@@ -1,15 +1,23 @@
-use crate::guards::caller_is_known_user;
+use crate::guards::{ensure_caller_is_known_user, KnownUserCheck};
+use crate::lifecycle::jobs;
 use crate::model::files::ForwardFileResult;
 use crate::model::index_sync_state::EventToSync;
 use crate::model::users::{FileStatusInternal, IndexSyncComplete};
 use crate::{mutate_state, RuntimeState};
+use bucket_canister::c2c_audit_log::{AuditOperation, ForwardedFrom};
 use bucket_canister::forward_file::{Response::*, *};
 use canister_api_macros::trace;
 use ic_cdk_macros::update;
 
-#[update(guard = "caller_is_known_user")]
+#[update]
 #[trace]
-fn forward_file(args: Args) -> Response {
+async fn forward_file(args: Args) -> Response {
+    match ensure_caller_is_known_user().await {
+        KnownUserCheck::Known => {}
+        KnownUserCheck::NotFound => return NotAuthorized,
+        KnownUserCheck::TryAgainLater => return TryAgainLater,
+    }
+
     mutate_state(|state| forward_file_impl(args, state))
 }
 
@@ -25,9 +33,23 @@ fn forward_file_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
         .forward(caller, args.file_id, new_file_id, accessors, now)
     {
         ForwardFileResult::Success(f) => {
+            let forwarded_from = ForwardedFrom {
+                file_id: args.file_id,
+                owner: runtime_state.data.files.owner(&args.file_id).unwrap(),
+            };
+            runtime_state.audit_log.record(
+                now,
+                new_file_id,
+                caller,
+                AuditOperation::Forwarded(forwarded_from),
+                f.correlation_id.clone(),
+            );
+
             let user = runtime_state.data.users.get_mut(&caller).unwrap();
             user.set_file_status(new_file_id, FileStatusInternal::Complete(IndexSyncComplete::No));
             runtime_state.data.index_sync_state.enqueue(EventToSync::FileAdded(f));
+            runtime_state.data.operations.increment("files_forwarded");
+            jobs::sync_index::trigger();
             Success(new_file_id)
         }
         ForwardFileResult::NotAuthorized => NotAuthorized,
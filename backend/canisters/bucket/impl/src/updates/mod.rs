@@ -1,4 +1,5 @@
 mod c2c_sync_index;
+mod c2c_sync_users_snapshot;
 mod delete_file;
 mod delete_files;
 mod forward_file;
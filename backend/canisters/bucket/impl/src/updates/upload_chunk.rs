@@ -1,16 +1,29 @@
-use crate::guards::caller_is_known_user;
+use crate::guards::{ensure_caller_is_known_user, KnownUserCheck};
+use crate::lifecycle::jobs;
 use crate::model::files::{PutChunkArgs, PutChunkResult};
 use crate::model::index_sync_state::EventToSync;
 use crate::model::users::{FileStatusInternal, IndexSyncComplete};
 use crate::{mutate_state, RuntimeState};
+use bucket_canister::c2c_audit_log::AuditOperation;
 use bucket_canister::upload_chunk_v2::{Response::*, *};
 use canister_api_macros::trace;
+use canister_logger::current_correlation_id;
 use ic_cdk_macros::update;
-use types::{FileRemoved, RejectedReason, UserId};
+use types::{FileRejectedReason, FileRemoved, RejectedReason, UserId};
+use utils::metrics::SIZE_BUCKETS_BYTES;
 
-#[update(guard = "caller_is_known_user")]
+#[update]
 #[trace]
-fn upload_chunk_v2(args: Args) -> Response {
+async fn upload_chunk_v2(args: Args) -> Response {
+    match ensure_caller_is_known_user().await {
+        KnownUserCheck::Known => {}
+        KnownUserCheck::NotFound => {
+            mutate_state(|state| state.data.operations.increment("uploads_rejected"));
+            return UserNotFound;
+        }
+        KnownUserCheck::TryAgainLater => return TryAgainLater,
+    }
+
     mutate_state(|state| upload_chunk_impl(args, state))
 }
 
@@ -19,6 +32,8 @@ fn upload_chunk_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
     let now = runtime_state.env.now();
     let user = runtime_state.data.users.get_mut(&user_id).unwrap();
     let file_id = args.file_id;
+    let chunk_size = args.bytes.len() as u64;
+    let total_size = args.total_size;
 
     let mut index_sync_complete = IndexSyncComplete::No;
     if let Some(status) = user.file_status(&file_id) {
@@ -28,27 +43,63 @@ fn upload_chunk_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
             }
             FileStatusInternal::Rejected(RejectedReason::AllowanceExceeded) => return AllowanceExceeded,
             FileStatusInternal::Rejected(RejectedReason::UserNotFound) => return UserNotFound,
+            FileStatusInternal::Rejected(RejectedReason::FileCountExceeded) => return FileCountExceeded,
+            FileStatusInternal::Rejected(RejectedReason::FileTooBig) => return FileTooBig,
+            FileStatusInternal::Rejected(RejectedReason::MimeTypeNotAllowed) => return MimeTypeNotAllowed,
             FileStatusInternal::Uploading(c) => index_sync_complete = *c,
         }
     } else {
+        // The index has the final say since it knows about the user's files in other buckets, but
+        // checking here avoids storing files which the index would then reject
+        if let Err(reason) = user
+            .limits()
+            .check_new_file(user.file_count(), total_size, Some(&args.mime_type))
+        {
+            runtime_state.data.operations.increment("uploads_rejected");
+            return match reason {
+                FileRejectedReason::AllowanceExceeded => AllowanceExceeded,
+                FileRejectedReason::UserNotFound => UserNotFound,
+                FileRejectedReason::FileCountExceeded => FileCountExceeded,
+                FileRejectedReason::FileTooBig => FileTooBig,
+                FileRejectedReason::MimeTypeNotAllowed => MimeTypeNotAllowed,
+            };
+        }
         user.set_file_status(file_id, FileStatusInternal::Uploading(IndexSyncComplete::No));
+        runtime_state.data.operations.increment("uploads_started");
     }
 
-    match runtime_state.data.files.put_chunk(PutChunkArgs::new(user_id, args, now)) {
+    let max_blob_size_bytes = runtime_state.data.config.max_blob_size_bytes;
+
+    match runtime_state
+        .data
+        .files
+        .put_chunk(PutChunkArgs::new(user_id, args, now), max_blob_size_bytes)
+    {
         PutChunkResult::Success(r) => {
+            let operations = &mut runtime_state.data.operations;
+            operations.increment_by("bytes_uploaded", chunk_size);
             if r.file_completed {
                 user.set_file_status(file_id, FileStatusInternal::Complete(index_sync_complete));
+                operations.increment("uploads_completed");
+                operations.observe("uploaded_file_size_bytes", SIZE_BUCKETS_BYTES, total_size);
+                runtime_state
+                    .audit_log
+                    .record(now, file_id, user_id, AuditOperation::Uploaded, current_correlation_id());
             }
             if let Some(file_added) = r.file_added {
                 runtime_state
                     .data
                     .index_sync_state
                     .enqueue(EventToSync::FileAdded(file_added));
+                jobs::sync_index::trigger();
             }
             Success
         }
         PutChunkResult::FileAlreadyExists => FileAlreadyExists,
-        PutChunkResult::FileTooBig(_) => FileTooBig,
+        PutChunkResult::FileTooBig(_) => {
+            runtime_state.data.operations.increment("uploads_rejected");
+            FileTooBig
+        }
         PutChunkResult::ChunkAlreadyExists => ChunkAlreadyExists,
         PutChunkResult::ChunkIndexTooHigh => ChunkIndexTooHigh,
         PutChunkResult::ChunkSizeMismatch(_) => ChunkSizeMismatch,
@@ -57,6 +108,8 @@ fn upload_chunk_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
             // pending files, so we now need to update the status and tell the index canister to
             // remove the file reference.
             user.set_file_status(file_id, FileStatusInternal::Rejected(RejectedReason::HashMismatch));
+            runtime_state.data.operations.increment("uploads_rejected");
+            runtime_state.data.operations.increment("hash_mismatches");
 
             // We only need to remove the file reference from the index canister if this file
             // consists of multiple chunks. If the file is a single chunk then the Success case of
@@ -71,7 +124,10 @@ fn upload_chunk_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
                         owner: user_id,
                         hash: hm.provided_hash,
                         blob_deleted: !runtime_state.data.files.contains_hash(&hm.provided_hash),
+                        correlation_id: canister_logger::current_correlation_id(),
+                        removed_by: None,
                     }));
+                jobs::sync_index::trigger();
             }
 
             HashMismatch
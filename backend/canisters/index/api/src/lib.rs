@@ -1,10 +1,14 @@
+use bucket_canister::c2c_sync_index;
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
+use types::TimestampMillis;
 
 mod lifecycle;
 mod queries;
 mod updates;
 
+pub mod subscriber;
+
 pub use lifecycle::*;
 pub use queries::*;
 pub use updates::*;
@@ -16,3 +20,13 @@ pub struct ProjectedAllowance {
     pub bytes_used_after_upload: u64,
     pub bytes_used_after_operation: u64,
 }
+
+// A batch of events which repeatedly failed to sync to a bucket and has been set aside so that it no
+// longer blocks the bucket's queue
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DeadLetter {
+    pub id: u64,
+    pub args: c2c_sync_index::Args,
+    pub attempts: u32,
+    pub dead_lettered_at: TimestampMillis,
+}
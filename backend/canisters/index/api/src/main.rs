@@ -2,6 +2,7 @@ use candid_gen::generate_candid_method;
 
 fn main() {
     generate_candid_method!(index, allocated_bucket_v2, query);
+    generate_candid_method!(index, billing_usage, query);
     generate_candid_method!(index, can_forward, query);
     generate_candid_method!(index, user, query);
 
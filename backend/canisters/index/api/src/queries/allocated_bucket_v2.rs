@@ -7,6 +7,9 @@ use types::{CanisterId, Hash};
 pub struct Args {
     pub file_hash: Hash,
     pub file_size: u64,
+    // If set, the file is checked against the MIME types the user is allowed to upload
+    #[serde(default)]
+    pub mime_type: Option<String>,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Debug)]
@@ -15,6 +18,9 @@ pub enum Response {
     AllowanceExceeded(ProjectedAllowance),
     UserNotFound,
     BucketUnavailable,
+    FileTooBig(u64),
+    FileCountExceeded(u32),
+    MimeTypeNotAllowed,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Debug)]
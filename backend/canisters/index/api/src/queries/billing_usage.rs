@@ -0,0 +1,59 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use types::{TimestampMillis, UserId};
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct Args {
+    // The period is rounded down to the usage snapshot interval boundaries (eg. to midnight each day),
+    // other than if `to` is in the future in which case the period runs up until now
+    pub from: TimestampMillis,
+    pub to: TimestampMillis,
+    // Users are returned in order of user id, starting after this user
+    pub after: Option<UserId>,
+    pub max_results: u32,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success(SuccessResult),
+    InvalidPeriod,
+    PeriodNotAvailable(PeriodNotAvailableResult),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct SuccessResult {
+    pub period_start: TimestampMillis,
+    pub period_end: TimestampMillis,
+    pub users: Vec<UserUsage>,
+    // Set if there are more users to be returned, in which case it should be passed as `after`
+    pub next_cursor: Option<UserId>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum UserUsage {
+    Available(UsageOverPeriod),
+    // The user's usage can't be calculated for the period, which happens if the usage snapshots it
+    // needs were never taken, eg. because the snapshot interval or limit has been changed since
+    Unavailable(UnavailableUsage),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct UsageOverPeriod {
+    pub user_id: UserId,
+    // The storage used over the period in byte-milliseconds
+    pub byte_ms: u128,
+    pub average_bytes: u64,
+    pub bytes_used: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct UnavailableUsage {
+    pub user_id: UserId,
+    pub bytes_used: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct PeriodNotAvailableResult {
+    // Usage snapshots older than this have been dropped
+    pub oldest_available: TimestampMillis,
+}
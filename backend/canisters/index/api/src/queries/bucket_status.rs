@@ -0,0 +1,26 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use types::{CanisterId, Hash, TimestampMillis, Version};
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct Args {
+    pub canister_id: CanisterId,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success(BucketStatus),
+    BucketNotFound,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct BucketStatus {
+    pub canister_id: CanisterId,
+    pub wasm_version: Version,
+    pub expected_module_hash: Option<Hash>,
+    pub module_hash: Option<Hash>,
+    pub module_hash_checked: Option<TimestampMillis>,
+    pub module_hash_drift: bool,
+    pub bytes_used: u64,
+    pub upgrade_in_progress: bool,
+}
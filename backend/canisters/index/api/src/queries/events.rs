@@ -0,0 +1,79 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use types::{CanisterId, FileAdded, FileRejected, FileRemoved, TimestampMillis, UserId};
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct Args {
+    // The index of the last event received, or None to start from the oldest event retained
+    pub after: Option<u64>,
+    // If set, only events relating to files in this bucket are returned
+    pub bucket: Option<CanisterId>,
+    pub max_results: u32,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success(SuccessResult),
+    // Events after the cursor have since been dropped, so the caller must resync their state from
+    // scratch and then continue from `latest_index`
+    CursorTooOld(CursorTooOldResult),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct SuccessResult {
+    pub events: Vec<IndexedEvent>,
+    // Pass as `after` to get the next page. This moves past any events which were filtered out.
+    pub next_cursor: Option<u64>,
+    pub caught_up: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct CursorTooOldResult {
+    pub oldest_index: u64,
+    pub latest_index: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct IndexedEvent {
+    pub index: u64,
+    pub timestamp: TimestampMillis,
+    pub bucket: Option<CanisterId>,
+    pub event: Event,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum Event {
+    FileAdded(FileAdded),
+    FileRemoved(FileRemoved),
+    FileRejected(FileRejected),
+    UserAdded(UserId),
+    UserRemoved(UserId),
+    UserIdUpdated(UserIdUpdated),
+    QuotaThresholdCrossed(QuotaThresholdCrossed),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct UserIdUpdated {
+    pub old_user_id: UserId,
+    pub new_user_id: UserId,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct QuotaThresholdCrossed {
+    pub user_id: UserId,
+    // The percentage of the user's byte limit which has been reached
+    pub threshold: u8,
+    pub bytes_used: u64,
+    pub byte_limit: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EventType {
+    FileAdded,
+    FileRemoved,
+    FileRejected,
+    UserAdded,
+    UserRemoved,
+    UserIdUpdated,
+    QuotaThresholdCrossed,
+}
@@ -0,0 +1,38 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use types::TimestampMillis;
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct Args {
+    pub traces: bool,
+    pub since: Option<TimestampMillis>,
+    // Only messages at this level or more severe are returned, eg. "warn"
+    pub level: Option<String>,
+    pub contains: Option<String>,
+    pub fields: Vec<(String, String)>,
+    // The `next_cursor` of the previous page
+    pub after: Option<u64>,
+    pub max_results: u32,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success(SuccessResult),
+    InvalidLevel,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct SuccessResult {
+    pub messages: Vec<LogEntry>,
+    pub next_cursor: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct LogEntry {
+    pub index: u64,
+    pub timestamp: TimestampMillis,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+    pub fields: Vec<(String, String)>,
+}
@@ -1,3 +1,11 @@
 pub mod allocated_bucket_v2;
+pub mod billing_usage;
+pub mod bucket_status;
+pub mod bucket_sync_dead_letters;
 pub mod can_forward;
+pub mod events;
+pub mod logs;
+pub mod reconciliation_report;
+pub mod subscribers;
 pub mod user;
+pub mod users_near_limit;
@@ -0,0 +1,36 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use types::{CanisterId, Hash, TimestampMillis, UserId};
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct Args {}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success(ReconciliationReport),
+    NoReport,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ReconciliationReport {
+    pub started: TimestampMillis,
+    pub completed: Option<TimestampMillis>,
+    pub repair: bool,
+    pub buckets_checked: u32,
+    // Buckets which couldn't be reached or whose files changed while they were being scanned
+    pub buckets_skipped: Vec<CanisterId>,
+    pub discrepancy_count: u64,
+    // Capped in size so may not contain every discrepancy found
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Discrepancy {
+    pub bucket: CanisterId,
+    pub hash: Hash,
+    pub owner: UserId,
+    pub index_reference_count: u32,
+    pub bucket_reference_count: u32,
+    pub index_size: Option<u64>,
+    pub bucket_size: Option<u64>,
+}
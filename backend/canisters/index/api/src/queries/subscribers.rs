@@ -0,0 +1,29 @@
+use crate::events::EventType;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use types::{CanisterId, TimestampMillis, UserId};
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct Args {}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success(Vec<Subscriber>),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct Subscriber {
+    pub canister_id: CanisterId,
+    pub event_types: Vec<EventType>,
+    pub users: Option<Vec<UserId>>,
+    pub queue_length: u32,
+    pub failed_attempts: u32,
+    pub next_retry_at: Option<TimestampMillis>,
+    // The number of batches which were dropped after failing too many times
+    pub batches_dropped: u64,
+    // The index of the last event delivered, from which the subscriber can catch up on any dropped
+    // events using the event feed
+    pub last_delivered_index: Option<u64>,
+    // The number of queued events which were dropped because they had been pruned from the event feed
+    pub events_expired: u64,
+}
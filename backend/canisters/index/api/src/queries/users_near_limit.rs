@@ -0,0 +1,25 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use types::UserId;
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct Args {
+    // If set, only users who have reached at least this percentage of their limit are returned,
+    // otherwise every user who has reached any of the configured thresholds is returned
+    pub min_threshold: Option<u8>,
+    pub max_results: u32,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success(Vec<UserNearLimit>),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct UserNearLimit {
+    pub user_id: UserId,
+    pub bytes_used: u64,
+    pub byte_limit: u64,
+    // The highest configured threshold which the user has reached
+    pub threshold: u8,
+}
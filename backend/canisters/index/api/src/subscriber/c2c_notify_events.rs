@@ -0,0 +1,18 @@
+use crate::events::IndexedEvent;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct Args {
+    // The events in the order they were added to the event feed. A batch may be delivered more than
+    // once if the index doesn't receive the response, so subscribers should ignore any events whose
+    // index they have already seen.
+    pub events: Vec<IndexedEvent>,
+    #[serde(default)]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success,
+}
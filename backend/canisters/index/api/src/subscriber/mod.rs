@@ -0,0 +1,2 @@
+// Methods which subscriber canisters must implement in order to be notified of events
+pub mod c2c_notify_events;
@@ -11,6 +11,13 @@ pub struct Args {
 pub struct UserConfig {
     pub user_id: UserId,
     pub byte_limit: u64,
+    #[serde(default)]
+    pub max_files: Option<u32>,
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+    // Either exact MIME types or wildcards such as "image/*"
+    #[serde(default)]
+    pub allowed_mime_types: Option<Vec<String>>,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Debug)]
@@ -0,0 +1,27 @@
+use bucket_canister::c2c_audit_log::AuditRecord;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use types::{CanisterId, FileId, UserId};
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct Args {
+    pub canister_id: CanisterId,
+    pub file_id: Option<FileId>,
+    pub user_id: Option<UserId>,
+    pub before: Option<u64>,
+    pub max_results: u32,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success(SuccessResult),
+    BucketNotFound,
+    InternalError(String),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct SuccessResult {
+    // Newest first
+    pub records: Vec<AuditRecord>,
+    pub next_cursor: Option<u64>,
+}
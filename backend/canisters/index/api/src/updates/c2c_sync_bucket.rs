@@ -1,20 +1,28 @@
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
-use types::{FileAdded, FileRejected, FileRemoved};
+use types::{AccessorId, FileAdded, FileRejected, FileRemoved};
 
 #[derive(CandidType, Serialize, Deserialize, Debug)]
 pub struct Args {
     pub files_added: Vec<FileAdded>,
     pub files_removed: Vec<FileRemoved>,
+    #[serde(default)]
+    pub accessors_unlinked: Vec<AccessorId>,
     pub bytes_remaining: i64,
+    #[serde(default)]
+    pub sequence_number: u64,
+    // Retries of the batch keep the same correlation id
+    #[serde(default)]
+    pub correlation_id: Option<String>,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Debug)]
 pub enum Response {
     Success(SuccessResult),
+    OutOfOrder(u64),
 }
 
-#[derive(CandidType, Serialize, Deserialize, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct SuccessResult {
     pub files_rejected: Vec<FileRejected>,
 }
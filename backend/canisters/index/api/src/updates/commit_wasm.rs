@@ -0,0 +1,26 @@
+use crate::update_bucket_canister_wasm::Canary;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use types::{CanisterId, Hash, Version};
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct Args {
+    pub version: Version,
+    pub compressed: bool,
+    pub sha256: Hash,
+    #[serde(default)]
+    pub canary: Option<Canary>,
+    #[serde(default)]
+    pub max_failures: Option<u32>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success,
+    NoWasmStaged,
+    VersionMismatch(Version),
+    HashMismatch(Hash),
+    VersionNotHigher,
+    InvalidCanaryPercentage,
+    CanaryBucketNotFound(CanisterId),
+}
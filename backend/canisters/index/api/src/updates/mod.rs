@@ -1,8 +1,25 @@
 pub mod add_or_update_users;
 pub mod add_service_principals;
+pub mod bucket_audit_log;
+pub mod c2c_lookup_user;
 pub mod c2c_notify_low_balance;
 pub mod c2c_sync_bucket;
+pub mod commit_wasm;
+pub mod discard_bucket_sync_dead_letter;
+pub mod pause_bucket_upgrades;
 pub mod remove_accessor;
+pub mod remove_subscriber;
 pub mod remove_user;
+pub mod replay_bucket_sync_dead_letter;
+pub mod resume_bucket_upgrades;
+pub mod resync_bucket_users;
+pub mod retry_failed_bucket_upgrades;
+pub mod rollback_bucket_canister_wasm;
+pub mod set_config;
+pub mod set_log_settings;
+pub mod set_subscriber;
+pub mod split_bucket_sync_dead_letter;
+pub mod start_reconciliation;
 pub mod update_bucket_canister_wasm;
 pub mod update_user_id;
+pub mod upload_wasm_chunk;
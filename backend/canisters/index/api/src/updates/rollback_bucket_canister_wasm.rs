@@ -0,0 +1,12 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use types::Version;
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct Args {}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success(Version),
+    NoPreviousWasm,
+}
@@ -0,0 +1,29 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use types::{BucketConfig, Cycles, Milliseconds};
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct Args {
+    pub target_active_buckets: Option<u32>,
+    pub bucket_pool_target_size: Option<u16>,
+    pub chunk_size_bytes: Option<u32>,
+    pub max_events_to_sync_per_batch: Option<u32>,
+    pub max_concurrent_canister_upgrades: Option<u32>,
+    pub max_sync_attempts: Option<u32>,
+    pub max_feed_events: Option<u32>,
+    pub feed_retention: Option<Milliseconds>,
+    pub quota_alert_thresholds: Option<Vec<u8>>,
+    pub usage_snapshot_interval: Option<Milliseconds>,
+    pub max_usage_snapshots: Option<u32>,
+    pub min_cycles_balance_for_bucket_creation: Option<Cycles>,
+    pub min_cycles_balance_for_top_ups: Option<Cycles>,
+    pub bucket_canister_initial_cycles_balance: Option<Cycles>,
+    pub bucket_canister_top_up_amount: Option<Cycles>,
+    pub bucket: Option<BucketConfig>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success,
+    InvalidConfig(String),
+}
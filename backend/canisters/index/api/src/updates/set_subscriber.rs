@@ -0,0 +1,19 @@
+use crate::events::EventType;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use types::{CanisterId, UserId};
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct Args {
+    // The canister to notify, which must implement `c2c_notify_events`
+    pub canister_id: CanisterId,
+    pub event_types: Vec<EventType>,
+    // If set, only events relating to these users are sent
+    pub users: Option<Vec<UserId>>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success,
+    NoEventTypes,
+}
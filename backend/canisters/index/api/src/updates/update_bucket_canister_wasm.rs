@@ -1,14 +1,28 @@
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
-use types::CanisterWasm;
+use types::{CanisterId, CanisterWasm};
 
 #[derive(CandidType, Serialize, Deserialize, Debug)]
 pub struct Args {
     pub bucket_canister_wasm: CanisterWasm,
+    #[serde(default)]
+    pub canary: Option<Canary>,
+    #[serde(default)]
+    pub max_failures: Option<u32>,
+}
+
+// The buckets to upgrade first, the remaining buckets are only upgraded once all of these have been
+// upgraded successfully
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Canary {
+    Percentage(u8),
+    Buckets(Vec<CanisterId>),
 }
 
 #[derive(CandidType, Serialize, Deserialize, Debug)]
 pub enum Response {
     Success,
     VersionNotHigher,
+    InvalidCanaryPercentage,
+    CanaryBucketNotFound(CanisterId),
 }
@@ -0,0 +1,29 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use std::fmt::{Debug, Formatter};
+use types::Version;
+
+#[derive(CandidType, Serialize, Deserialize)]
+pub struct Args {
+    pub version: Version,
+    pub chunk_index: u32,
+    pub bytes: ByteBuf,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub enum Response {
+    Success,
+    UnexpectedChunkIndex(u32),
+    WasmTooLarge(u64),
+}
+
+impl Debug for Args {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Args")
+            .field("version", &self.version)
+            .field("chunk_index", &self.chunk_index)
+            .field("byte_length", &self.bytes.len())
+            .finish()
+    }
+}
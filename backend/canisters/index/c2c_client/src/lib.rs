@@ -3,14 +3,31 @@ use index_canister::*;
 
 // Queries
 generate_c2c_call!(allocated_bucket_v2);
+generate_c2c_call!(bucket_status);
 generate_c2c_call!(user);
 
 // Updates
 generate_c2c_call!(add_or_update_users);
 generate_c2c_call!(add_service_principals);
+generate_c2c_call!(c2c_lookup_user);
 generate_c2c_call!(c2c_notify_low_balance);
 generate_c2c_call!(c2c_sync_bucket);
+generate_c2c_call!(commit_wasm);
+generate_c2c_call!(pause_bucket_upgrades);
 generate_c2c_call!(remove_accessor);
 generate_c2c_call!(remove_user);
+generate_c2c_call!(resume_bucket_upgrades);
+generate_c2c_call!(retry_failed_bucket_upgrades);
+generate_c2c_call!(rollback_bucket_canister_wasm);
+generate_c2c_call!(set_config);
 generate_c2c_call!(update_bucket_canister_wasm);
 generate_c2c_call!(update_user_id);
+generate_c2c_call!(upload_wasm_chunk);
+
+// Methods implemented by subscriber canisters
+pub mod subscriber {
+    use canister_client_macros::*;
+    use index_canister::subscriber::*;
+
+    generate_c2c_call!(c2c_notify_events);
+}
@@ -2,6 +2,29 @@ use canister_client_macros::*;
 use index_canister::*;
 
 // Queries
+generate_query_call!(billing_usage);
+generate_query_call!(bucket_status);
+generate_query_call!(bucket_sync_dead_letters);
+generate_query_call!(events);
+generate_query_call!(logs);
+generate_query_call!(reconciliation_report);
+generate_query_call!(subscribers);
+generate_query_call!(users_near_limit);
 
 // Updates
+generate_update_call!(bucket_audit_log);
+generate_update_call!(commit_wasm);
+generate_update_call!(discard_bucket_sync_dead_letter);
+generate_update_call!(pause_bucket_upgrades);
+generate_update_call!(remove_subscriber);
+generate_update_call!(replay_bucket_sync_dead_letter);
+generate_update_call!(resume_bucket_upgrades);
+generate_update_call!(resync_bucket_users);
+generate_update_call!(retry_failed_bucket_upgrades);
+generate_update_call!(rollback_bucket_canister_wasm);
+generate_update_call!(set_log_settings);
+generate_update_call!(set_subscriber);
+generate_update_call!(split_bucket_sync_dead_letter);
+generate_update_call!(start_reconciliation);
 generate_update_call!(update_bucket_canister_wasm);
+generate_update_call!(upload_wasm_chunk);
@@ -1,18 +1,33 @@
+use crate::model::accessors_backfill::AccessorsBackfill;
 use crate::model::blobs::Blobs;
-use crate::model::buckets::{BucketRecord, Buckets};
+use crate::model::bucket_sync_state::BucketSyncMetrics;
+use crate::model::bucket_upgrade_rollout::{BucketUpgradeRollout, BucketUpgradeRolloutStatus};
+use crate::model::buckets::{BucketRecord, Buckets, TargetedSyncMetrics};
+use crate::model::config::Config;
+use crate::model::event_feed::EventFeed;
+use crate::model::quotas::highest_threshold_reached;
+use crate::model::reconciliation::Reconciliation;
+use crate::model::staged_wasm::StagedWasm;
+use crate::model::subscribers::Subscribers;
+use crate::model::usage::UsageAccrual;
 use candid::{CandidType, Principal};
 use canister_logger::LogMessagesWrapper;
 use canister_state_macros::canister_state;
+use index_canister::events::{Event, QuotaThresholdCrossed};
+use index_canister::reconciliation_report::Discrepancy;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use tracing::{error, info};
 use types::{
-    CanisterId, CanisterWasm, Cycles, FileAdded, FileRejected, FileRejectedReason, FileRemoved, Hash, TimestampMillis,
-    Timestamped, UserId, Version,
+    AccessorId, CanisterId, CanisterWasm, Cycles, FileAdded, FileRejected, FileRejectedReason, FileRemoved, Hash, LogSettings,
+    TimestampMillis, Timestamped, UserId, UserLimits, Version,
 };
 use utils::canister::{CanistersRequiringUpgrade, FailedUpgradeCount};
 use utils::env::Environment;
 use utils::memory;
+use utils::metrics::MetricsRegistry;
 
 mod guards;
 mod lifecycle;
@@ -20,11 +35,6 @@ mod model;
 mod queries;
 mod updates;
 
-const DEFAULT_CHUNK_SIZE_BYTES: u32 = 1 << 19; // 1/2 Mb
-const MAX_EVENTS_TO_SYNC_PER_BATCH: usize = 10000;
-const MIN_CYCLES_BALANCE: Cycles = 10_000_000_000_000; // 10T
-const BUCKET_CANISTER_TOP_UP_AMOUNT: Cycles = 1_000_000_000_000; // 1T
-
 thread_local! {
     static LOG_MESSAGES: RefCell<LogMessagesWrapper> = RefCell::default();
     static WASM_VERSION: RefCell<Timestamped<Version>> = RefCell::default();
@@ -55,22 +65,49 @@ impl RuntimeState {
     pub fn metrics(&self) -> Metrics {
         let blob_metrics = self.data.blobs.metrics();
         let bucket_upgrade_metrics = self.data.canisters_requiring_upgrade.metrics();
+        let now = self.env.now();
 
         Metrics {
             memory_used: memory::used(),
-            now: self.env.now(),
+            now,
             cycles_balance: self.env.cycles_balance(),
             wasm_version: WASM_VERSION.with(|v| **v.borrow()),
             blob_count: blob_metrics.blob_count,
             total_blob_bytes: blob_metrics.total_blob_bytes,
             file_count: blob_metrics.file_count,
             total_file_bytes: blob_metrics.total_file_bytes,
-            active_buckets: self.data.buckets.iter_active_buckets().map(|b| b.into()).collect(),
-            full_buckets: self.data.buckets.iter_full_buckets().map(|b| b.into()).collect(),
+            active_buckets: self
+                .data
+                .buckets
+                .iter_active_buckets()
+                .map(|b| BucketMetrics::new(b, now))
+                .collect(),
+            full_buckets: self
+                .data
+                .buckets
+                .iter_full_buckets()
+                .map(|b| BucketMetrics::new(b, now))
+                .collect(),
+            bucket_pool_size: self.data.buckets.iter_pooled_buckets().count() as u64,
             bucket_upgrades_pending: bucket_upgrade_metrics.pending as u64,
             bucket_upgrades_in_progress: bucket_upgrade_metrics.in_progress as u64,
             bucket_upgrades_failed: bucket_upgrade_metrics.failed,
+            bucket_upgrades_paused: bucket_upgrade_metrics.paused,
+            bucket_upgrade_rollout: self.data.bucket_upgrade_rollout.status(),
             bucket_canister_wasm: self.data.bucket_canister_wasm.version,
+            buckets_with_module_hash_drift: self
+                .data
+                .buckets
+                .iter()
+                .filter(|b| self.data.has_module_hash_drift(b))
+                .map(|b| b.canister_id)
+                .collect(),
+            module_hash_audit_last_run: self.data.module_hash_audit_last_run,
+            previous_bucket_canister_wasm: self.data.previous_bucket_canister_wasm.as_ref().map(|w| w.version),
+            config: self.data.config.clone(),
+            targeted_sync: self.data.buckets.targeted_sync_metrics(),
+            all_accessors_tracked: self.data.all_accessors_tracked,
+            operations: self.data.operations.clone(),
         }
     }
 }
@@ -79,37 +116,175 @@ impl RuntimeState {
 struct Data {
     pub service_principals: HashSet<Principal>,
     pub bucket_canister_wasm: CanisterWasm,
-    pub users: HashMap<UserId, UserRecordInternal>,
+    pub users: BTreeMap<UserId, UserRecordInternal>,
+    // The buckets holding files which each accessor has been given access to
+    #[serde(default)]
+    pub accessors: HashMap<AccessorId, HashSet<CanisterId>>,
+    // False if the state predates users being provisioned on buckets lazily, in which case every user
+    // must be recorded against every bucket since they were all sent every user
+    #[serde(default)]
+    pub user_buckets_seeded: bool,
+    // False if there may be files which were added before accessors were tracked, in which case
+    // events about untracked accessors must still be sent to every bucket
+    #[serde(default)]
+    pub all_accessors_tracked: bool,
+    #[serde(default)]
+    pub accessors_backfill: Option<AccessorsBackfill>,
     pub blobs: Blobs,
     pub buckets: Buckets,
     pub canisters_requiring_upgrade: CanistersRequiringUpgrade,
+    #[serde(default)]
+    pub previous_bucket_canister_wasm: Option<CanisterWasm>,
+    #[serde(default)]
+    pub bucket_upgrade_rollout: BucketUpgradeRollout,
+    #[serde(default)]
+    pub staged_bucket_canister_wasm: StagedWasm,
+    // The sha256 of each version of the bucket canister wasm module, as installed
+    #[serde(default)]
+    pub bucket_canister_wasm_hashes: BTreeMap<Version, Hash>,
+    #[serde(default)]
+    pub module_hash_audit_last_run: TimestampMillis,
+    // The latest boundary at which every user's usage has been snapshotted
+    #[serde(default)]
+    pub usage_snapshots_taken_up_to: TimestampMillis,
+    // The usage of removed users, kept until they were removed before the oldest period which can be
+    // billed so that they can still be billed for the periods in which they held files
+    #[serde(default)]
+    pub removed_users_usage: BTreeMap<UserId, UsageAccrual>,
+    #[serde(default)]
+    pub reconciliation: Reconciliation,
+    #[serde(default)]
+    pub events: EventFeed,
+    #[serde(default)]
+    pub subscribers: Subscribers,
+    // The users whose quota alert threshold is still to be recalculated after the thresholds changed
+    #[serde(default)]
+    pub quota_threshold_updates_pending: VecDeque<UserId>,
+    #[serde(default)]
+    pub operations: MetricsRegistry,
+    #[serde(default)]
+    pub log_settings: LogSettings,
+    // The settings last synced to the buckets, which new buckets are also initialized with
+    #[serde(default)]
+    pub bucket_log_settings: LogSettings,
     pub total_cycles_spent_on_canisters: Cycles,
     pub test_mode: bool,
+    #[serde(default)]
+    pub config: Config,
 }
 
 impl Data {
     fn new(service_principals: Vec<Principal>, bucket_canister_wasm: CanisterWasm, test_mode: bool) -> Data {
-        Data {
+        let mut data = Data {
             service_principals: service_principals.into_iter().collect(),
             bucket_canister_wasm,
-            users: HashMap::new(),
+            users: BTreeMap::new(),
+            accessors: HashMap::new(),
+            user_buckets_seeded: true,
+            all_accessors_tracked: true,
+            accessors_backfill: None,
             blobs: Blobs::default(),
             buckets: Buckets::default(),
             canisters_requiring_upgrade: CanistersRequiringUpgrade::default(),
+            previous_bucket_canister_wasm: None,
+            bucket_upgrade_rollout: BucketUpgradeRollout::default(),
+            staged_bucket_canister_wasm: StagedWasm::default(),
+            bucket_canister_wasm_hashes: BTreeMap::new(),
+            module_hash_audit_last_run: 0,
+            usage_snapshots_taken_up_to: 0,
+            removed_users_usage: BTreeMap::new(),
+            reconciliation: Reconciliation::default(),
+            events: EventFeed::default(),
+            subscribers: Subscribers::default(),
+            quota_threshold_updates_pending: VecDeque::new(),
+            operations: MetricsRegistry::default(),
+            log_settings: LogSettings::default(),
+            bucket_log_settings: LogSettings::default(),
             total_cycles_spent_on_canisters: 0,
             test_mode,
+            config: Config::default(),
+        };
+        data.record_bucket_canister_wasm_hash();
+        data
+    }
+
+    // Adds the event to the event feed, dropping any events which fall outside of the retention limits,
+    // and queues it for any subscribers whose filter it matches. Subscribers' queued events are dropped
+    // along with the events pruned from the feed.
+    pub fn push_event(&mut self, bucket: Option<CanisterId>, event: Event, now: TimestampMillis) {
+        let event = self.events.push(bucket, event, now);
+        self.subscribers.enqueue(event);
+        self.events
+            .prune(self.config.max_feed_events as usize, self.config.feed_retention, now);
+        self.subscribers.expire_events(self.events.oldest_index());
+    }
+
+    pub fn record_bucket_canister_wasm_hash(&mut self) {
+        let wasm = &self.bucket_canister_wasm;
+        if !self.bucket_canister_wasm_hashes.contains_key(&wasm.version) {
+            let hash: Hash = Sha256::digest(&wasm.decompress().module).into();
+            self.bucket_canister_wasm_hashes.insert(wasm.version, hash);
         }
     }
 
+    // Records the module hash reported by a bucket and returns true if it matches the hash of the
+    // wasm version given
+    pub fn record_module_hash(
+        &mut self,
+        canister_id: CanisterId,
+        expected_version: Version,
+        module_hash: Option<Hash>,
+        now: TimestampMillis,
+    ) -> bool {
+        if let Some(bucket) = self.buckets.get_mut(&canister_id) {
+            bucket.module_hash = module_hash;
+            bucket.module_hash_checked = Some(now);
+        }
+
+        let matches = self.module_hash_matches(expected_version, module_hash);
+        if !matches {
+            error!(
+                canister_id = canister_id.to_string().as_str(),
+                %expected_version,
+                "Bucket module hash does not match the expected wasm"
+            );
+        }
+        matches
+    }
+
+    pub fn has_module_hash_drift(&self, bucket: &BucketRecord) -> bool {
+        bucket.module_hash_checked.is_some() && !self.module_hash_matches(bucket.wasm_version, bucket.module_hash)
+    }
+
+    // If the hash of the wasm version isn't known the module can't be verified, so doesn't match
+    fn module_hash_matches(&self, version: Version, module_hash: Option<Hash>) -> bool {
+        self.bucket_canister_wasm_hashes
+            .get(&version)
+            .map_or(false, |expected| module_hash == Some(*expected))
+    }
+
+    // The caller must call `on_usage_changed` for the owner once it has added the `FileAdded` event, so
+    // that any `QuotaThresholdCrossed` event caused by the file follows it in the event feed
     pub fn add_file_reference(&mut self, bucket: CanisterId, file: FileAdded) -> Result<(), FileRejected> {
         let FileAdded {
             file_id,
             owner,
             hash,
             size,
+            accessors,
+            mime_type,
+            ..
         } = file;
 
         if let Some(user) = self.users.get_mut(&owner) {
+            if let Err(reason) = user.check_new_file(size, mime_type.as_deref()) {
+                return Err(FileRejected {
+                    file_id,
+                    owner: Some(owner),
+                    reason,
+                });
+            }
+
             if !self.blobs.user_owns_blob(&owner, &hash) {
                 let bytes_used_after_upload = user
                     .bytes_used
@@ -119,6 +294,7 @@ impl Data {
                 if bytes_used_after_upload > user.byte_limit {
                     return Err(FileRejected {
                         file_id,
+                        owner: Some(owner),
                         reason: FileRejectedReason::AllowanceExceeded,
                     });
                 } else {
@@ -126,38 +302,202 @@ impl Data {
                     user.blobs_owned.insert(hash);
                 }
             }
+            user.file_count += 1;
+            user.buckets.insert(bucket);
         } else {
             return Err(FileRejected {
                 file_id,
+                owner: Some(owner),
                 reason: FileRejectedReason::UserNotFound,
             });
         }
 
         self.blobs.add(hash, size, owner, bucket);
 
+        for accessor_id in accessors {
+            self.accessors.entry(accessor_id).or_default().insert(bucket);
+        }
+
         Ok(())
     }
 
-    pub fn remove_file_reference(&mut self, bucket: CanisterId, file: FileRemoved) {
+    pub fn unlink_accessor(&mut self, accessor_id: AccessorId, bucket: CanisterId) {
+        if let Some(buckets) = self.accessors.get_mut(&accessor_id) {
+            buckets.remove(&bucket);
+            if buckets.is_empty() {
+                self.accessors.remove(&accessor_id);
+            }
+        }
+    }
+
+    // Returns true if the index held a reference to the file, else false
+    pub fn remove_file_reference(&mut self, bucket: CanisterId, file: FileRemoved, now: TimestampMillis) -> bool {
         let FileRemoved { owner, hash, .. } = file;
 
+        if !self.blobs.has_reference(&hash, &owner, &bucket) {
+            return false;
+        }
+        if let Some(user) = self.users.get_mut(&owner) {
+            user.file_count = user.file_count.saturating_sub(1);
+        }
+
         if let Some(bytes_removed) = self.blobs.remove(hash, owner, bucket) {
             if let Some(user) = self.users.get_mut(&owner) {
                 user.bytes_used = user.bytes_used.saturating_sub(bytes_removed);
                 user.blobs_owned.remove(&hash);
             }
+            self.on_usage_changed(owner, now);
         }
+        true
     }
 
+    // Should be called whenever the user's usage changes
+    pub fn on_usage_changed(&mut self, user_id: UserId, now: TimestampMillis) {
+        if let Some(user) = self.users.get_mut(&user_id) {
+            user.usage.record(
+                user.bytes_used,
+                now,
+                self.config.usage_snapshot_interval,
+                self.config.max_usage_snapshots,
+            );
+        }
+        self.update_quota_threshold(user_id, now);
+    }
+
+    // Records the highest quota alert threshold which the user's usage has now reached, adding an event
+    // to the event feed if it is higher than before. Should be called whenever the user's usage or
+    // limit changes.
+    pub fn update_quota_threshold(&mut self, user_id: UserId, now: TimestampMillis) {
+        if let Some(user) = self.users.get_mut(&user_id) {
+            let threshold = highest_threshold_reached(user.bytes_used, user.byte_limit, &self.config.quota_alert_thresholds);
+            let previous = std::mem::replace(&mut user.quota_threshold_reached, threshold);
+
+            if let Some(threshold) = threshold.filter(|t| previous.map_or(true, |p| *t > p)) {
+                let event = Event::QuotaThresholdCrossed(QuotaThresholdCrossed {
+                    user_id,
+                    threshold,
+                    bytes_used: user.bytes_used,
+                    byte_limit: user.byte_limit,
+                });
+                info!(user_id = %user_id, threshold, "User reached quota alert threshold");
+                self.push_event(None, event, now);
+            }
+        }
+        true
+    }
+
+    // Queues every user to have their quota alert threshold recalculated, which must be done whenever the
+    // thresholds change. There may be too many users to do this within a single message, so the
+    // `update_quota_thresholds` job works through the queue in batches.
+    pub fn queue_quota_threshold_updates(&mut self) {
+        self.quota_threshold_updates_pending = self.users.keys().copied().collect();
+    }
+
+    // Sets the index's references to match what the buckets hold, then recalculates the usage of
+    // each affected user
+    pub fn repair_references(&mut self, discrepancies: &[Discrepancy], now: TimestampMillis) {
+        let mut users_affected: HashMap<UserId, Vec<Hash>> = HashMap::new();
+
+        for discrepancy in discrepancies {
+            let size = discrepancy.bucket_size.or(discrepancy.index_size).unwrap_or_default();
+            self.blobs.set_reference_count(
+                discrepancy.hash,
+                size,
+                discrepancy.owner,
+                discrepancy.bucket,
+                discrepancy.bucket_reference_count,
+            );
+            users_affected.entry(discrepancy.owner).or_default().push(discrepancy.hash);
+
+            if discrepancy.bucket_reference_count > 0 {
+                if let Some(user) = self.users.get_mut(&discrepancy.owner) {
+                    user.buckets.insert(discrepancy.bucket);
+                }
+            }
+        }
+
+        for (user_id, hashes) in users_affected {
+            if let Some(user) = self.users.get_mut(&user_id) {
+                for hash in hashes {
+                    if self.blobs.user_owns_blob(&user_id, &hash) {
+                        user.blobs_owned.insert(hash);
+                    } else {
+                        user.blobs_owned.remove(&hash);
+                    }
+                }
+                user.bytes_used = user.blobs_owned.iter().filter_map(|h| self.blobs.size(h)).sum();
+                user.file_count = self.blobs.user_file_count(&user_id, &user.blobs_owned);
+            }
+            self.on_usage_changed(user_id, now);
+        }
+    }
+
+    // Also rebuilds each user's file count
     pub fn hydrate_blobs_owned(&mut self) {
+        for user in self.users.values_mut() {
+            user.file_count = 0;
+        }
         for (hash, references) in self.blobs.iter() {
-            for user_id in references.owners.keys() {
+            for (user_id, reference_counts) in references.owners.iter() {
                 if let Some(user) = self.users.get_mut(user_id) {
                     user.blobs_owned.insert(*hash);
+                    user.file_count += reference_counts.iter().map(|rc| rc.count() as u64).sum::<u64>();
+                    user.buckets.extend(reference_counts.iter().map(|rc| rc.bucket()));
                 }
             }
         }
     }
+
+    pub fn seed_user_buckets(&mut self) {
+        if !self.user_buckets_seeded {
+            let canister_ids: Vec<_> = self.buckets.iter().map(|b| b.canister_id).collect();
+            for user in self.users.values_mut() {
+                user.buckets.extend(canister_ids.iter().copied());
+            }
+            self.user_buckets_seeded = true;
+        }
+    }
+
+    // Starts accruing usage for any users added before usage was accounted for
+    pub fn start_usage_accrual(&mut self, now: TimestampMillis) {
+        for user in self.users.values_mut().filter(|u| !u.usage.is_started()) {
+            user.usage = UsageAccrual::new(user.bytes_used, now);
+        }
+    }
+
+    // Stops accruing usage for a removed user but keeps what they have accrued so far so that they can
+    // still be billed for the periods before they were removed
+    pub fn retain_removed_user_usage(&mut self, user_id: UserId, mut usage: UsageAccrual, now: TimestampMillis) {
+        if usage.is_started() {
+            usage.record(0, now, self.config.usage_snapshot_interval, self.config.max_usage_snapshots);
+            self.removed_users_usage.insert(user_id, usage);
+        }
+    }
+
+    // The start of the oldest period for which usage can still be calculated
+    pub fn oldest_usage_period_available(&self, now: TimestampMillis) -> TimestampMillis {
+        let interval = self.config.usage_snapshot_interval;
+        let latest_boundary = now / interval * interval;
+        latest_boundary.saturating_sub(self.config.max_usage_snapshots.saturating_sub(1) as u64 * interval)
+    }
+
+    // Snapshots every user's usage if an interval boundary has passed since the last snapshots, and
+    // drops the usage of removed users once they were removed before the oldest period available
+    pub fn take_usage_snapshots_if_due(&mut self, now: TimestampMillis) {
+        let interval = self.config.usage_snapshot_interval;
+        let latest_boundary = now / interval * interval;
+        if latest_boundary <= self.usage_snapshots_taken_up_to {
+            return;
+        }
+
+        for user in self.users.values_mut() {
+            user.usage.take_snapshots(now, interval, self.config.max_usage_snapshots);
+        }
+        let oldest_available = self.oldest_usage_period_available(now);
+        self.removed_users_usage
+            .retain(|_, usage| usage.last_updated() >= oldest_available);
+        self.usage_snapshots_taken_up_to = latest_boundary;
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -166,6 +506,27 @@ struct UserRecordInternal {
     pub bytes_used: u64,
     #[serde(default)]
     pub blobs_owned: HashSet<Hash>,
+    // The number of files the user owns across all buckets, including every copy of each blob
+    #[serde(default)]
+    pub file_count: u64,
+    // The buckets which know about this user, either because the user has interacted with them or
+    // because they hold files owned by the user. Only these buckets are sent events about the user.
+    #[serde(default)]
+    pub buckets: HashSet<CanisterId>,
+    // The highest of the configured quota alert thresholds which the user's usage has reached
+    #[serde(default)]
+    pub quota_threshold_reached: Option<u8>,
+    #[serde(default)]
+    pub limits: UserLimits,
+    #[serde(default)]
+    pub usage: UsageAccrual,
+}
+
+impl UserRecordInternal {
+    // Checks the new file against the user's limits, other than their byte limit
+    pub fn check_new_file(&self, size: u64, mime_type: Option<&str>) -> Result<(), FileRejectedReason> {
+        self.limits.check_new_file(self.file_count, size, mime_type)
+    }
 }
 
 #[derive(CandidType, Serialize, Debug)]
@@ -180,10 +541,20 @@ pub struct Metrics {
     pub total_file_bytes: u64,
     pub active_buckets: Vec<BucketMetrics>,
     pub full_buckets: Vec<BucketMetrics>,
+    pub bucket_pool_size: u64,
     pub bucket_upgrades_pending: u64,
     pub bucket_upgrades_in_progress: u64,
     pub bucket_upgrades_failed: Vec<FailedUpgradeCount>,
+    pub bucket_upgrades_paused: bool,
+    pub bucket_upgrade_rollout: BucketUpgradeRolloutStatus,
     pub bucket_canister_wasm: Version,
+    pub buckets_with_module_hash_drift: Vec<CanisterId>,
+    pub module_hash_audit_last_run: TimestampMillis,
+    pub previous_bucket_canister_wasm: Option<Version>,
+    pub config: Config,
+    pub targeted_sync: TargetedSyncMetrics,
+    pub all_accessors_tracked: bool,
+    pub operations: MetricsRegistry,
 }
 
 #[derive(CandidType, Serialize, Debug)]
@@ -191,14 +562,16 @@ pub struct BucketMetrics {
     pub canister_id: CanisterId,
     pub wasm_version: Version,
     pub bytes_used: u64,
+    pub sync: BucketSyncMetrics,
 }
 
-impl From<&BucketRecord> for BucketMetrics {
-    fn from(bucket: &BucketRecord) -> Self {
+impl BucketMetrics {
+    fn new(bucket: &BucketRecord, now: TimestampMillis) -> BucketMetrics {
         BucketMetrics {
             canister_id: bucket.canister_id,
             wasm_version: bucket.wasm_version,
             bytes_used: bucket.bytes_used,
+            sync: bucket.sync_state.metrics(now),
         }
     }
 }
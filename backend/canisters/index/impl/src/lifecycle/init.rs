@@ -5,13 +5,14 @@ use canister_logger::set_panic_hook;
 use ic_cdk_macros::init;
 use index_canister::init::Args;
 use tracing::info;
+use types::LogSettings;
 use utils::env::canister::CanisterEnv;
 
 #[init]
 #[trace]
 fn init(args: Args) {
     set_panic_hook();
-    init_logger(args.test_mode);
+    init_logger(args.test_mode, &LogSettings::default());
 
     let env = Box::new(CanisterEnv::new());
 
@@ -0,0 +1,884 @@
+use crate::model::bucket_sync_state::EventToSync;
+use crate::{mutate_state, read_state, RuntimeState};
+use bucket_canister::c2c_sync_index::{Args, Response, SuccessResult};
+use tracing::error;
+use types::{AccessorId, CanisterId, CanisterWasm, Cycles, Milliseconds, Version};
+use utils::canister::get_module_hash;
+use utils::metrics::CallMetricNames;
+use utils::scheduler;
+use utils::time::{DAY_IN_MS, MINUTE_IN_MS};
+
+const ENSURE_SUFFICIENT_ACTIVE_BUCKETS_INTERVAL: Milliseconds = MINUTE_IN_MS;
+const MODULE_HASH_AUDIT_INTERVAL: Milliseconds = DAY_IN_MS;
+const RECALCULATE_BLOB_METRICS_INTERVAL: Milliseconds = 10 * MINUTE_IN_MS;
+const BACKFILL_ACCESSORS_RETRY_DELAY: Milliseconds = 10 * MINUTE_IN_MS;
+const TAKE_USAGE_SNAPSHOTS_INTERVAL: Milliseconds = 10 * MINUTE_IN_MS;
+
+pub fn register_jobs() {
+    scheduler::register(
+        ensure_sufficient_active_buckets::NAME,
+        Some(ENSURE_SUFFICIENT_ACTIVE_BUCKETS_INTERVAL),
+        ensure_sufficient_active_buckets::run,
+    );
+    scheduler::register(sync_users_with_buckets::NAME, None, sync_users_with_buckets::run);
+    scheduler::register(notify_subscribers::NAME, None, notify_subscribers::run);
+    scheduler::register(update_quota_thresholds::NAME, None, update_quota_thresholds::run);
+    scheduler::register(upgrade_canisters::NAME, None, upgrade_canisters::run);
+    scheduler::register(
+        audit_bucket_module_hashes::NAME,
+        Some(MODULE_HASH_AUDIT_INTERVAL),
+        audit_bucket_module_hashes::run,
+    );
+    scheduler::register(
+        recalculate_blob_metrics::NAME,
+        Some(RECALCULATE_BLOB_METRICS_INTERVAL),
+        recalculate_blob_metrics::run,
+    );
+    scheduler::register(backfill_accessors::NAME, None, backfill_accessors::run);
+    scheduler::register(
+        take_usage_snapshots::NAME,
+        Some(TAKE_USAGE_SNAPSHOTS_INTERVAL),
+        take_usage_snapshots::run,
+    );
+    scheduler::register(reconcile_buckets::NAME, None, reconcile_buckets::run);
+    scheduler::register(sync_users_snapshots::NAME, None, sync_users_snapshots::run);
+}
+
+#[export_name = "canister_global_timer"]
+extern "C" fn global_timer() {
+    scheduler::run_due_jobs();
+}
+
+// Creates new buckets whenever there are fewer active buckets than the target or the pool of
+// pre-installed buckets is below its target size
+pub mod ensure_sufficient_active_buckets {
+    use super::*;
+    use crate::model::buckets::BucketRecord;
+    use types::{BucketConfig, LogSettings};
+    use utils::canister::create_and_install;
+    use utils::consts::CREATE_CANISTER_CYCLES_FEE;
+    use PrepareResponse::*;
+
+    pub const NAME: &str = "ensure_sufficient_active_buckets";
+
+    pub fn trigger() {
+        scheduler::run_now(NAME);
+    }
+
+    pub fn run() {
+        match mutate_state(prepare) {
+            DoNothing => (),
+            CyclesBalanceTooLow => error!("Cycles balance too low to add a new bucket"),
+            CreateBucket(args) => {
+                ic_cdk::spawn(create_bucket(args));
+            }
+        }
+    }
+
+    struct CreateBucketArgs {
+        canister_wasm: CanisterWasm,
+        cycles_to_use: Cycles,
+        init_canister_args: bucket_canister::init::Args,
+    }
+
+    enum PrepareResponse {
+        DoNothing,
+        CyclesBalanceTooLow,
+        CreateBucket(CreateBucketArgs),
+    }
+
+    fn prepare(runtime_state: &mut RuntimeState) -> PrepareResponse {
+        let config = &runtime_state.data.config;
+
+        if !runtime_state
+            .data
+            .buckets
+            .try_to_acquire_creation_lock(config.target_active_buckets as usize, config.bucket_pool_target_size as usize)
+        {
+            return DoNothing;
+        }
+
+        let initial_cycles_balance = config.bucket_canister_initial_cycles_balance;
+        let (cycles_required, min_cycles_balance) = if runtime_state.data.test_mode {
+            (
+                (initial_cycles_balance + CREATE_CANISTER_CYCLES_FEE) / 4,
+                config.min_cycles_balance_for_bucket_creation / 10,
+            )
+        } else {
+            (
+                initial_cycles_balance + CREATE_CANISTER_CYCLES_FEE,
+                config.min_cycles_balance_for_bucket_creation,
+            )
+        };
+        if !utils::cycles::can_spend_cycles(cycles_required, min_cycles_balance) {
+            runtime_state.data.buckets.release_creation_lock();
+            return CyclesBalanceTooLow;
+        }
+
+        CreateBucket(CreateBucketArgs {
+            canister_wasm: runtime_state.data.bucket_canister_wasm.decompress(),
+            cycles_to_use: cycles_required,
+            init_canister_args: bucket_canister::init::Args {
+                wasm_version: runtime_state.data.bucket_canister_wasm.version,
+                test_mode: runtime_state.data.test_mode,
+                config: config.bucket.clone(),
+                log_settings: runtime_state.data.bucket_log_settings.clone(),
+            },
+        })
+    }
+
+    async fn create_bucket(args: CreateBucketArgs) {
+        let bucket_config = args.init_canister_args.config.clone();
+        let log_settings = args.init_canister_args.log_settings.clone();
+        let wasm_arg = candid::encode_one(args.init_canister_args).unwrap();
+
+        let result = create_and_install(None, args.canister_wasm.module, wasm_arg, args.cycles_to_use).await;
+
+        if let Ok(canister_id) = result {
+            let wasm_version = args.canister_wasm.version;
+            let bucket = BucketRecord::new(canister_id, wasm_version);
+            mutate_state(|state| commit(bucket, bucket_config, log_settings, state));
+
+            if let Ok(module_hash) = get_module_hash(canister_id).await {
+                mutate_state(|state| {
+                    let now = state.env.now();
+                    state.data.record_module_hash(canister_id, wasm_version, module_hash, now);
+                });
+            }
+        } else {
+            mutate_state(|state| state.data.buckets.release_creation_lock());
+        }
+    }
+
+    fn commit(
+        mut bucket: BucketRecord,
+        bucket_config: BucketConfig,
+        log_settings: LogSettings,
+        runtime_state: &mut RuntimeState,
+    ) {
+        // The config and log settings may have been updated while the bucket was being created
+        let now = runtime_state.env.now();
+        if bucket_config != runtime_state.data.config.bucket {
+            bucket
+                .sync_state
+                .enqueue(EventToSync::ConfigUpdated(runtime_state.data.config.bucket.clone()), now);
+        }
+        if log_settings != runtime_state.data.bucket_log_settings {
+            bucket.sync_state.enqueue(
+                EventToSync::LogSettingsUpdated(runtime_state.data.bucket_log_settings.clone()),
+                now,
+            );
+        }
+        let target_active_buckets = runtime_state.data.config.target_active_buckets as usize;
+        runtime_state.data.buckets.add_bucket(bucket, true, target_active_buckets);
+
+        // More buckets may still be needed and the new bucket may have a config update to sync
+        trigger();
+        sync_users_with_buckets::trigger();
+    }
+}
+
+// Triggered whenever events are queued to be synced to the buckets
+pub mod sync_users_with_buckets {
+    use super::*;
+    use crate::model::bucket_sync_state::SyncFailedResult;
+    use index_canister::events::Event;
+
+    const CALL_METRICS: CallMetricNames = CallMetricNames {
+        calls: "bucket_sync_batches_sent",
+        failures: "bucket_sync_batches_failed",
+        latency_ms: "bucket_sync_latency_ms",
+    };
+
+    pub const NAME: &str = "sync_users_with_buckets";
+
+    pub fn trigger() {
+        scheduler::run_now(NAME);
+    }
+
+    pub fn run() {
+        let (batches, next_retry_at, now) = mutate_state(|state| {
+            let now = state.env.now();
+            (next_batch(state), state.data.buckets.next_sync_retry_at(), now)
+        });
+
+        for (canister_id, args) in batches {
+            let correlation_id = args.correlation_id.clone();
+            ic_cdk::spawn(canister_logger::with_correlation_id_async(
+                correlation_id,
+                send_to_bucket(canister_id, args),
+            ));
+        }
+
+        // The job only holds a single next run time, so a retry scheduled after an earlier run could
+        // otherwise be lost, leaving the failed batch stuck until something else triggers the job
+        if let Some(next_retry_at) = next_retry_at {
+            scheduler::run_after(NAME, next_retry_at.saturating_sub(now));
+        }
+    }
+
+    fn next_batch(runtime_state: &mut RuntimeState) -> Vec<(CanisterId, Args)> {
+        let max_events = runtime_state.data.config.max_events_to_sync_per_batch as usize;
+        let now = runtime_state.env.now();
+        let mut batches = runtime_state.data.buckets.pop_args_for_next_sync(max_events, now);
+        for (_, args) in batches.iter_mut() {
+            // Retried and replayed batches already have a correlation id
+            args.correlation_id.get_or_insert_with(canister_logger::new_correlation_id);
+        }
+        batches
+    }
+
+    async fn send_to_bucket(canister_id: CanisterId, args: Args) {
+        let start = read_state(|state| state.env.now());
+        let response = bucket_canister_c2c_client::c2c_sync_index(canister_id, &args).await;
+        mutate_state(|state| {
+            let latency = state.env.now().saturating_sub(start);
+            state.data.operations.record_call(&CALL_METRICS, response.is_ok(), latency);
+        });
+
+        match response {
+            Ok(Response::Success(result)) => {
+                mutate_state(|state| handle_success(canister_id, result, state));
+            }
+            Ok(Response::OutOfOrder(last_applied)) => {
+                mutate_state(|state| handle_out_of_order(canister_id, args, last_applied, state));
+            }
+            Err(_) => {
+                mutate_state(|state| handle_error(canister_id, args, state));
+            }
+        }
+    }
+
+    fn handle_success(canister_id: CanisterId, result: SuccessResult, runtime_state: &mut RuntimeState) {
+        let now = runtime_state.env.now();
+        for file in result.files_removed {
+            canister_logger::with_correlation_id(file.correlation_id.clone(), || {
+                if runtime_state.data.remove_file_reference(canister_id, file.clone(), now) {
+                    runtime_state
+                        .data
+                        .push_event(Some(canister_id), Event::FileRemoved(file), now);
+                }
+            });
+        }
+        notify_subscribers::trigger();
+
+        if let Some(bucket) = runtime_state.data.buckets.get_mut(&canister_id) {
+            bucket.sync_state.mark_sync_completed();
+        }
+
+        // Pick up any events which were queued while this batch was being synced
+        trigger();
+    }
+
+    // The bucket expected a different sequence number, so the events are requeued to be resent in a
+    // batch numbered from the last one the bucket applied
+    fn handle_out_of_order(canister_id: CanisterId, args: Args, last_applied: u64, runtime_state: &mut RuntimeState) {
+        error!(
+            canister_id = canister_id.to_string().as_str(),
+            sequence_number = args.sequence_number,
+            last_applied,
+            "Bucket rejected out of order sync batch"
+        );
+        if let Some(bucket) = runtime_state.data.buckets.get_mut(&canister_id) {
+            bucket.sync_state.mark_sync_out_of_order(args, last_applied);
+        }
+        trigger();
+    }
+
+    fn handle_error(canister_id: CanisterId, args: Args, runtime_state: &mut RuntimeState) {
+        let max_attempts = runtime_state.data.config.max_sync_attempts;
+        let now = runtime_state.env.now();
+
+        if let Some(bucket) = runtime_state.data.buckets.get_mut(&canister_id) {
+            match bucket.sync_state.mark_sync_failed(args, max_attempts, now) {
+                SyncFailedResult::RetryAt(retry_at) => scheduler::run_after(NAME, retry_at.saturating_sub(now)),
+                SyncFailedResult::DeadLettered(id) => {
+                    error!(
+                        canister_id = canister_id.to_string().as_str(),
+                        id, "Sync batch moved to dead letters after too many failed attempts"
+                    );
+                    // Carry on with the events queued behind the failed batch
+                    trigger();
+                }
+            }
+        }
+    }
+}
+
+// Triggered whenever events are added to the event feed, sending each subscriber the events which
+// match its filter
+pub mod notify_subscribers {
+    use super::*;
+    use crate::model::subscribers::NotifyFailedResult;
+    use index_canister::subscriber::c2c_notify_events;
+
+    const CALL_METRICS: CallMetricNames = CallMetricNames {
+        calls: "subscriber_batches_sent",
+        failures: "subscriber_batches_failed",
+        latency_ms: "subscriber_notify_latency_ms",
+    };
+
+    pub const NAME: &str = "notify_subscribers";
+
+    pub fn trigger() {
+        scheduler::run_now(NAME);
+    }
+
+    pub fn run() {
+        let (batches, next_retry_at, now) = mutate_state(|state| {
+            let now = state.env.now();
+            (next_batch(state), state.data.subscribers.next_retry_at(), now)
+        });
+
+        for (canister_id, args) in batches {
+            let correlation_id = args.correlation_id.clone();
+            ic_cdk::spawn(canister_logger::with_correlation_id_async(
+                correlation_id,
+                notify_subscriber(canister_id, args),
+            ));
+        }
+
+        // As when syncing buckets, a retry scheduled by an earlier run could otherwise be lost
+        if let Some(next_retry_at) = next_retry_at {
+            scheduler::run_after(NAME, next_retry_at.saturating_sub(now));
+        }
+    }
+
+    fn next_batch(runtime_state: &mut RuntimeState) -> Vec<(CanisterId, c2c_notify_events::Args)> {
+        let max_events = runtime_state.data.config.max_events_to_sync_per_batch as usize;
+        let now = runtime_state.env.now();
+        let mut batches = runtime_state
+            .data
+            .subscribers
+            .pop_args_for_next_notifications(max_events, now);
+        for (_, args) in batches.iter_mut() {
+            // Retried batches already have a correlation id
+            args.correlation_id.get_or_insert_with(canister_logger::new_correlation_id);
+        }
+        batches
+    }
+
+    async fn notify_subscriber(canister_id: CanisterId, args: c2c_notify_events::Args) {
+        let start = read_state(|state| state.env.now());
+        let response = index_canister_c2c_client::subscriber::c2c_notify_events(canister_id, &args).await;
+        mutate_state(|state| {
+            let latency = state.env.now().saturating_sub(start);
+            state.data.operations.record_call(&CALL_METRICS, response.is_ok(), latency);
+        });
+
+        match response {
+            Ok(c2c_notify_events::Response::Success) => mutate_state(|state| handle_success(canister_id, args, state)),
+            Err(error) => {
+                error!(
+                    canister_id = canister_id.to_string().as_str(),
+                    error_code = ?error.0,
+                    error_message = error.1.as_str(),
+                    "Error notifying subscriber"
+                );
+                mutate_state(|state| handle_error(canister_id, args, state))
+            }
+        }
+    }
+
+    fn handle_success(canister_id: CanisterId, args: c2c_notify_events::Args, runtime_state: &mut RuntimeState) {
+        runtime_state.data.subscribers.mark_notify_completed(&canister_id, &args);
+
+        // Pick up any events which were queued while this batch was being sent
+        trigger();
+    }
+
+    fn handle_error(canister_id: CanisterId, args: c2c_notify_events::Args, runtime_state: &mut RuntimeState) {
+        let max_attempts = runtime_state.data.config.max_sync_attempts;
+        let now = runtime_state.env.now();
+
+        match runtime_state
+            .data
+            .subscribers
+            .mark_notify_failed(&canister_id, args, max_attempts, now)
+        {
+            Some(NotifyFailedResult::RetryAt(retry_at)) => scheduler::run_after(NAME, retry_at.saturating_sub(now)),
+            Some(NotifyFailedResult::Dropped(event_count)) => {
+                error!(
+                    canister_id = canister_id.to_string().as_str(),
+                    event_count, "Subscriber notification dropped after too many failed attempts"
+                );
+                runtime_state.data.operations.increment("subscriber_batches_dropped");
+                // Carry on with the events queued behind the dropped batch
+                trigger();
+            }
+            None => {}
+        }
+    }
+}
+
+// Triggered when the quota alert thresholds change, recalculating the threshold reached by each user in
+// batches since there may be too many users to do in a single message
+pub mod update_quota_thresholds {
+    use super::*;
+
+    const BATCH_SIZE: usize = 1000;
+
+    pub const NAME: &str = "update_quota_thresholds";
+
+    pub fn trigger() {
+        scheduler::run_now(NAME);
+    }
+
+    pub fn run() {
+        if mutate_state(update_next_batch) {
+            trigger();
+        }
+    }
+
+    // Returns true if there are more users still to update
+    fn update_next_batch(runtime_state: &mut RuntimeState) -> bool {
+        let now = runtime_state.env.now();
+        let pending = &mut runtime_state.data.quota_threshold_updates_pending;
+        let count = pending.len().min(BATCH_SIZE);
+        let user_ids: Vec<_> = pending.drain(..count).collect();
+
+        for user_id in user_ids {
+            runtime_state.data.update_quota_threshold(user_id, now);
+        }
+        if count > 0 {
+            notify_subscribers::trigger();
+        }
+        !runtime_state.data.quota_threshold_updates_pending.is_empty()
+    }
+}
+
+// Triggered whenever buckets are queued to be upgraded and each time an upgrade completes
+pub mod upgrade_canisters {
+    use super::*;
+    use crate::model::bucket_upgrade_rollout::RolloutFailure;
+    use utils::canister::{upgrade, FailedUpgrade};
+
+    type CanisterToUpgrade = utils::canister::CanisterToUpgrade<bucket_canister::post_upgrade::Args>;
+
+    pub const NAME: &str = "upgrade_canisters";
+
+    pub fn trigger() {
+        scheduler::run_now(NAME);
+    }
+
+    pub fn run() {
+        let canisters_to_upgrade = mutate_state(next_batch);
+        if !canisters_to_upgrade.is_empty() {
+            ic_cdk::spawn(perform_upgrades(canisters_to_upgrade));
+        }
+    }
+
+    fn next_batch(runtime_state: &mut RuntimeState) -> Vec<CanisterToUpgrade> {
+        let max_concurrent_upgrades = runtime_state.data.config.max_concurrent_canister_upgrades;
+        let count_in_progress = runtime_state.data.canisters_requiring_upgrade.count_in_progress();
+        (0..max_concurrent_upgrades.saturating_sub(count_in_progress))
+            .map_while(|_| try_get_next(runtime_state))
+            .collect()
+    }
+
+    fn try_get_next(runtime_state: &mut RuntimeState) -> Option<CanisterToUpgrade> {
+        let canister_id = runtime_state.data.canisters_requiring_upgrade.try_take_next()?;
+        let bucket = runtime_state.data.buckets.get(&canister_id)?;
+        let new_wasm = runtime_state.data.bucket_canister_wasm.decompress();
+
+        Some(CanisterToUpgrade {
+            canister_id,
+            current_wasm_version: bucket.wasm_version,
+            new_wasm: runtime_state.data.bucket_canister_wasm.decompress(),
+            args: bucket_canister::post_upgrade::Args {
+                wasm_version: new_wasm.version,
+            },
+        })
+    }
+
+    async fn perform_upgrades(canisters_to_upgrade: Vec<CanisterToUpgrade>) {
+        let futures: Vec<_> = canisters_to_upgrade.into_iter().map(perform_upgrade).collect();
+
+        futures::future::join_all(futures).await;
+    }
+
+    async fn perform_upgrade(canister_to_upgrade: CanisterToUpgrade) {
+        let canister_id = canister_to_upgrade.canister_id;
+        let from_version = canister_to_upgrade.current_wasm_version;
+        let to_version = canister_to_upgrade.new_wasm.version;
+
+        match upgrade(canister_to_upgrade).await {
+            Ok(_) => {
+                // Confirm that the expected module is now installed before treating the upgrade as a
+                // success. If the hash can't be retrieved the upgrade is unverified so is treated as
+                // having failed, allowing it to be retried.
+                let module_hash = get_module_hash(canister_id).await;
+
+                mutate_state(|state| {
+                    let now = state.env.now();
+                    let verified = match module_hash {
+                        Ok(hash) => state.data.record_module_hash(canister_id, to_version, hash, now),
+                        Err(_) => false,
+                    };
+                    if verified {
+                        on_success(canister_id, to_version, state);
+                    } else {
+                        on_failure(canister_id, from_version, to_version, state);
+                    }
+                });
+            }
+            Err(_) => {
+                mutate_state(|state| on_failure(canister_id, from_version, to_version, state));
+            }
+        }
+
+        trigger();
+    }
+
+    fn on_success(canister_id: CanisterId, to_version: Version, runtime_state: &mut RuntimeState) {
+        if let Some(bucket) = runtime_state.data.buckets.get_mut(&canister_id) {
+            bucket.wasm_version = to_version;
+        }
+        let canisters_requiring_upgrade = &mut runtime_state.data.canisters_requiring_upgrade;
+        canisters_requiring_upgrade.mark_success(&canister_id);
+
+        let rollout = &mut runtime_state.data.bucket_upgrade_rollout;
+        if to_version == runtime_state.data.bucket_canister_wasm.version {
+            for canister_id in rollout.mark_success(&canister_id) {
+                canisters_requiring_upgrade.enqueue(canister_id);
+            }
+        } else if !rollout.hold_back(canister_id) {
+            // The wasm was updated or rolled back while this upgrade was in progress
+            canisters_requiring_upgrade.enqueue(canister_id);
+        }
+    }
+
+    fn on_failure(canister_id: CanisterId, from_version: Version, to_version: Version, runtime_state: &mut RuntimeState) {
+        let canisters_requiring_upgrade = &mut runtime_state.data.canisters_requiring_upgrade;
+        canisters_requiring_upgrade.mark_failure(FailedUpgrade {
+            canister_id,
+            from_version,
+            to_version,
+        });
+
+        if to_version != runtime_state.data.bucket_canister_wasm.version {
+            return;
+        }
+
+        match runtime_state.data.bucket_upgrade_rollout.mark_failure(&canister_id) {
+            RolloutFailure::Counted => {}
+            RolloutFailure::Halted => {
+                canisters_requiring_upgrade.pause();
+                error!(%to_version, "Bucket upgrades halted due to too many failures");
+            }
+            RolloutFailure::CanaryFailed(held_back) => {
+                for canister_id in held_back {
+                    if let Some(bucket) = runtime_state.data.buckets.get(&canister_id) {
+                        canisters_requiring_upgrade.mark_failure(FailedUpgrade {
+                            canister_id,
+                            from_version: bucket.wasm_version,
+                            to_version,
+                        });
+                    }
+                }
+                canisters_requiring_upgrade.pause();
+                error!(
+                    canister_id = canister_id.to_string().as_str(),
+                    %to_version,
+                    "Bucket upgrades halted due to a canary failing"
+                );
+            }
+        }
+    }
+}
+
+// Periodically checks that the module installed in each bucket matches the wasm version the index
+// believes it to be running
+mod audit_bucket_module_hashes {
+    use super::*;
+
+    pub const NAME: &str = "audit_bucket_module_hashes";
+
+    pub fn run() {
+        let canister_ids = mutate_state(next_batch);
+        if !canister_ids.is_empty() {
+            ic_cdk::spawn(audit_buckets(canister_ids));
+        }
+    }
+
+    fn next_batch(runtime_state: &mut RuntimeState) -> Vec<CanisterId> {
+        runtime_state.data.module_hash_audit_last_run = runtime_state.env.now();
+
+        // Buckets mid-upgrade are skipped since their hash is checked once the upgrade completes
+        runtime_state
+            .data
+            .buckets
+            .iter()
+            .map(|b| b.canister_id)
+            .filter(|c| !runtime_state.data.canisters_requiring_upgrade.is_in_progress(c))
+            .collect()
+    }
+
+    async fn audit_buckets(canister_ids: Vec<CanisterId>) {
+        let futures: Vec<_> = canister_ids.into_iter().map(audit_bucket).collect();
+
+        futures::future::join_all(futures).await;
+    }
+
+    async fn audit_bucket(canister_id: CanisterId) {
+        if let Ok(module_hash) = get_module_hash(canister_id).await {
+            mutate_state(|state| {
+                if let Some(wasm_version) = state.data.buckets.get(&canister_id).map(|b| b.wasm_version) {
+                    let now = state.env.now();
+                    state.data.record_module_hash(canister_id, wasm_version, module_hash, now);
+                }
+            });
+        }
+    }
+}
+
+mod recalculate_blob_metrics {
+    use super::*;
+
+    pub const NAME: &str = "recalculate_blob_metrics";
+
+    pub fn run() {
+        mutate_state(|state| {
+            let now = state.env.now();
+            state.data.blobs.recalculate_metrics_if_due(now);
+        })
+    }
+}
+
+// Fills in the buckets of each accessor for state which predates accessors being tracked, after which
+// events about accessors are only sent to the buckets holding their files. Runs after each upgrade
+// until complete.
+mod backfill_accessors {
+    use super::*;
+    use crate::model::accessors_backfill::AccessorsBackfill;
+    use bucket_canister::c2c_accessors;
+
+    pub const NAME: &str = "backfill_accessors";
+
+    const PAGE_SIZE: u32 = 1000;
+
+    pub fn trigger() {
+        scheduler::run_now(NAME);
+    }
+
+    pub fn run() {
+        if let Some((canister_id, args)) = mutate_state(next_page) {
+            ic_cdk::spawn(backfill_page(canister_id, args));
+        }
+    }
+
+    fn next_page(runtime_state: &mut RuntimeState) -> Option<(CanisterId, c2c_accessors::Args)> {
+        let data = &mut runtime_state.data;
+        if data.all_accessors_tracked {
+            return None;
+        }
+
+        let buckets = &data.buckets;
+        let backfill = data
+            .accessors_backfill
+            .get_or_insert_with(|| AccessorsBackfill::new(buckets.iter().map(|b| b.canister_id)));
+
+        if let Some((canister_id, start_after)) = backfill.try_start_next() {
+            Some((
+                canister_id,
+                c2c_accessors::Args {
+                    start_after,
+                    max_results: PAGE_SIZE,
+                },
+            ))
+        } else {
+            if backfill.is_complete() {
+                data.all_accessors_tracked = true;
+                data.accessors_backfill = None;
+            }
+            None
+        }
+    }
+
+    async fn backfill_page(canister_id: CanisterId, args: c2c_accessors::Args) {
+        match bucket_canister_c2c_client::c2c_accessors(canister_id, &args).await {
+            Ok(c2c_accessors::Response::Success(result)) => {
+                mutate_state(|state| handle_success(canister_id, result.accessors, args.max_results, state));
+                trigger();
+            }
+            Err(_) => {
+                // Buckets which haven't yet been upgraded won't recognise the call, so it is retried
+                // after a delay
+                mutate_state(|state| {
+                    if let Some(backfill) = state.data.accessors_backfill.as_mut() {
+                        backfill.mark_page_failed();
+                    }
+                });
+                scheduler::run_after(NAME, BACKFILL_ACCESSORS_RETRY_DELAY);
+            }
+        }
+    }
+
+    fn handle_success(canister_id: CanisterId, accessors: Vec<AccessorId>, max_results: u32, runtime_state: &mut RuntimeState) {
+        let last_accessor = accessors.last().copied();
+        let page_full = accessors.len() >= max_results as usize;
+
+        for accessor_id in accessors {
+            runtime_state
+                .data
+                .accessors
+                .entry(accessor_id)
+                .or_default()
+                .insert(canister_id);
+        }
+
+        if let Some(backfill) = runtime_state.data.accessors_backfill.as_mut() {
+            backfill.mark_page_completed(last_accessor, page_full);
+        }
+    }
+}
+
+// Snapshots each user's accrued usage shortly after each usage snapshot interval boundary. Users whose
+// usage changes are snapshotted as part of the change, so this is only needed for users whose usage
+// hasn't changed since the boundary.
+mod take_usage_snapshots {
+    use super::*;
+
+    pub const NAME: &str = "take_usage_snapshots";
+
+    pub fn run() {
+        mutate_state(|state| {
+            let now = state.env.now();
+            state.data.take_usage_snapshots_if_due(now);
+        })
+    }
+}
+
+// Triggered when a reconciliation is started and then after each page of files is processed until
+// every bucket has been checked
+pub mod reconcile_buckets {
+    use super::*;
+    use bucket_canister::c2c_files;
+    use types::FileId;
+
+    const PAGE_SIZE: u32 = 1000;
+
+    pub const NAME: &str = "reconcile_buckets";
+
+    pub fn trigger() {
+        scheduler::run_now(NAME);
+    }
+
+    pub fn run() {
+        if let Some((canister_id, start_after)) = mutate_state(next_page) {
+            ic_cdk::spawn(fetch_page(canister_id, start_after));
+        }
+    }
+
+    fn next_page(runtime_state: &mut RuntimeState) -> Option<(CanisterId, Option<FileId>)> {
+        let now = runtime_state.env.now();
+        runtime_state.data.reconciliation.next_page(now)
+    }
+
+    async fn fetch_page(canister_id: CanisterId, start_after: Option<FileId>) {
+        let args = c2c_files::Args {
+            start_after,
+            max_results: PAGE_SIZE,
+        };
+
+        match bucket_canister_c2c_client::c2c_files(canister_id, &args).await {
+            Ok(c2c_files::Response::Success(page)) => {
+                mutate_state(|state| handle_page(canister_id, page, state));
+            }
+            Err(_) => {
+                mutate_state(|state| state.data.reconciliation.record_bucket_skipped(canister_id));
+            }
+        }
+
+        trigger();
+    }
+
+    fn handle_page(canister_id: CanisterId, page: c2c_files::SuccessResult, runtime_state: &mut RuntimeState) {
+        let now = runtime_state.env.now();
+        let data = &mut runtime_state.data;
+
+        if let Some(scan) = data.reconciliation.record_page(canister_id, page, PAGE_SIZE as usize) {
+            let is_comparable = data.buckets.get(&canister_id).map_or(false, |b| {
+                b.sync_state.is_idle()
+                    && scan.is_comparable(b.sync_receiver.last_sequence_number(), b.sync_state.last_sequence_number())
+            });
+
+            if !is_comparable {
+                data.reconciliation.record_bucket_skipped(canister_id);
+                return;
+            }
+
+            let discrepancies = scan.find_discrepancies(&data.blobs);
+            if !discrepancies.is_empty() {
+                error!(
+                    canister_id = canister_id.to_string().as_str(),
+                    count = discrepancies.len(),
+                    "Bucket files out of sync with the index"
+                );
+                if data.reconciliation.repair() {
+                    data.repair_references(&discrepancies, now);
+                    notify_subscribers::trigger();
+                }
+            }
+            data.reconciliation.record_bucket_checked(discrepancies);
+        }
+    }
+}
+
+// Triggered when a bucket's users are to be resynced and then after each page of users is sent
+pub mod sync_users_snapshots {
+    use super::*;
+    use bucket_canister::c2c_sync_users_snapshot;
+    use tracing::info;
+
+    const PAGE_SIZE: usize = 10000;
+
+    pub const NAME: &str = "sync_users_snapshots";
+
+    pub fn trigger() {
+        scheduler::run_now(NAME);
+    }
+
+    pub fn run() {
+        let pages = mutate_state(|state| state.data.buckets.pop_users_snapshot_pages(PAGE_SIZE));
+        for (canister_id, args) in pages {
+            ic_cdk::spawn(send_to_bucket(canister_id, args));
+        }
+    }
+
+    async fn send_to_bucket(canister_id: CanisterId, args: c2c_sync_users_snapshot::Args) {
+        match bucket_canister_c2c_client::c2c_sync_users_snapshot(canister_id, &args).await {
+            Ok(c2c_sync_users_snapshot::Response::Success(result)) => {
+                mutate_state(|state| {
+                    if let Some(bucket) = state.data.buckets.get_mut(&canister_id) {
+                        if args.is_last_page {
+                            bucket.users_snapshot = None;
+                            info!(
+                                canister_id = canister_id.to_string().as_str(),
+                                users_added = result.users_added,
+                                users_removed = result.users_removed,
+                                "Bucket users resynced"
+                            );
+                        } else if let Some(snapshot) = bucket.users_snapshot.as_mut() {
+                            snapshot.mark_page_sent(&args.users);
+                        }
+                    }
+                });
+                trigger();
+            }
+            Err(_) => {
+                mutate_state(|state| {
+                    if let Some(snapshot) = state
+                        .data
+                        .buckets
+                        .get_mut(&canister_id)
+                        .and_then(|b| b.users_snapshot.as_mut())
+                    {
+                        snapshot.mark_page_failed();
+                    }
+                });
+                scheduler::run_after(NAME, MINUTE_IN_MS);
+            }
+        }
+    }
+}
@@ -1,16 +1,18 @@
 use crate::{init_state as set_state, Data, RuntimeState, LOG_MESSAGES, WASM_VERSION};
-use types::{Timestamped, Version};
+use types::{LogSettings, Timestamped, Version};
 use utils::env::Environment;
 
-mod heartbeat;
 mod init;
+pub mod jobs;
 mod post_upgrade;
 mod pre_upgrade;
 
 const BUFFER_SIZE: usize = 4 * 1024 * 1024; // 4MB
+const LOG_BUFFER_SIZE_MB: u32 = 32;
 
-fn init_logger(enable_trace: bool) {
-    let log_messages = canister_logger::init_logger(enable_trace, None, ic_cdk::api::time);
+fn init_logger(trace_always: bool, log_settings: &LogSettings) {
+    let log_messages =
+        canister_logger::init_logger(trace_always, log_settings, None, Some(LOG_BUFFER_SIZE_MB), ic_cdk::api::time);
 
     LOG_MESSAGES.with(|c| *c.borrow_mut() = log_messages);
 }
@@ -21,4 +23,7 @@ fn init_state(env: Box<dyn Environment>, data: Data, wasm_version: Version) {
 
     set_state(runtime_state);
     WASM_VERSION.with(|v| *v.borrow_mut() = Timestamped::new(wasm_version, now));
+
+    // The global timer is cleared on upgrade so the jobs must be registered again each time
+    jobs::register_jobs();
 }
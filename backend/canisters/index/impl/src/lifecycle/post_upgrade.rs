@@ -2,11 +2,13 @@ use crate::lifecycle::{init_logger, init_state, BUFFER_SIZE};
 use crate::{Data, LOG_MESSAGES};
 use canister_api_macros::trace;
 use canister_logger::{set_panic_hook, LogMessage, LogMessagesWrapper};
-use ic_cdk::api::stable::BufferedStableReader;
 use ic_cdk_macros::post_upgrade;
 use index_canister::post_upgrade::Args;
+use std::io::BufReader;
 use tracing::info;
 use utils::env::canister::CanisterEnv;
+use utils::env::Environment;
+use utils::stable_memory::StableReader;
 
 #[post_upgrade]
 #[trace]
@@ -14,14 +16,17 @@ fn post_upgrade(args: Args) {
     set_panic_hook();
 
     let env = Box::new(CanisterEnv::new());
-    let reader = BufferedStableReader::new(BUFFER_SIZE);
+    let reader = BufReader::with_capacity(BUFFER_SIZE, StableReader::new(canister_logger::stable_state_offset()));
 
     let (mut data, log_messages, trace_messages): (Data, Vec<LogMessage>, Vec<LogMessage>) =
         serializer::deserialize(reader).unwrap();
 
     data.hydrate_blobs_owned();
+    data.seed_user_buckets();
+    data.start_usage_accrual(env.now());
+    data.record_bucket_canister_wasm_hash();
 
-    init_logger(data.test_mode);
+    init_logger(data.test_mode, &data.log_settings);
     init_state(env, data, args.wasm_version);
 
     if !log_messages.is_empty() || !trace_messages.is_empty() {
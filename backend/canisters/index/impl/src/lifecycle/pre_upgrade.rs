@@ -1,9 +1,10 @@
 use crate::lifecycle::BUFFER_SIZE;
 use crate::{take_state, LOG_MESSAGES};
 use canister_api_macros::trace;
-use ic_cdk::api::stable::BufferedStableWriter;
 use ic_cdk_macros::pre_upgrade;
+use std::io::BufWriter;
 use tracing::info;
+use utils::stable_memory::StableWriter;
 
 #[pre_upgrade]
 #[trace]
@@ -16,7 +17,8 @@ fn pre_upgrade() {
     let log_messages = messages_container.logs.drain_messages();
     let trace_messages = messages_container.traces.drain_messages();
 
+    // The state is written after the log messages held in stable memory so that they are preserved
     let stable_state = (state.data, log_messages, trace_messages);
-    let writer = BufferedStableWriter::new(BUFFER_SIZE);
+    let writer = BufWriter::with_capacity(BUFFER_SIZE, StableWriter::new(canister_logger::stable_state_offset()));
     serializer::serialize(&stable_state, writer).unwrap();
 }
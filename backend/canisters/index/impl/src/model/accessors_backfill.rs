@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use types::{AccessorId, CanisterId};
+
+// Used to fill in the buckets of each accessor for state which predates accessors being tracked.
+// Each bucket is visited in turn, paging through the accessors of its files.
+#[derive(Serialize, Deserialize, Default)]
+pub struct AccessorsBackfill {
+    buckets_remaining: VecDeque<CanisterId>,
+    after: Option<AccessorId>,
+    // Not persisted so that a call which was in flight during an upgrade doesn't stall the backfill
+    #[serde(skip)]
+    in_progress: bool,
+}
+
+impl AccessorsBackfill {
+    pub fn new(buckets: impl IntoIterator<Item = CanisterId>) -> AccessorsBackfill {
+        AccessorsBackfill {
+            buckets_remaining: buckets.into_iter().collect(),
+            after: None,
+            in_progress: false,
+        }
+    }
+
+    pub fn try_start_next(&mut self) -> Option<(CanisterId, Option<AccessorId>)> {
+        if self.in_progress {
+            None
+        } else {
+            let canister_id = *self.buckets_remaining.front()?;
+            self.in_progress = true;
+            Some((canister_id, self.after))
+        }
+    }
+
+    // If the page was full, the next page of the same bucket is fetched, otherwise it moves on to the
+    // next bucket
+    pub fn mark_page_completed(&mut self, last_accessor: Option<AccessorId>, page_full: bool) {
+        self.in_progress = false;
+        if page_full {
+            self.after = last_accessor;
+        } else {
+            self.buckets_remaining.pop_front();
+            self.after = None;
+        }
+    }
+
+    pub fn mark_page_failed(&mut self) {
+        self.in_progress = false;
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.buckets_remaining.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+
+    #[test]
+    fn pages_through_each_bucket_in_turn() {
+        let bucket1 = Principal::from_slice(&[1]);
+        let bucket2 = Principal::from_slice(&[2]);
+        let accessor = Principal::from_slice(&[10]);
+        let mut backfill = AccessorsBackfill::new([bucket1, bucket2]);
+
+        assert_eq!(backfill.try_start_next(), Some((bucket1, None)));
+        assert_eq!(backfill.try_start_next(), None);
+
+        backfill.mark_page_completed(Some(accessor), true);
+        assert_eq!(backfill.try_start_next(), Some((bucket1, Some(accessor))));
+
+        backfill.mark_page_failed();
+        assert_eq!(backfill.try_start_next(), Some((bucket1, Some(accessor))));
+
+        backfill.mark_page_completed(None, false);
+        assert_eq!(backfill.try_start_next(), Some((bucket2, None)));
+
+        backfill.mark_page_completed(None, false);
+        assert!(backfill.is_complete());
+        assert_eq!(backfill.try_start_next(), None);
+    }
+}
@@ -38,6 +38,27 @@ impl Blobs {
         None
     }
 
+    // Overwrites the number of references the user has to the blob within the bucket, this should
+    // only be used to repair the references after they are found to be out of sync with the bucket
+    pub fn set_reference_count(&mut self, hash: Hash, size: u64, user_id: UserId, bucket: CanisterId, count: u32) {
+        if count == 0 {
+            if let Occupied(mut e) = self.blobs.entry(hash) {
+                let blob_record = e.get_mut();
+                blob_record.set_reference_count(user_id, bucket, 0);
+                if blob_record.owners.is_empty() {
+                    e.remove();
+                }
+            }
+        } else {
+            let blob_record = self.blobs.entry(hash).or_insert(BlobRecord {
+                owners: HashMap::new(),
+                size,
+            });
+            blob_record.size = size;
+            blob_record.set_reference_count(user_id, bucket, count);
+        }
+    }
+
     pub fn update_user_id(&mut self, hash: &Hash, old_user_id: UserId, new_user_id: UserId) {
         if let Some(blob) = self.blobs.get_mut(hash) {
             blob.update_user_id(old_user_id, new_user_id);
@@ -50,6 +71,40 @@ impl Blobs {
             .and_then(|b| b.owners.values().flatten().map(|rc| rc.bucket).next())
     }
 
+    pub fn size(&self, hash: &Hash) -> Option<u64> {
+        self.blobs.get(hash).map(|b| b.size)
+    }
+
+    // Returns (hash, size, owner, reference count) for each owner of each blob held by the bucket
+    pub fn references_in_bucket<'a>(&'a self, bucket: &'a CanisterId) -> impl Iterator<Item = (Hash, u64, UserId, u32)> + 'a {
+        self.blobs.iter().flat_map(move |(hash, blob)| {
+            blob.owners.iter().flat_map(move |(user_id, reference_counts)| {
+                reference_counts
+                    .iter()
+                    .filter(move |rc| &rc.bucket == bucket)
+                    .map(move |rc| (*hash, blob.size, *user_id, rc.count))
+            })
+        })
+    }
+
+    pub fn has_reference(&self, hash: &Hash, user_id: &UserId, bucket: &CanisterId) -> bool {
+        self.blobs
+            .get(hash)
+            .and_then(|b| b.owners.get(user_id))
+            .map_or(false, |refs| refs.iter().any(|rc| &rc.bucket == bucket && rc.count > 0))
+    }
+
+    // Returns the number of files the user owns across all buckets, given the hashes of the blobs they own
+    pub fn user_file_count<'a>(&self, user_id: &UserId, blobs_owned: impl IntoIterator<Item = &'a Hash>) -> u64 {
+        blobs_owned
+            .into_iter()
+            .filter_map(|h| self.blobs.get(h))
+            .filter_map(|b| b.owners.get(user_id))
+            .flatten()
+            .map(|rc| rc.count as u64)
+            .sum()
+    }
+
     pub fn user_owns_blob(&self, user_id: &UserId, hash: &Hash) -> bool {
         self.blobs.get(hash).map_or(false, |b| b.owners.contains_key(user_id))
     }
@@ -122,6 +177,16 @@ impl BlobRecord {
         removed_from_user
     }
 
+    pub fn set_reference_count(&mut self, user_id: UserId, bucket: CanisterId, count: u32) {
+        let reference_counts = self.owners.entry(user_id).or_default();
+        reference_counts.retain(|rc| rc.bucket != bucket);
+        if count > 0 {
+            reference_counts.push(ReferenceCount::new(bucket, count));
+        } else if reference_counts.is_empty() {
+            self.owners.remove(&user_id);
+        }
+    }
+
     pub fn update_user_id(&mut self, old_user_id: UserId, new_user_id: UserId) {
         if let Some(refs) = self.owners.remove(&old_user_id) {
             self.owners.insert(new_user_id, refs);
@@ -140,6 +205,14 @@ impl ReferenceCount {
         ReferenceCount { bucket, count }
     }
 
+    pub fn bucket(&self) -> CanisterId {
+        self.bucket
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
     fn incr(&mut self) -> u32 {
         self.count += 1;
         self.count
@@ -194,4 +267,22 @@ mod tests {
 
         assert!(blobs.blobs.is_empty());
     }
+
+    #[test]
+    fn user_file_count_includes_every_copy() {
+        let mut blobs = Blobs::default();
+        let user1 = Principal::from_slice(&[1]);
+        let user2 = Principal::from_slice(&[2]);
+        let bucket1 = Principal::from_slice(&[0, 1]);
+        let bucket2 = Principal::from_slice(&[0, 2]);
+
+        blobs.add([0; 32], 100, user1, bucket1);
+        blobs.add([0; 32], 100, user1, bucket1);
+        blobs.add([0; 32], 100, user1, bucket2);
+        blobs.add([0; 32], 100, user2, bucket1);
+        blobs.add([1; 32], 100, user1, bucket1);
+
+        assert_eq!(blobs.user_file_count(&user1, &[[0; 32], [1; 32]]), 4);
+        assert_eq!(blobs.user_file_count(&user2, &[[0; 32]]), 1);
+    }
 }
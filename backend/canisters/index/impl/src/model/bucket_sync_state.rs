@@ -1,64 +1,226 @@
-use crate::MAX_EVENTS_TO_SYNC_PER_BATCH;
+use crate::model::retry_state::{FailedResult, NextAttempt, RetryState};
 use bucket_canister::c2c_sync_index::Args;
+use candid::CandidType;
+use index_canister::DeadLetter;
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
-use types::{AccessorId, UserId};
+use types::{AccessorId, BucketConfig, LogSettings, Milliseconds, TimestampMillis, UserId, UserLimits};
 
 // We want to send events to the each bucket in order, so while a sync is in progress we avoid sending
 // more events in case the first batch fails and the second succeeds. If a sync fails, the args that
-// were sent are stored so that they can be retried again.
+// were sent are stored so that they can be retried again. Each batch is given a new sequence number
+// (retries reuse the original) so that the bucket can detect batches it has already applied. If the
+// bucket rejects a batch as being out of order, the batch's events are requeued and numbering
+// restarts from the last batch the bucket applied.
+//
+// Failed batches are retried with exponential backoff by the `RetryState`. Once a batch has failed too
+// many times it is moved to the dead letters so that it no longer blocks the queue. Dead letters can then be inspected
+// and either discarded, split into smaller batches or replayed, in which case they are given a new
+// sequence number and sent ahead of any queued events.
 #[derive(Serialize, Deserialize, Default)]
 pub struct BucketSyncState {
-    queue: VecDeque<EventToSync>,
-    in_progress: bool,
-    args_to_retry: Option<Args>,
+    queue: VecDeque<QueuedEvent>,
+    #[serde(flatten)]
+    retry_state: RetryState<Args>,
+    #[serde(default)]
+    last_sequence_number: u64,
+    #[serde(default)]
+    replays: VecDeque<Args>,
+    #[serde(default)]
+    dead_letters: Vec<DeadLetter>,
+    #[serde(default)]
+    next_dead_letter_id: u64,
+}
+
+pub enum SyncFailedResult {
+    RetryAt(TimestampMillis),
+    DeadLettered(u64),
 }
 
 impl BucketSyncState {
-    pub fn enqueue(&mut self, event: EventToSync) {
-        self.queue.push_back(event);
+    pub fn enqueue(&mut self, event: EventToSync, now: TimestampMillis) {
+        self.queue.push_back(QueuedEvent {
+            event,
+            enqueued_at: Some(now),
+        });
     }
 
-    pub fn pop_args_for_next_sync(&mut self) -> Option<Args> {
-        if self.in_progress {
-            None
-        } else if let Some(args) = self.args_to_retry.take() {
-            self.in_progress = true;
+    pub fn pop_args_for_next_sync(&mut self, max_events: usize, now: TimestampMillis) -> Option<Args> {
+        match self.retry_state.next_attempt(now) {
+            NextAttempt::Wait => None,
+            NextAttempt::Retry(args) => Some(args),
+            NextAttempt::Ready => {
+                let args = self.pop_args_for_new_sync(max_events)?;
+                self.retry_state.mark_started();
+                Some(args)
+            }
+        }
+    }
+
+    fn pop_args_for_new_sync(&mut self, max_events: usize) -> Option<Args> {
+        if let Some(mut args) = self.replays.pop_front() {
+            args.sequence_number = self.next_sequence_number();
             Some(args)
         } else if self.queue.is_empty() {
             None
         } else {
-            let mut args = Args {
-                users_added: Vec::new(),
-                users_removed: Vec::new(),
-                accessors_removed: Vec::new(),
-                user_ids_updated: Vec::new(),
-            };
-
-            for _ in 0..MAX_EVENTS_TO_SYNC_PER_BATCH {
-                if let Some(event) = self.queue.pop_front() {
-                    match event {
-                        EventToSync::UserAdded(a) => args.users_added.push(a),
-                        EventToSync::UserRemoved(r) => args.users_removed.push(r),
-                        EventToSync::AccessorRemoved(r) => args.accessors_removed.push(r),
-                        EventToSync::UserIdUpdated(old, new) => args.user_ids_updated.push((old, new)),
-                    }
-                } else {
-                    break;
-                }
-            }
-            self.in_progress = true;
-            Some(args)
+            let count = max_events.min(self.queue.len());
+            let events = self.queue.drain(..count).map(|e| e.event).collect();
+            let sequence_number = self.next_sequence_number();
+            Some(args_from_events(events, sequence_number))
         }
     }
 
+    pub fn is_idle(&self) -> bool {
+        self.retry_state.is_idle() && self.replays.is_empty() && self.queue.is_empty()
+    }
+
+    // The time at which the failed batch is due to be retried, if there is one
+    pub fn next_retry_at(&self) -> Option<TimestampMillis> {
+        self.retry_state.next_retry_at()
+    }
+
+    pub fn last_sequence_number(&self) -> u64 {
+        self.last_sequence_number
+    }
+
     pub fn mark_sync_completed(&mut self) {
-        self.in_progress = false;
+        self.retry_state.mark_completed();
+    }
+
+    pub fn mark_sync_failed(&mut self, args: Args, max_attempts: u32, now: TimestampMillis) -> SyncFailedResult {
+        match self.retry_state.mark_failed(args, max_attempts, now) {
+            FailedResult::RetryAt(retry_at) => SyncFailedResult::RetryAt(retry_at),
+            FailedResult::GaveUp(args, attempts) => {
+                let id = self.next_dead_letter_id;
+                self.next_dead_letter_id += 1;
+                self.dead_letters.push(DeadLetter {
+                    id,
+                    args,
+                    attempts,
+                    dead_lettered_at: now,
+                });
+                SyncFailedResult::DeadLettered(id)
+            }
+        }
+    }
+
+    // The requeued events keep no timestamp since the time they were originally enqueued is unknown
+    pub fn mark_sync_out_of_order(&mut self, args: Args, last_applied: u64) {
+        self.retry_state.mark_completed();
+        for event in events_from_args(&args).into_iter().rev() {
+            self.queue.push_front(QueuedEvent {
+                event,
+                enqueued_at: None,
+            });
+        }
+        self.last_sequence_number = last_applied;
+    }
+
+    // Dead letters are ordered by id since ids are allocated in increasing order
+    pub fn dead_letters(&self, start_after: Option<u64>, max_results: usize) -> (Vec<DeadLetter>, u32) {
+        let dead_letters = self
+            .dead_letters
+            .iter()
+            .filter(|d| start_after.map_or(true, |s| d.id > s))
+            .take(max_results)
+            .cloned()
+            .collect();
+
+        (dead_letters, self.dead_letters.len() as u32)
+    }
+
+    pub fn replay_dead_letter(&mut self, id: u64) -> bool {
+        if let Some(dead_letter) = self.take_dead_letter(id) {
+            self.replays.push_back(dead_letter.args);
+            true
+        } else {
+            false
+        }
+    }
+
+    // Splits the dead letter's events into two new dead letters, so that the event causing the failure
+    // can be isolated. Returns the ids of the new dead letters.
+    pub fn split_dead_letter(&mut self, id: u64) -> Option<(u64, u64)> {
+        let index = self.dead_letters.iter().position(|d| d.id == id)?;
+        let mut events = events_from_args(&self.dead_letters[index].args);
+        if events.len() < 2 {
+            return None;
+        }
+
+        let dead_letter = self.dead_letters.remove(index);
+        let second_half = events.split_off(events.len() / 2);
+        let first_id = self.next_dead_letter_id;
+        let second_id = first_id + 1;
+        self.next_dead_letter_id += 2;
+
+        for (id, events) in [(first_id, events), (second_id, second_half)] {
+            let mut args = args_from_events(events, 0);
+            args.correlation_id = dead_letter.args.correlation_id.clone();
+            self.dead_letters.push(DeadLetter {
+                id,
+                args,
+                attempts: dead_letter.attempts,
+                dead_lettered_at: dead_letter.dead_lettered_at,
+            });
+        }
+        Some((first_id, second_id))
+    }
+
+    pub fn discard_dead_letter(&mut self, id: u64) -> bool {
+        self.take_dead_letter(id).is_some()
+    }
+
+    pub fn metrics(&self, now: TimestampMillis) -> BucketSyncMetrics {
+        BucketSyncMetrics {
+            queue_length: self.queue.len() as u32,
+            oldest_queued_event_age: self.queue.front().and_then(|e| e.enqueued_at).map(|t| now.saturating_sub(t)),
+            failed_attempts: self.retry_state.failed_attempts(),
+            next_retry_at: self.retry_state.next_retry_at(),
+            replays_pending: self.replays.len() as u32,
+            dead_letters: self.dead_letters.len() as u32,
+        }
     }
 
-    pub fn mark_sync_failed(&mut self, args: Args) {
-        self.in_progress = false;
-        self.args_to_retry = Some(args);
+    fn take_dead_letter(&mut self, id: u64) -> Option<DeadLetter> {
+        let index = self.dead_letters.iter().position(|d| d.id == id)?;
+        Some(self.dead_letters.remove(index))
+    }
+
+    fn next_sequence_number(&mut self) -> u64 {
+        self.last_sequence_number += 1;
+        self.last_sequence_number
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(from = "QueuedEventPrevious")]
+struct QueuedEvent {
+    event: EventToSync,
+    // None for events queued before timestamps were recorded
+    enqueued_at: Option<TimestampMillis>,
+}
+
+// Events queued before timestamps were recorded were stored without them
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum QueuedEventPrevious {
+    WithTimestamp {
+        event: EventToSync,
+        enqueued_at: Option<TimestampMillis>,
+    },
+    WithoutTimestamp(EventToSync),
+}
+
+impl From<QueuedEventPrevious> for QueuedEvent {
+    fn from(value: QueuedEventPrevious) -> Self {
+        match value {
+            QueuedEventPrevious::WithTimestamp { event, enqueued_at } => QueuedEvent { event, enqueued_at },
+            QueuedEventPrevious::WithoutTimestamp(event) => QueuedEvent {
+                event,
+                enqueued_at: None,
+            },
+        }
     }
 }
 
@@ -68,4 +230,133 @@ pub enum EventToSync {
     UserRemoved(UserId),
     AccessorRemoved(AccessorId),
     UserIdUpdated(UserId, UserId),
+    ConfigUpdated(BucketConfig),
+    LogSettingsUpdated(LogSettings),
+    UserLimitsUpdated(UserId, UserLimits),
+}
+
+#[derive(CandidType, Serialize, Debug)]
+pub struct BucketSyncMetrics {
+    pub queue_length: u32,
+    pub oldest_queued_event_age: Option<Milliseconds>,
+    pub failed_attempts: u32,
+    pub next_retry_at: Option<TimestampMillis>,
+    pub replays_pending: u32,
+    pub dead_letters: u32,
+}
+
+fn args_from_events(events: Vec<EventToSync>, sequence_number: u64) -> Args {
+    let mut args = Args {
+        users_added: Vec::new(),
+        users_removed: Vec::new(),
+        accessors_removed: Vec::new(),
+        user_ids_updated: Vec::new(),
+        config: None,
+        user_limits_updated: Vec::new(),
+        sequence_number,
+        log_settings: None,
+        correlation_id: None,
+    };
+
+    for event in events {
+        match event {
+            EventToSync::UserAdded(a) => args.users_added.push(a),
+            EventToSync::UserRemoved(r) => args.users_removed.push(r),
+            EventToSync::AccessorRemoved(r) => args.accessors_removed.push(r),
+            EventToSync::UserIdUpdated(old, new) => args.user_ids_updated.push((old, new)),
+            EventToSync::ConfigUpdated(c) => args.config = Some(c),
+            EventToSync::LogSettingsUpdated(s) => args.log_settings = Some(s),
+            EventToSync::UserLimitsUpdated(u, l) => args.user_limits_updated.push((u, l)),
+        }
+    }
+    args
+}
+
+fn events_from_args(args: &Args) -> Vec<EventToSync> {
+    args.users_added
+        .iter()
+        .map(|u| EventToSync::UserAdded(*u))
+        .chain(args.users_removed.iter().map(|u| EventToSync::UserRemoved(*u)))
+        .chain(args.accessors_removed.iter().map(|a| EventToSync::AccessorRemoved(*a)))
+        .chain(args.user_ids_updated.iter().map(|(o, n)| EventToSync::UserIdUpdated(*o, *n)))
+        .chain(args.config.iter().map(|c| EventToSync::ConfigUpdated(c.clone())))
+        .chain(args.log_settings.iter().map(|s| EventToSync::LogSettingsUpdated(s.clone())))
+        .chain(
+            args.user_limits_updated
+                .iter()
+                .map(|(u, l)| EventToSync::UserLimitsUpdated(*u, l.clone())),
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+    use utils::time::MINUTE_IN_MS;
+
+    #[test]
+    fn failing_batch_retried_with_backoff_then_dead_lettered() {
+        let mut sync_state = BucketSyncState::default();
+        sync_state.enqueue(EventToSync::UserRemoved(Principal::from_slice(&[1])), 0);
+
+        let args = sync_state.pop_args_for_next_sync(10, 0).unwrap();
+        assert!(matches!(sync_state.mark_sync_failed(args, 3, 0), SyncFailedResult::RetryAt(t) if t == MINUTE_IN_MS));
+
+        assert!(sync_state.pop_args_for_next_sync(10, MINUTE_IN_MS - 1).is_none());
+        let args = sync_state.pop_args_for_next_sync(10, MINUTE_IN_MS).unwrap();
+        assert!(
+            matches!(sync_state.mark_sync_failed(args, 3, MINUTE_IN_MS), SyncFailedResult::RetryAt(t) if t == 3 * MINUTE_IN_MS)
+        );
+
+        let args = sync_state.pop_args_for_next_sync(10, 3 * MINUTE_IN_MS).unwrap();
+        assert!(matches!(
+            sync_state.mark_sync_failed(args, 3, 0),
+            SyncFailedResult::DeadLettered(0)
+        ));
+
+        // The queue is no longer blocked
+        sync_state.enqueue(EventToSync::UserRemoved(Principal::from_slice(&[2])), 0);
+        let args = sync_state.pop_args_for_next_sync(10, 0).unwrap();
+        assert_eq!(args.sequence_number, 2);
+        assert_eq!(sync_state.dead_letters(None, 10).1, 1);
+    }
+
+    #[test]
+    fn split_then_replay_dead_letter() {
+        let mut sync_state = BucketSyncState::default();
+        for i in 0..3 {
+            sync_state.enqueue(EventToSync::UserRemoved(Principal::from_slice(&[i])), 0);
+        }
+        let args = sync_state.pop_args_for_next_sync(10, 0).unwrap();
+        sync_state.mark_sync_failed(args, 1, 0);
+
+        let (first, second) = sync_state.split_dead_letter(0).unwrap();
+        assert_eq!(sync_state.dead_letters(None, 10).1, 2);
+        assert!(sync_state.split_dead_letter(first).is_none());
+
+        let (page, _) = sync_state.dead_letters(Some(first), 10);
+        assert_eq!(page.iter().map(|d| d.id).collect::<Vec<_>>(), vec![second]);
+
+        assert!(sync_state.replay_dead_letter(second));
+        let args = sync_state.pop_args_for_next_sync(10, 0).unwrap();
+        assert_eq!(args.users_removed.len(), 2);
+        assert_eq!(args.sequence_number, 2);
+        assert!(sync_state.discard_dead_letter(first));
+        assert_eq!(sync_state.dead_letters(None, 10).1, 0);
+    }
+
+    #[test]
+    fn events_queued_without_timestamps_can_be_deserialized() {
+        let events: VecDeque<EventToSync> = [1, 2]
+            .into_iter()
+            .map(|i| EventToSync::UserRemoved(Principal::from_slice(&[i])))
+            .collect();
+        let mut bytes = Vec::new();
+        serializer::serialize(&events, &mut bytes).unwrap();
+
+        let queue: VecDeque<QueuedEvent> = serializer::deserialize(bytes.as_slice()).unwrap();
+        assert_eq!(queue.len(), 2);
+        assert!(queue.iter().all(|e| e.enqueued_at.is_none()));
+    }
 }
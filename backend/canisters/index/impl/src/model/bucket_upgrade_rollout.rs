@@ -0,0 +1,183 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use types::{CanisterId, Version};
+
+pub const DEFAULT_MAX_UPGRADE_FAILURES: u32 = 3;
+
+// Tracks the progress of rolling out a new bucket canister wasm. The canaries are upgraded first and
+// the remaining buckets are held back until every canary has been upgraded successfully. If the
+// number of failures reaches `max_failures` the rollout is halted until it is manually resumed. If a
+// canary fails the rollout is halted straight away and the held back buckets are released from it.
+#[derive(Serialize, Deserialize)]
+pub struct BucketUpgradeRollout {
+    wasm_version: Version,
+    canaries: HashSet<CanisterId>,
+    held_back: Vec<CanisterId>,
+    max_failures: u32,
+    failures: u32,
+    halted: bool,
+}
+
+impl Default for BucketUpgradeRollout {
+    fn default() -> Self {
+        BucketUpgradeRollout {
+            wasm_version: Version::min(),
+            canaries: HashSet::new(),
+            held_back: Vec::new(),
+            max_failures: DEFAULT_MAX_UPGRADE_FAILURES,
+            failures: 0,
+            halted: false,
+        }
+    }
+}
+
+impl BucketUpgradeRollout {
+    // Returns the rollout along with the buckets which can be upgraded straight away
+    pub fn start(
+        wasm_version: Version,
+        canaries: Vec<CanisterId>,
+        others: Vec<CanisterId>,
+        max_failures: u32,
+    ) -> (BucketUpgradeRollout, Vec<CanisterId>) {
+        let mut rollout = BucketUpgradeRollout {
+            wasm_version,
+            max_failures,
+            ..Default::default()
+        };
+
+        if canaries.is_empty() || others.is_empty() {
+            (rollout, canaries.into_iter().chain(others).collect())
+        } else {
+            rollout.canaries = canaries.iter().copied().collect();
+            rollout.held_back = others;
+            (rollout, canaries)
+        }
+    }
+
+    // Returns the buckets which are released once the final canary has been upgraded
+    pub fn mark_success(&mut self, canister_id: &CanisterId) -> Vec<CanisterId> {
+        if self.canaries.remove(canister_id) && self.canaries.is_empty() {
+            std::mem::take(&mut self.held_back)
+        } else {
+            Vec::new()
+        }
+    }
+
+    // Buckets whose upgrades were in progress when the rollout started join it once those upgrades
+    // complete. Returns true if the bucket is held back behind the remaining canaries.
+    pub fn hold_back(&mut self, canister_id: CanisterId) -> bool {
+        if self.canaries.is_empty() {
+            false
+        } else {
+            self.held_back.push(canister_id);
+            true
+        }
+    }
+
+    pub fn mark_failure(&mut self, canister_id: &CanisterId) -> RolloutFailure {
+        self.failures += 1;
+        if self.canaries.remove(canister_id) {
+            self.canaries.clear();
+            self.halted = true;
+            RolloutFailure::CanaryFailed(std::mem::take(&mut self.held_back))
+        } else if !self.halted && self.failures >= self.max_failures {
+            self.halted = true;
+            RolloutFailure::Halted
+        } else {
+            RolloutFailure::Counted
+        }
+    }
+
+    pub fn resume(&mut self) {
+        self.failures = 0;
+        self.halted = false;
+    }
+
+    pub fn status(&self) -> BucketUpgradeRolloutStatus {
+        BucketUpgradeRolloutStatus {
+            wasm_version: self.wasm_version,
+            canaries_remaining: self.canaries.len() as u32,
+            held_back: self.held_back.len() as u32,
+            failures: self.failures,
+            max_failures: self.max_failures,
+            halted: self.halted,
+        }
+    }
+}
+
+pub enum RolloutFailure {
+    Counted,
+    // The number of failures has reached `max_failures`
+    Halted,
+    // The held back buckets are returned so that they can be recorded as failed upgrades, allowing
+    // them to be retried along with the canary once the failure has been investigated
+    CanaryFailed(Vec<CanisterId>),
+}
+
+#[derive(CandidType, Serialize, Debug)]
+pub struct BucketUpgradeRolloutStatus {
+    pub wasm_version: Version,
+    pub canaries_remaining: u32,
+    pub held_back: u32,
+    pub failures: u32,
+    pub max_failures: u32,
+    pub halted: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+
+    fn canister_id(i: u8) -> CanisterId {
+        Principal::from_slice(&[i])
+    }
+
+    #[test]
+    fn held_back_buckets_released_once_all_canaries_succeed() {
+        let (mut rollout, to_upgrade_now) = BucketUpgradeRollout::start(
+            Version::new(1, 0, 0),
+            vec![canister_id(1), canister_id(2)],
+            vec![canister_id(3), canister_id(4)],
+            DEFAULT_MAX_UPGRADE_FAILURES,
+        );
+
+        assert_eq!(to_upgrade_now, vec![canister_id(1), canister_id(2)]);
+        assert!(rollout.mark_success(&canister_id(1)).is_empty());
+        assert_eq!(rollout.mark_success(&canister_id(2)), vec![canister_id(3), canister_id(4)]);
+        assert!(rollout.mark_success(&canister_id(3)).is_empty());
+    }
+
+    #[test]
+    fn rollout_halted_after_max_failures() {
+        let (mut rollout, _) = BucketUpgradeRollout::start(Version::new(1, 0, 0), Vec::new(), vec![canister_id(1)], 2);
+
+        assert!(matches!(rollout.mark_failure(&canister_id(1)), RolloutFailure::Counted));
+        assert!(matches!(rollout.mark_failure(&canister_id(1)), RolloutFailure::Halted));
+        assert!(rollout.status().halted);
+
+        rollout.resume();
+
+        assert!(!rollout.status().halted);
+        assert_eq!(rollout.status().failures, 0);
+    }
+
+    #[test]
+    fn held_back_buckets_released_when_canary_fails() {
+        let (mut rollout, _) = BucketUpgradeRollout::start(
+            Version::new(1, 0, 0),
+            vec![canister_id(1)],
+            vec![canister_id(2)],
+            DEFAULT_MAX_UPGRADE_FAILURES,
+        );
+        assert!(rollout.hold_back(canister_id(3)));
+
+        assert!(
+            matches!(rollout.mark_failure(&canister_id(1)), RolloutFailure::CanaryFailed(held_back) if held_back == vec![canister_id(2), canister_id(3)])
+        );
+        assert!(rollout.status().halted);
+        assert_eq!(rollout.status().held_back, 0);
+        assert!(!rollout.hold_back(canister_id(4)));
+    }
+}
@@ -1,18 +1,39 @@
 use crate::model::bucket_sync_state::BucketSyncState;
 use crate::model::bucket_sync_state::EventToSync;
+use crate::model::users_snapshot::UsersSnapshot;
 use arrayref::array_ref;
-use bucket_canister::c2c_sync_index;
+use bucket_canister::{c2c_sync_index, c2c_sync_users_snapshot};
+use candid::CandidType;
+use index_canister::c2c_sync_bucket;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use types::{CanisterId, CyclesTopUp, Hash, Version};
-
-const TARGET_ACTIVE_BUCKETS: usize = 4;
+use types::{CanisterId, CyclesTopUp, Hash, TimestampMillis, UserId, Version};
+use utils::canister::Pool;
+use utils::sync_receiver::SyncReceiver;
 
 #[derive(Serialize, Deserialize, Default)]
 pub struct Buckets {
     active_buckets: Vec<BucketRecord>,
     full_buckets: HashMap<CanisterId, BucketRecord>,
+    // Empty buckets which have already been created and installed, ready to be activated as soon as
+    // one of the active buckets becomes full
+    #[serde(default)]
+    pool: Pool<BucketRecord>,
     creation_in_progress: bool,
+    #[serde(default)]
+    targeted_sync_metrics: TargetedSyncMetrics,
+}
+
+// Tracks the events which are only sent to the buckets they are relevant to, along with how many
+// bucket enqueues were saved compared to sending each of those events to every bucket, and how many
+// events still had to be broadcast because the buckets they are relevant to weren't known
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Default, Debug)]
+pub struct TargetedSyncMetrics {
+    pub events: u64,
+    pub bucket_enqueues: u64,
+    pub bucket_enqueues_skipped: u64,
+    #[serde(default)]
+    pub broadcasts: u64,
 }
 
 impl Buckets {
@@ -21,21 +42,24 @@ impl Buckets {
             .iter()
             .find(|b| &b.canister_id == canister_id)
             .or_else(|| self.full_buckets.get(canister_id))
+            .or_else(|| self.pool.iter().find(|b| &b.canister_id == canister_id))
     }
 
     pub fn get_mut(&mut self, canister_id: &CanisterId) -> Option<&mut BucketRecord> {
         if let Some(bucket) = self.active_buckets.iter_mut().find(|b| &b.canister_id == canister_id) {
             Some(bucket)
+        } else if let Some(bucket) = self.full_buckets.get_mut(canister_id) {
+            Some(bucket)
         } else {
-            self.full_buckets.get_mut(canister_id)
+            self.pool.iter_mut().find(|b| &b.canister_id == canister_id)
         }
     }
 
-    pub fn try_to_acquire_creation_lock(&mut self) -> bool {
+    pub fn try_to_acquire_creation_lock(&mut self, target_active_buckets: usize, pool_target_size: usize) -> bool {
         if self.creation_in_progress {
             false
         } else {
-            self.creation_in_progress = self.active_buckets.len() < TARGET_ACTIVE_BUCKETS;
+            self.creation_in_progress = self.active_buckets.len() < target_active_buckets || self.pool.len() < pool_target_size;
             self.creation_in_progress
         }
     }
@@ -44,13 +68,31 @@ impl Buckets {
         self.creation_in_progress = false;
     }
 
-    pub fn add_bucket(&mut self, bucket: BucketRecord, release_creation_lock: bool) {
-        self.active_buckets.push(bucket);
+    // New buckets go straight into the active set if it is below its target size, otherwise they are
+    // held in the pool until they are needed
+    pub fn add_bucket(&mut self, bucket: BucketRecord, release_creation_lock: bool, target_active_buckets: usize) {
+        if self.active_buckets.len() < target_active_buckets {
+            self.active_buckets.push(bucket);
+        } else {
+            self.pool.push(bucket);
+        }
         if release_creation_lock {
             self.release_creation_lock();
         }
     }
 
+    // Moves buckets from the pool into the active set until either the active set is back up to its
+    // target size or the pool is empty
+    pub fn activate_pooled_buckets(&mut self, target_active_buckets: usize) {
+        while self.active_buckets.len() < target_active_buckets {
+            if let Some(bucket) = self.pool.pop() {
+                self.active_buckets.push(bucket);
+            } else {
+                break;
+            }
+        }
+    }
+
     pub fn allocate(&self, blob_hash: Hash) -> Option<CanisterId> {
         let bucket_count = self.active_buckets.len();
         if bucket_count == 0 {
@@ -64,27 +106,91 @@ impl Buckets {
             Some(self.active_buckets[index].canister_id)
         }
     }
-    pub fn sync_event(&mut self, event: EventToSync) {
+    pub fn sync_event(&mut self, event: EventToSync, now: TimestampMillis) {
         for bucket in self.iter_mut() {
-            bucket.sync_state.enqueue(event.clone());
+            bucket.sync_state.enqueue(event.clone(), now);
         }
     }
 
-    pub fn pop_args_for_next_sync(&mut self) -> Vec<(CanisterId, c2c_sync_index::Args)> {
+    pub fn sync_event_to_buckets<'a>(
+        &mut self,
+        canister_ids: impl IntoIterator<Item = &'a CanisterId>,
+        event: EventToSync,
+        now: TimestampMillis,
+    ) {
+        let mut enqueued = 0;
+        for canister_id in canister_ids {
+            if let Some(bucket) = self.get_mut(canister_id) {
+                bucket.sync_state.enqueue(event.clone(), now);
+                enqueued += 1;
+            }
+        }
+
+        let bucket_count = self.iter().count() as u64;
+        let metrics = &mut self.targeted_sync_metrics;
+        metrics.events += 1;
+        metrics.bucket_enqueues += enqueued;
+        metrics.bucket_enqueues_skipped += bucket_count.saturating_sub(enqueued);
+    }
+
+    pub fn sync_untargeted_event(&mut self, event: EventToSync, now: TimestampMillis) {
+        self.sync_event(event, now);
+        self.targeted_sync_metrics.broadcasts += 1;
+    }
+
+    pub fn targeted_sync_metrics(&self) -> TargetedSyncMetrics {
+        self.targeted_sync_metrics
+    }
+
+    pub fn pop_args_for_next_sync(
+        &mut self,
+        max_events: usize,
+        now: TimestampMillis,
+    ) -> Vec<(CanisterId, c2c_sync_index::Args)> {
         self.iter_mut()
             .filter_map(|bucket| {
                 bucket
                     .sync_state
-                    .pop_args_for_next_sync()
+                    .pop_args_for_next_sync(max_events, now)
+                    .map(|args| (bucket.canister_id, args))
+            })
+            .collect()
+    }
+
+    // The earliest time at which any bucket's failed sync batch is due to be retried
+    pub fn next_sync_retry_at(&self) -> Option<TimestampMillis> {
+        self.iter().filter_map(|b| b.sync_state.next_retry_at()).min()
+    }
+
+    pub fn pop_users_snapshot_pages(&mut self, page_size: usize) -> Vec<(CanisterId, c2c_sync_users_snapshot::Args)> {
+        self.iter_mut()
+            .filter_map(|bucket| {
+                bucket
+                    .users_snapshot
+                    .as_mut()
+                    .and_then(|s| s.pop_args_for_next_page(page_size))
                     .map(|args| (bucket.canister_id, args))
             })
             .collect()
     }
 
-    pub fn archive(&mut self, canister_id: CanisterId) {
+    pub fn remove_user_from_users_snapshots(&mut self, user_id: &UserId) {
+        for snapshot in self.iter_mut().filter_map(|b| b.users_snapshot.as_mut()) {
+            snapshot.remove_user(user_id);
+        }
+    }
+
+    pub fn update_user_id_in_users_snapshots(&mut self, old_user_id: UserId, new_user_id: UserId) {
+        for snapshot in self.iter_mut().filter_map(|b| b.users_snapshot.as_mut()) {
+            snapshot.update_user_id(old_user_id, new_user_id);
+        }
+    }
+
+    pub fn archive(&mut self, canister_id: CanisterId, target_active_buckets: usize) {
         if let Some(index) = self.active_buckets.iter().position(|b| b.canister_id == canister_id) {
             let bucket = self.active_buckets.remove(index);
             self.full_buckets.insert(canister_id, bucket);
+            self.activate_pooled_buckets(target_active_buckets);
         }
     }
 
@@ -98,7 +204,9 @@ impl Buckets {
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &BucketRecord> {
-        self.iter_active_buckets().chain(self.iter_full_buckets())
+        self.iter_active_buckets()
+            .chain(self.iter_full_buckets())
+            .chain(self.iter_pooled_buckets())
     }
 
     pub fn iter_active_buckets(&self) -> impl Iterator<Item = &BucketRecord> {
@@ -109,8 +217,15 @@ impl Buckets {
         self.full_buckets.values()
     }
 
+    pub fn iter_pooled_buckets(&self) -> impl Iterator<Item = &BucketRecord> {
+        self.pool.iter()
+    }
+
     fn iter_mut(&mut self) -> impl Iterator<Item = &mut BucketRecord> {
-        self.active_buckets.iter_mut().chain(self.full_buckets.values_mut())
+        self.active_buckets
+            .iter_mut()
+            .chain(self.full_buckets.values_mut())
+            .chain(self.pool.iter_mut())
     }
 }
 
@@ -121,6 +236,16 @@ pub struct BucketRecord {
     pub bytes_used: u64,
     pub sync_state: BucketSyncState,
     pub cycle_top_ups: Vec<CyclesTopUp>,
+    // The module hash reported by the bucket's `canister_status` when it was last checked
+    #[serde(default)]
+    pub module_hash: Option<Hash>,
+    #[serde(default)]
+    pub module_hash_checked: Option<TimestampMillis>,
+    // Used to detect batches of events from the bucket which have already been applied
+    #[serde(default)]
+    pub sync_receiver: SyncReceiver<c2c_sync_bucket::SuccessResult>,
+    #[serde(default)]
+    pub users_snapshot: Option<UsersSnapshot>,
 }
 
 impl BucketRecord {
@@ -131,6 +256,64 @@ impl BucketRecord {
             bytes_used: 0,
             sync_state: BucketSyncState::default(),
             cycle_top_ups: Vec::new(),
+            module_hash: None,
+            module_hash_checked: None,
+            sync_receiver: SyncReceiver::default(),
+            users_snapshot: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+
+    const TARGET_ACTIVE_BUCKETS: usize = 4;
+    const BUCKET_POOL_TARGET_SIZE: usize = 1;
+
+    #[test]
+    fn archiving_full_bucket_activates_pooled_bucket() {
+        let mut buckets = Buckets::default();
+
+        for i in 0..(TARGET_ACTIVE_BUCKETS + 1) {
+            let bucket = BucketRecord::new(Principal::from_slice(&[i as u8]), Version::min());
+            buckets.add_bucket(bucket, false, TARGET_ACTIVE_BUCKETS);
         }
+
+        assert_eq!(buckets.iter_active_buckets().count(), TARGET_ACTIVE_BUCKETS);
+        assert_eq!(buckets.iter_pooled_buckets().count(), 1);
+        assert!(!buckets.try_to_acquire_creation_lock(TARGET_ACTIVE_BUCKETS, BUCKET_POOL_TARGET_SIZE));
+
+        buckets.archive(Principal::from_slice(&[0]), TARGET_ACTIVE_BUCKETS);
+
+        assert_eq!(buckets.iter_active_buckets().count(), TARGET_ACTIVE_BUCKETS);
+        assert_eq!(buckets.iter_full_buckets().count(), 1);
+        assert_eq!(buckets.iter_pooled_buckets().count(), 0);
+        assert!(buckets.try_to_acquire_creation_lock(TARGET_ACTIVE_BUCKETS, BUCKET_POOL_TARGET_SIZE));
+    }
+
+    #[test]
+    fn targeted_events_only_sent_to_given_buckets() {
+        let mut buckets = Buckets::default();
+
+        for i in 0..TARGET_ACTIVE_BUCKETS {
+            let bucket = BucketRecord::new(Principal::from_slice(&[i as u8]), Version::min());
+            buckets.add_bucket(bucket, false, TARGET_ACTIVE_BUCKETS);
+        }
+
+        let user_id = Principal::from_slice(&[100]);
+        let targets = [Principal::from_slice(&[1]), Principal::from_slice(&[100])];
+        buckets.sync_event_to_buckets(&targets, EventToSync::UserRemoved(user_id), 0);
+
+        let args = buckets.pop_args_for_next_sync(10, 0);
+        assert_eq!(args.len(), 1);
+        assert_eq!(args[0].0, Principal::from_slice(&[1]));
+        assert_eq!(args[0].1.users_removed, vec![user_id]);
+
+        let metrics = buckets.targeted_sync_metrics();
+        assert_eq!(metrics.events, 1);
+        assert_eq!(metrics.bucket_enqueues, 1);
+        assert_eq!(metrics.bucket_enqueues_skipped, TARGET_ACTIVE_BUCKETS as u64 - 1);
     }
 }
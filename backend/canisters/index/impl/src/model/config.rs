@@ -0,0 +1,248 @@
+use candid::CandidType;
+use index_canister::set_config::Args as SetConfigArgs;
+use serde::{Deserialize, Serialize};
+use types::{BucketConfig, Cycles, Milliseconds};
+use utils::time::{DAY_IN_MS, WEEK_IN_MS};
+
+// Chunks are uploaded via ingress messages which are limited to 2Mb, so leave some headroom for the
+// other args
+const MAX_CHUNK_SIZE_BYTES: u32 = (1 << 21) - (1 << 16);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Config {
+    pub target_active_buckets: u32,
+    pub bucket_pool_target_size: u16,
+    pub chunk_size_bytes: u32,
+    pub max_events_to_sync_per_batch: u32,
+    pub max_concurrent_canister_upgrades: u32,
+    // The number of times a batch of events can fail to sync to a bucket before it is dead lettered
+    #[serde(default = "default_max_sync_attempts")]
+    pub max_sync_attempts: u32,
+    // Events are dropped from the event feed once there are more than `max_feed_events` or once they
+    // are older than `feed_retention`
+    #[serde(default = "default_max_feed_events")]
+    pub max_feed_events: u32,
+    #[serde(default = "default_feed_retention")]
+    pub feed_retention: Milliseconds,
+    // The percentages of their byte limit at which users are flagged as nearing their limit. An event
+    // is added to the event feed each time a user's usage reaches a higher threshold.
+    #[serde(default = "default_quota_alert_thresholds")]
+    pub quota_alert_thresholds: Vec<u8>,
+    // Each user's accrued storage usage is snapshotted at every `usage_snapshot_interval` boundary so
+    // that usage can be calculated for billing periods starting and ending on those boundaries. The
+    // most recent `max_usage_snapshots` snapshots are kept.
+    #[serde(default = "default_usage_snapshot_interval")]
+    pub usage_snapshot_interval: Milliseconds,
+    #[serde(default = "default_max_usage_snapshots")]
+    pub max_usage_snapshots: u32,
+    pub min_cycles_balance_for_bucket_creation: Cycles,
+    pub min_cycles_balance_for_top_ups: Cycles,
+    pub bucket_canister_initial_cycles_balance: Cycles,
+    pub bucket_canister_top_up_amount: Cycles,
+    pub bucket: BucketConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            target_active_buckets: 4,
+            bucket_pool_target_size: 1,
+            chunk_size_bytes: 1 << 19, // 1/2 Mb
+            max_events_to_sync_per_batch: 10000,
+            max_concurrent_canister_upgrades: 1,
+            max_sync_attempts: default_max_sync_attempts(),
+            max_feed_events: default_max_feed_events(),
+            feed_retention: default_feed_retention(),
+            quota_alert_thresholds: default_quota_alert_thresholds(),
+            usage_snapshot_interval: default_usage_snapshot_interval(),
+            max_usage_snapshots: default_max_usage_snapshots(),
+            min_cycles_balance_for_bucket_creation: 60_000_000_000_000, // 60T
+            min_cycles_balance_for_top_ups: 10_000_000_000_000,         // 10T
+            bucket_canister_initial_cycles_balance: 10_000_000_000_000, // 10T
+            bucket_canister_top_up_amount: 1_000_000_000_000,           // 1T
+            bucket: BucketConfig::default(),
+        }
+    }
+}
+
+fn default_max_sync_attempts() -> u32 {
+    10
+}
+
+fn default_max_feed_events() -> u32 {
+    100_000
+}
+
+fn default_feed_retention() -> Milliseconds {
+    WEEK_IN_MS
+}
+
+fn default_quota_alert_thresholds() -> Vec<u8> {
+    vec![80, 95, 100]
+}
+
+fn default_usage_snapshot_interval() -> Milliseconds {
+    DAY_IN_MS
+}
+
+fn default_max_usage_snapshots() -> u32 {
+    62
+}
+
+impl Config {
+    // Returns a copy of the config with the changes applied, or an error if the resulting config is
+    // invalid
+    pub fn with_changes(&self, args: SetConfigArgs) -> Result<Config, String> {
+        let mut config = self.clone();
+
+        if let Some(target_active_buckets) = args.target_active_buckets {
+            config.target_active_buckets = target_active_buckets;
+        }
+        if let Some(bucket_pool_target_size) = args.bucket_pool_target_size {
+            config.bucket_pool_target_size = bucket_pool_target_size;
+        }
+        if let Some(chunk_size_bytes) = args.chunk_size_bytes {
+            config.chunk_size_bytes = chunk_size_bytes;
+        }
+        if let Some(max_events_to_sync_per_batch) = args.max_events_to_sync_per_batch {
+            config.max_events_to_sync_per_batch = max_events_to_sync_per_batch;
+        }
+        if let Some(max_concurrent_canister_upgrades) = args.max_concurrent_canister_upgrades {
+            config.max_concurrent_canister_upgrades = max_concurrent_canister_upgrades;
+        }
+        if let Some(max_sync_attempts) = args.max_sync_attempts {
+            config.max_sync_attempts = max_sync_attempts;
+        }
+        if let Some(max_feed_events) = args.max_feed_events {
+            config.max_feed_events = max_feed_events;
+        }
+        if let Some(feed_retention) = args.feed_retention {
+            config.feed_retention = feed_retention;
+        }
+        if let Some(quota_alert_thresholds) = args.quota_alert_thresholds {
+            config.quota_alert_thresholds = quota_alert_thresholds;
+        }
+        if let Some(usage_snapshot_interval) = args.usage_snapshot_interval {
+            config.usage_snapshot_interval = usage_snapshot_interval;
+        }
+        if let Some(max_usage_snapshots) = args.max_usage_snapshots {
+            config.max_usage_snapshots = max_usage_snapshots;
+        }
+        if let Some(min_cycles_balance) = args.min_cycles_balance_for_bucket_creation {
+            config.min_cycles_balance_for_bucket_creation = min_cycles_balance;
+        }
+        if let Some(min_cycles_balance) = args.min_cycles_balance_for_top_ups {
+            config.min_cycles_balance_for_top_ups = min_cycles_balance;
+        }
+        if let Some(initial_cycles_balance) = args.bucket_canister_initial_cycles_balance {
+            config.bucket_canister_initial_cycles_balance = initial_cycles_balance;
+        }
+        if let Some(top_up_amount) = args.bucket_canister_top_up_amount {
+            config.bucket_canister_top_up_amount = top_up_amount;
+        }
+        if let Some(bucket_config) = args.bucket {
+            config.bucket = bucket_config;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.target_active_buckets == 0 {
+            Err("'target_active_buckets' must be greater than 0".to_string())
+        } else if self.chunk_size_bytes == 0 || self.chunk_size_bytes > MAX_CHUNK_SIZE_BYTES {
+            Err(format!("'chunk_size_bytes' must be between 1 and {}", MAX_CHUNK_SIZE_BYTES))
+        } else if self.max_events_to_sync_per_batch == 0 || self.bucket.max_events_to_sync_per_batch == 0 {
+            Err("'max_events_to_sync_per_batch' must be greater than 0".to_string())
+        } else if self.max_concurrent_canister_upgrades == 0 {
+            Err("'max_concurrent_canister_upgrades' must be greater than 0".to_string())
+        } else if self.max_sync_attempts == 0 {
+            Err("'max_sync_attempts' must be greater than 0".to_string())
+        } else if self.max_feed_events == 0 {
+            Err("'max_feed_events' must be greater than 0".to_string())
+        } else if self.quota_alert_thresholds.iter().any(|t| *t == 0 || *t > 100)
+            || self.quota_alert_thresholds.windows(2).any(|w| w[0] >= w[1])
+        {
+            Err("'quota_alert_thresholds' must be increasing percentages between 1 and 100".to_string())
+        } else if self.usage_snapshot_interval == 0 {
+            Err("'usage_snapshot_interval' must be greater than 0".to_string())
+        } else if self.max_usage_snapshots == 0 {
+            Err("'max_usage_snapshots' must be greater than 0".to_string())
+        } else if self.bucket_canister_top_up_amount == 0 {
+            Err("'bucket_canister_top_up_amount' must be greater than 0".to_string())
+        } else if self.bucket.data_limit_bytes == 0 || self.bucket.data_limit_bytes > i64::MAX as u64 {
+            Err("'data_limit_bytes' is out of range".to_string())
+        } else if self.bucket.max_blob_size_bytes == 0 || self.bucket.max_blob_size_bytes > self.bucket.data_limit_bytes {
+            Err("'max_blob_size_bytes' must be greater than 0 and no larger than 'data_limit_bytes'".to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_args() -> SetConfigArgs {
+        SetConfigArgs {
+            target_active_buckets: None,
+            bucket_pool_target_size: None,
+            chunk_size_bytes: None,
+            max_events_to_sync_per_batch: None,
+            max_concurrent_canister_upgrades: None,
+            max_sync_attempts: None,
+            max_feed_events: None,
+            feed_retention: None,
+            quota_alert_thresholds: None,
+            usage_snapshot_interval: None,
+            max_usage_snapshots: None,
+            min_cycles_balance_for_bucket_creation: None,
+            min_cycles_balance_for_top_ups: None,
+            bucket_canister_initial_cycles_balance: None,
+            bucket_canister_top_up_amount: None,
+            bucket: None,
+        }
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn changes_are_applied() {
+        let args = SetConfigArgs {
+            target_active_buckets: Some(6),
+            chunk_size_bytes: Some(1 << 20),
+            ..empty_args()
+        };
+
+        let config = Config::default().with_changes(args).unwrap();
+
+        assert_eq!(config.target_active_buckets, 6);
+        assert_eq!(config.chunk_size_bytes, 1 << 20);
+        assert_eq!(config.bucket, BucketConfig::default());
+    }
+
+    #[test]
+    fn invalid_changes_are_rejected() {
+        let args = SetConfigArgs {
+            bucket: Some(BucketConfig {
+                max_blob_size_bytes: 2 << 30,
+                ..BucketConfig::default()
+            }),
+            ..empty_args()
+        };
+
+        assert!(Config::default().with_changes(args).is_err());
+
+        let args = SetConfigArgs {
+            quota_alert_thresholds: Some(vec![95, 80]),
+            ..empty_args()
+        };
+
+        assert!(Config::default().with_changes(args).is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use index_canister::events::{Event, IndexedEvent};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use types::{CanisterId, Milliseconds, TimestampMillis};
+
+// An ordered feed of the changes to files and users, which service principals can consume in order
+// to mirror the index's state. Each event is given an index which is used as the cursor. Events are
+// dropped once they fall outside of the retention limits, after which any consumer whose cursor is
+// older than the oldest event retained must resync from scratch.
+#[derive(Serialize, Deserialize, Default)]
+pub struct EventFeed {
+    events: VecDeque<IndexedEvent>,
+    next_index: u64,
+}
+
+pub enum EventsResult {
+    Success(EventsPage),
+    CursorTooOld(u64),
+}
+
+pub struct EventsPage {
+    pub events: Vec<IndexedEvent>,
+    pub next_cursor: Option<u64>,
+    pub caught_up: bool,
+}
+
+impl EventFeed {
+    pub fn push(&mut self, bucket: Option<CanisterId>, event: Event, now: TimestampMillis) -> &IndexedEvent {
+        self.events.push_back(IndexedEvent {
+            index: self.next_index,
+            timestamp: now,
+            bucket,
+            event,
+        });
+        self.next_index += 1;
+        self.events.back().unwrap()
+    }
+
+    pub fn prune(&mut self, max_events: usize, max_age: Milliseconds, now: TimestampMillis) {
+        while self.events.len() > max_events
+            || self
+                .events
+                .front()
+                .map_or(false, |e| now.saturating_sub(e.timestamp) > max_age)
+        {
+            self.events.pop_front();
+        }
+    }
+
+    pub fn events(&self, after: Option<u64>, bucket: Option<CanisterId>, max_results: usize) -> EventsResult {
+        let oldest_index = self.oldest_index();
+        let start = after.map_or(oldest_index, |a| a.saturating_add(1));
+        if start < oldest_index {
+            return EventsResult::CursorTooOld(oldest_index);
+        }
+
+        let mut events = Vec::new();
+        let mut next_cursor = after;
+        let mut caught_up = true;
+        for event in self.events.iter().skip((start - oldest_index) as usize) {
+            if events.len() == max_results {
+                caught_up = false;
+                break;
+            }
+            if bucket.map_or(true, |b| event.bucket == Some(b)) {
+                events.push(event.clone());
+            }
+            next_cursor = Some(event.index);
+        }
+
+        EventsResult::Success(EventsPage {
+            events,
+            next_cursor,
+            caught_up,
+        })
+    }
+
+    pub fn oldest_index(&self) -> u64 {
+        self.next_index - self.events.len() as u64
+    }
+
+    pub fn latest_index(&self) -> Option<u64> {
+        self.next_index.checked_sub(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+
+    fn user(id: u8) -> Principal {
+        Principal::from_slice(&[id])
+    }
+
+    #[test]
+    fn events_paged_and_filtered_by_bucket() {
+        let bucket = user(10);
+        let mut feed = EventFeed::default();
+        feed.push(None, Event::UserAdded(user(1)), 0);
+        feed.push(Some(bucket), Event::UserRemoved(user(2)), 0);
+        feed.push(None, Event::UserAdded(user(3)), 0);
+
+        let page = match feed.events(None, None, 2) {
+            EventsResult::Success(p) => p,
+            EventsResult::CursorTooOld(_) => panic!(),
+        };
+        assert_eq!(page.events.len(), 2);
+        assert_eq!(page.next_cursor, Some(1));
+        assert!(!page.caught_up);
+
+        let page = match feed.events(None, Some(bucket), 10) {
+            EventsResult::Success(p) => p,
+            EventsResult::CursorTooOld(_) => panic!(),
+        };
+        assert_eq!(page.events.len(), 1);
+        assert_eq!(page.next_cursor, Some(2));
+        assert!(page.caught_up);
+    }
+
+    #[test]
+    fn cursor_too_old_once_events_pruned() {
+        let mut feed = EventFeed::default();
+        for i in 0..5 {
+            feed.push(None, Event::UserAdded(user(i)), i as u64 * 10);
+        }
+        feed.prune(3, 100, 40);
+        assert_eq!(feed.oldest_index(), 2);
+        feed.prune(10, 15, 40);
+        assert_eq!(feed.oldest_index(), 3);
+
+        assert!(matches!(feed.events(Some(1), None, 10), EventsResult::CursorTooOld(3)));
+        assert!(matches!(feed.events(Some(2), None, 10), EventsResult::Success(p) if p.events.len() == 2));
+    }
+
+    #[test]
+    fn cursor_at_max_index_returns_no_events() {
+        let mut feed = EventFeed::default();
+        feed.push(None, Event::UserAdded(user(1)), 0);
+
+        assert!(matches!(feed.events(Some(u64::MAX), None, 10), EventsResult::Success(p) if p.events.is_empty()));
+    }
+}
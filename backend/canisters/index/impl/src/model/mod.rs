@@ -1,3 +1,14 @@
+pub mod accessors_backfill;
 pub mod blobs;
 pub mod bucket_sync_state;
+pub mod bucket_upgrade_rollout;
 pub mod buckets;
+pub mod config;
+pub mod event_feed;
+pub mod quotas;
+pub mod reconciliation;
+pub mod retry_state;
+pub mod staged_wasm;
+pub mod subscribers;
+pub mod usage;
+pub mod users_snapshot;
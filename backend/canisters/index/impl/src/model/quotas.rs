@@ -0,0 +1,30 @@
+// Returns the highest threshold, as a percentage of the byte limit, which the usage has reached.
+// Users with no allowance can't upload anything so are never flagged.
+pub fn highest_threshold_reached(bytes_used: u64, byte_limit: u64, thresholds: &[u8]) -> Option<u8> {
+    if byte_limit == 0 {
+        return None;
+    }
+
+    thresholds
+        .iter()
+        .rev()
+        .find(|t| bytes_used as u128 * 100 >= **t as u128 * byte_limit as u128)
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highest_threshold_reached_is_returned() {
+        let thresholds = [80, 95, 100];
+
+        assert_eq!(highest_threshold_reached(79, 100, &thresholds), None);
+        assert_eq!(highest_threshold_reached(80, 100, &thresholds), Some(80));
+        assert_eq!(highest_threshold_reached(99, 100, &thresholds), Some(95));
+        assert_eq!(highest_threshold_reached(150, 100, &thresholds), Some(100));
+        assert_eq!(highest_threshold_reached(u64::MAX, u64::MAX, &thresholds), Some(100));
+        assert_eq!(highest_threshold_reached(0, 0, &thresholds), None);
+    }
+}
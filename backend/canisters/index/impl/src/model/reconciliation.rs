@@ -0,0 +1,302 @@
+use crate::model::blobs::Blobs;
+use bucket_canister::c2c_files;
+use index_canister::reconciliation_report::{Discrepancy, ReconciliationReport};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use types::{CanisterId, FileId, Hash, TimestampMillis, UserId};
+
+const MAX_DISCREPANCIES_TO_REPORT: usize = 1000;
+
+// Compares the files each bucket actually holds against the index's blob references, one bucket at
+// a time and one page of files at a time. The result of the most recent completed run is kept so
+// that it can be inspected by the service principals.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Reconciliation {
+    run: Option<ReconciliationRun>,
+    last_report: Option<ReconciliationReport>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ReconciliationRun {
+    buckets_remaining: VecDeque<CanisterId>,
+    current: Option<BucketScan>,
+    report: ReconciliationReport,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BucketScan {
+    pub canister_id: CanisterId,
+    start_after: Option<FileId>,
+    // The sequence numbers of the syncs between the index and the bucket when the scan started
+    sequence_numbers: Option<(u64, u64)>,
+    changed_during_scan: bool,
+    files: HashMap<(Hash, UserId), FileReferences>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct FileReferences {
+    size: u64,
+    count: u32,
+}
+
+impl Reconciliation {
+    pub fn start(&mut self, buckets: Vec<CanisterId>, repair: bool, now: TimestampMillis) -> bool {
+        if self.run.is_some() {
+            return false;
+        }
+
+        self.run = Some(ReconciliationRun {
+            buckets_remaining: buckets.into_iter().collect(),
+            current: None,
+            report: ReconciliationReport {
+                started: now,
+                completed: None,
+                repair,
+                buckets_checked: 0,
+                buckets_skipped: Vec::new(),
+                discrepancy_count: 0,
+                discrepancies: Vec::new(),
+            },
+        });
+        true
+    }
+
+    pub fn repair(&self) -> bool {
+        self.run.as_ref().map_or(false, |r| r.report.repair)
+    }
+
+    // Returns the bucket and cursor for the next page of files to fetch. Once every bucket has been
+    // scanned the run is completed and None is returned.
+    pub fn next_page(&mut self, now: TimestampMillis) -> Option<(CanisterId, Option<FileId>)> {
+        let run = self.run.as_mut()?;
+
+        if run.current.is_none() {
+            if let Some(canister_id) = run.buckets_remaining.pop_front() {
+                run.current = Some(BucketScan::new(canister_id));
+            } else {
+                let mut report = self.run.take().unwrap().report;
+                report.completed = Some(now);
+                self.last_report = Some(report);
+                return None;
+            }
+        }
+
+        run.current.as_ref().map(|s| (s.canister_id, s.start_after))
+    }
+
+    // Adds a page of files to the current scan and returns the scan once its last page is received
+    pub fn record_page(
+        &mut self,
+        canister_id: CanisterId,
+        page: c2c_files::SuccessResult,
+        max_results: usize,
+    ) -> Option<BucketScan> {
+        let run = self.run.as_mut()?;
+        let scan = run.current.as_mut().filter(|s| s.canister_id == canister_id)?;
+
+        let is_last_page = page.files.len() < max_results;
+        scan.add_page(page);
+
+        if is_last_page {
+            run.current.take()
+        } else {
+            None
+        }
+    }
+
+    pub fn record_bucket_skipped(&mut self, canister_id: CanisterId) {
+        if let Some(run) = self.run.as_mut() {
+            if run.current.as_ref().map_or(false, |s| s.canister_id == canister_id) {
+                run.current = None;
+            }
+            run.report.buckets_skipped.push(canister_id);
+        }
+    }
+
+    pub fn record_bucket_checked(&mut self, discrepancies: Vec<Discrepancy>) {
+        if let Some(run) = self.run.as_mut() {
+            let report = &mut run.report;
+            report.buckets_checked += 1;
+            report.discrepancy_count += discrepancies.len() as u64;
+
+            let space = MAX_DISCREPANCIES_TO_REPORT.saturating_sub(report.discrepancies.len());
+            report.discrepancies.extend(discrepancies.into_iter().take(space));
+        }
+    }
+
+    // Returns the report of the run in progress if there is one, else that of the last completed run
+    pub fn report(&self) -> Option<&ReconciliationReport> {
+        self.run.as_ref().map(|r| &r.report).or(self.last_report.as_ref())
+    }
+}
+
+impl BucketScan {
+    fn new(canister_id: CanisterId) -> BucketScan {
+        BucketScan {
+            canister_id,
+            start_after: None,
+            sequence_numbers: None,
+            changed_during_scan: false,
+            files: HashMap::new(),
+        }
+    }
+
+    fn add_page(&mut self, page: c2c_files::SuccessResult) {
+        let sequence_numbers = (page.last_sequence_number_sent, page.last_sequence_number_received);
+        if page.index_sync_pending || self.sequence_numbers.map_or(false, |s| s != sequence_numbers) {
+            self.changed_during_scan = true;
+        }
+        self.sequence_numbers = Some(sequence_numbers);
+
+        if let Some(last) = page.files.last() {
+            self.start_after = Some(last.file_id);
+        }
+
+        for file in page.files {
+            let references = self.files.entry((file.hash, file.owner)).or_default();
+            references.size = file.size;
+            references.count += 1;
+        }
+    }
+
+    // The files can only be compared if every event between the index and the bucket has been synced
+    // in both directions and the bucket's files didn't change while they were being scanned
+    pub fn is_comparable(
+        &self,
+        last_sequence_number_received_from_bucket: u64,
+        last_sequence_number_sent_to_bucket: u64,
+    ) -> bool {
+        !self.changed_during_scan
+            && self.sequence_numbers == Some((last_sequence_number_received_from_bucket, last_sequence_number_sent_to_bucket))
+    }
+
+    pub fn find_discrepancies(&self, blobs: &Blobs) -> Vec<Discrepancy> {
+        let mut index_references: HashMap<(Hash, UserId), FileReferences> = blobs
+            .references_in_bucket(&self.canister_id)
+            .map(|(hash, size, owner, count)| ((hash, owner), FileReferences { size, count }))
+            .collect();
+
+        let mut discrepancies = Vec::new();
+        for (&(hash, owner), bucket_references) in self.files.iter() {
+            let index_references = index_references.remove(&(hash, owner));
+
+            let matches = index_references.as_ref().map_or(false, |i| {
+                i.count == bucket_references.count && i.size == bucket_references.size
+            });
+
+            if !matches {
+                discrepancies.push(Discrepancy {
+                    bucket: self.canister_id,
+                    hash,
+                    owner,
+                    index_reference_count: index_references.as_ref().map_or(0, |i| i.count),
+                    bucket_reference_count: bucket_references.count,
+                    index_size: index_references.map(|i| i.size),
+                    bucket_size: Some(bucket_references.size),
+                });
+            }
+        }
+
+        // Anything left is referenced by the index but no longer held by the bucket
+        for ((hash, owner), index_references) in index_references {
+            discrepancies.push(Discrepancy {
+                bucket: self.canister_id,
+                hash,
+                owner,
+                index_reference_count: index_references.count,
+                bucket_reference_count: 0,
+                index_size: Some(index_references.size),
+                bucket_size: None,
+            });
+        }
+
+        discrepancies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bucket_canister::c2c_files::FileSummary;
+    use candid::Principal;
+
+    fn page(files: Vec<FileSummary>, last_sequence_number_sent: u64) -> c2c_files::SuccessResult {
+        c2c_files::SuccessResult {
+            files,
+            index_sync_pending: false,
+            last_sequence_number_sent,
+            last_sequence_number_received: 0,
+        }
+    }
+
+    fn file(file_id: FileId, owner: UserId, hash: Hash) -> FileSummary {
+        FileSummary {
+            file_id,
+            owner,
+            hash,
+            size: 100,
+        }
+    }
+
+    #[test]
+    fn discrepancies_found_in_both_directions() {
+        let bucket = Principal::from_slice(&[1]);
+        let user = Principal::from_slice(&[2]);
+        let mut blobs = Blobs::default();
+        blobs.add([1; 32], 100, user, bucket);
+        blobs.add([2; 32], 100, user, bucket);
+
+        let mut reconciliation = Reconciliation::default();
+        reconciliation.start(vec![bucket], false, 0);
+
+        assert_eq!(reconciliation.next_page(0), Some((bucket, None)));
+        let files = vec![file(1, user, [1; 32]), file(2, user, [3; 32])];
+        assert!(reconciliation.record_page(bucket, page(files, 5), 2).is_none());
+
+        assert_eq!(reconciliation.next_page(0), Some((bucket, Some(2))));
+        let scan = reconciliation.record_page(bucket, page(Vec::new(), 5), 2).unwrap();
+        assert!(scan.is_comparable(5, 0));
+
+        let mut discrepancies = scan.find_discrepancies(&blobs);
+        discrepancies.sort_by_key(|d| d.hash);
+
+        assert_eq!(discrepancies.len(), 2);
+        assert_eq!(
+            (
+                discrepancies[0].index_reference_count,
+                discrepancies[0].bucket_reference_count
+            ),
+            (1, 0)
+        );
+        assert_eq!(
+            (
+                discrepancies[1].index_reference_count,
+                discrepancies[1].bucket_reference_count
+            ),
+            (0, 1)
+        );
+
+        reconciliation.record_bucket_checked(discrepancies);
+        assert_eq!(reconciliation.next_page(10), None);
+
+        let report = reconciliation.report().unwrap();
+        assert_eq!(report.completed, Some(10));
+        assert_eq!(report.buckets_checked, 1);
+        assert_eq!(report.discrepancy_count, 2);
+    }
+
+    #[test]
+    fn scan_not_comparable_if_bucket_synced_during_scan() {
+        let bucket = Principal::from_slice(&[1]);
+        let user = Principal::from_slice(&[2]);
+
+        let mut reconciliation = Reconciliation::default();
+        reconciliation.start(vec![bucket], false, 0);
+        reconciliation.next_page(0);
+        reconciliation.record_page(bucket, page(vec![file(1, user, [1; 32])], 5), 1);
+        reconciliation.next_page(0);
+        let scan = reconciliation.record_page(bucket, page(Vec::new(), 6), 1).unwrap();
+
+        assert!(!scan.is_comparable(6, 0));
+    }
+}
@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::min;
+use types::{Milliseconds, TimestampMillis};
+use utils::time::{DAY_IN_MS, MINUTE_IN_MS};
+
+const RETRY_BASE_DELAY: Milliseconds = MINUTE_IN_MS;
+const RETRY_MAX_DELAY: Milliseconds = DAY_IN_MS;
+
+// Tracks the batches sent to another canister so that only one is in flight at a time, meaning events
+// arrive in order. If a batch fails it is held so that it can be retried with exponential backoff
+// before any new batch is sent, until it has failed too many times, at which point it is handed back
+// to the caller to deal with.
+//
+// This is flattened into the structs which hold it, so the field names must not change.
+#[derive(Serialize, Deserialize)]
+pub struct RetryState<A> {
+    in_progress: bool,
+    args_to_retry: Option<A>,
+    #[serde(default)]
+    failed_attempts: u32,
+    #[serde(default)]
+    next_retry_at: TimestampMillis,
+}
+
+pub enum NextAttempt<A> {
+    // Either a batch is in flight or the failed batch isn't due to be retried yet
+    Wait,
+    // The failed batch is due to be retried, and has been marked as in flight
+    Retry(A),
+    // A new batch can be sent, which must then be marked as in flight by calling `mark_started`
+    Ready,
+}
+
+pub enum FailedResult<A> {
+    RetryAt(TimestampMillis),
+    GaveUp(A, u32),
+}
+
+impl<A> RetryState<A> {
+    pub fn next_attempt(&mut self, now: TimestampMillis) -> NextAttempt<A> {
+        if self.in_progress || (self.args_to_retry.is_some() && now < self.next_retry_at) {
+            NextAttempt::Wait
+        } else if let Some(args) = self.args_to_retry.take() {
+            self.in_progress = true;
+            NextAttempt::Retry(args)
+        } else {
+            NextAttempt::Ready
+        }
+    }
+
+    pub fn mark_started(&mut self) {
+        self.in_progress = true;
+    }
+
+    pub fn mark_completed(&mut self) {
+        self.in_progress = false;
+        self.failed_attempts = 0;
+    }
+
+    pub fn mark_failed(&mut self, args: A, max_attempts: u32, now: TimestampMillis) -> FailedResult<A> {
+        self.in_progress = false;
+        self.failed_attempts += 1;
+
+        if self.failed_attempts >= max_attempts {
+            let attempts = self.failed_attempts;
+            self.failed_attempts = 0;
+            FailedResult::GaveUp(args, attempts)
+        } else {
+            self.args_to_retry = Some(args);
+            self.next_retry_at = now + retry_delay(self.failed_attempts);
+            FailedResult::RetryAt(self.next_retry_at)
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        !self.in_progress && self.args_to_retry.is_none()
+    }
+
+    pub fn failed_attempts(&self) -> u32 {
+        self.failed_attempts
+    }
+
+    pub fn next_retry_at(&self) -> Option<TimestampMillis> {
+        self.args_to_retry.as_ref().map(|_| self.next_retry_at)
+    }
+}
+
+impl<A> Default for RetryState<A> {
+    fn default() -> Self {
+        RetryState {
+            in_progress: false,
+            args_to_retry: None,
+            failed_attempts: 0,
+            next_retry_at: 0,
+        }
+    }
+}
+
+// The delay before retrying a batch which has failed `failed_attempts` times, doubling after each
+// failure up to a maximum of a day
+fn retry_delay(failed_attempts: u32) -> Milliseconds {
+    2u64.checked_pow(failed_attempts.saturating_sub(1))
+        .and_then(|m| RETRY_BASE_DELAY.checked_mul(m))
+        .map_or(RETRY_MAX_DELAY, |d| min(d, RETRY_MAX_DELAY))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_doubles_up_to_max() {
+        assert_eq!(retry_delay(1), MINUTE_IN_MS);
+        assert_eq!(retry_delay(2), 2 * MINUTE_IN_MS);
+        assert_eq!(retry_delay(3), 4 * MINUTE_IN_MS);
+        assert_eq!(retry_delay(100), DAY_IN_MS);
+    }
+}
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use types::{Hash, Version};
+
+const MAX_WASM_SIZE_BYTES: usize = 32 * (1 << 20); // 32Mb
+
+// Holds the chunks of a wasm module which is being uploaded over multiple calls. Chunks must be
+// uploaded in order and uploading chunk 0 discards anything previously staged.
+#[derive(Serialize, Deserialize, Default)]
+pub struct StagedWasm {
+    version: Option<Version>,
+    next_chunk_index: u32,
+    #[serde(with = "serde_bytes")]
+    bytes: Vec<u8>,
+}
+
+pub enum PutChunkResult {
+    Success,
+    UnexpectedChunkIndex(u32),
+    WasmTooLarge(u64),
+}
+
+pub enum TakeResult {
+    Success(Vec<u8>),
+    NoWasmStaged,
+    VersionMismatch(Version),
+    HashMismatch(Hash),
+}
+
+impl StagedWasm {
+    pub fn put_chunk(&mut self, version: Version, chunk_index: u32, bytes: &[u8]) -> PutChunkResult {
+        if chunk_index == 0 {
+            self.clear();
+            self.version = Some(version);
+        } else if self.version != Some(version) {
+            return PutChunkResult::UnexpectedChunkIndex(0);
+        } else if chunk_index != self.next_chunk_index {
+            return PutChunkResult::UnexpectedChunkIndex(self.next_chunk_index);
+        }
+
+        if self.bytes.len() + bytes.len() > MAX_WASM_SIZE_BYTES {
+            self.clear();
+            return PutChunkResult::WasmTooLarge(MAX_WASM_SIZE_BYTES as u64);
+        }
+
+        self.bytes.extend_from_slice(bytes);
+        self.next_chunk_index += 1;
+        PutChunkResult::Success
+    }
+
+    // Returns the assembled module if its version and sha256 match those expected. The staged wasm is
+    // cleared unless there was nothing staged for the version given.
+    pub fn take(&mut self, version: Version, expected_sha256: Hash) -> TakeResult {
+        match self.version {
+            None => return TakeResult::NoWasmStaged,
+            Some(v) if v != version => return TakeResult::VersionMismatch(v),
+            _ => {}
+        }
+
+        let bytes = std::mem::take(&mut self.bytes);
+        self.clear();
+
+        let sha256: Hash = Sha256::digest(&bytes).into();
+        if sha256 == expected_sha256 {
+            TakeResult::Success(bytes)
+        } else {
+            TakeResult::HashMismatch(sha256)
+        }
+    }
+
+    fn clear(&mut self) {
+        self.version = None;
+        self.next_chunk_index = 0;
+        self.bytes.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_assembled_and_verified() {
+        let mut staged_wasm = StagedWasm::default();
+        let version = Version::new(1, 2, 3);
+        let sha256: Hash = Sha256::digest(&[1, 2, 3, 4, 5]).into();
+
+        assert!(matches!(staged_wasm.put_chunk(version, 0, &[1, 2]), PutChunkResult::Success));
+        assert!(matches!(
+            staged_wasm.put_chunk(version, 2, &[5]),
+            PutChunkResult::UnexpectedChunkIndex(1)
+        ));
+        assert!(matches!(staged_wasm.put_chunk(version, 1, &[3, 4]), PutChunkResult::Success));
+        assert!(matches!(staged_wasm.put_chunk(version, 2, &[5]), PutChunkResult::Success));
+
+        assert!(matches!(staged_wasm.take(version, sha256), TakeResult::Success(bytes) if bytes == vec![1, 2, 3, 4, 5]));
+        assert!(matches!(staged_wasm.take(version, sha256), TakeResult::NoWasmStaged));
+    }
+
+    #[test]
+    fn hash_mismatch_rejected() {
+        let mut staged_wasm = StagedWasm::default();
+        let version = Version::new(1, 2, 3);
+
+        staged_wasm.put_chunk(version, 0, &[1, 2, 3]);
+
+        assert!(matches!(staged_wasm.take(version, [0; 32]), TakeResult::HashMismatch(_)));
+    }
+}
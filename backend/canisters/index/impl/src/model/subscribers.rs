@@ -0,0 +1,321 @@
+use crate::model::retry_state::{FailedResult, NextAttempt, RetryState};
+use index_canister::events::{Event, EventType, IndexedEvent};
+use index_canister::subscriber::c2c_notify_events::Args;
+use index_canister::subscribers::Subscriber as SubscriberSummary;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use types::{CanisterId, TimestampMillis, UserId};
+
+// Leaves some headroom below the 2MB message size limit for the rest of the args
+const MAX_BATCH_SIZE_BYTES: usize = (1 << 21) - (1 << 16);
+
+// Subscriber canisters are sent batches of the events which match their filter. As when syncing events
+// to buckets, only one batch is sent to each subscriber at a time so that events arrive in order, and
+// failed batches are retried with exponential backoff. Once a batch has failed too many times it is
+// dropped so that it no longer blocks the queue. Since every event is also added to the event feed,
+// subscribers can recover any dropped events from there, starting after the last event they received.
+// Queued events which have been pruned from the event feed are dropped too, so that the queue of a
+// subscriber which is unreachable can't grow any larger than the feed.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Subscribers {
+    subscribers: HashMap<CanisterId, Subscriber>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Subscriber {
+    event_types: Vec<EventType>,
+    users: Option<HashSet<UserId>>,
+    queue: VecDeque<IndexedEvent>,
+    #[serde(flatten)]
+    retry_state: RetryState<Args>,
+    batches_dropped: u64,
+    last_delivered_index: Option<u64>,
+    #[serde(default)]
+    events_expired: u64,
+}
+
+pub enum NotifyFailedResult {
+    RetryAt(TimestampMillis),
+    Dropped(usize),
+}
+
+impl Subscribers {
+    // Adds the subscriber, or if it already exists, replaces its filter while keeping any events which
+    // are already queued
+    pub fn set(&mut self, canister_id: CanisterId, event_types: Vec<EventType>, users: Option<Vec<UserId>>) {
+        let users = users.map(|u| u.into_iter().collect());
+        if let Some(subscriber) = self.subscribers.get_mut(&canister_id) {
+            subscriber.event_types = event_types;
+            subscriber.users = users;
+        } else {
+            self.subscribers.insert(
+                canister_id,
+                Subscriber {
+                    event_types,
+                    users,
+                    queue: VecDeque::new(),
+                    retry_state: RetryState::default(),
+                    batches_dropped: 0,
+                    last_delivered_index: None,
+                    events_expired: 0,
+                },
+            );
+        }
+    }
+
+    pub fn remove(&mut self, canister_id: &CanisterId) -> bool {
+        self.subscribers.remove(canister_id).is_some()
+    }
+
+    // Queues the event for each subscriber whose filter it matches
+    pub fn enqueue(&mut self, event: &IndexedEvent) {
+        for subscriber in self.subscribers.values_mut().filter(|s| s.matches(&event.event)) {
+            subscriber.queue.push_back(event.clone());
+        }
+    }
+
+    // Drops any queued events which are older than the oldest event retained by the event feed
+    pub fn expire_events(&mut self, oldest_index: u64) {
+        for subscriber in self.subscribers.values_mut() {
+            while subscriber.queue.front().map_or(false, |e| e.index < oldest_index) {
+                subscriber.queue.pop_front();
+                subscriber.events_expired += 1;
+            }
+        }
+    }
+
+    pub fn pop_args_for_next_notifications(&mut self, max_events: usize, now: TimestampMillis) -> Vec<(CanisterId, Args)> {
+        self.subscribers
+            .iter_mut()
+            .filter_map(|(canister_id, subscriber)| {
+                subscriber
+                    .pop_args_for_next_notification(max_events, now)
+                    .map(|a| (*canister_id, a))
+            })
+            .collect()
+    }
+
+    pub fn mark_notify_completed(&mut self, canister_id: &CanisterId, args: &Args) {
+        if let Some(subscriber) = self.subscribers.get_mut(canister_id) {
+            subscriber.retry_state.mark_completed();
+            if let Some(last) = args.events.last() {
+                subscriber.last_delivered_index = Some(last.index);
+            }
+        }
+    }
+
+    // Returns None if the subscriber has since been removed
+    pub fn mark_notify_failed(
+        &mut self,
+        canister_id: &CanisterId,
+        args: Args,
+        max_attempts: u32,
+        now: TimestampMillis,
+    ) -> Option<NotifyFailedResult> {
+        let subscriber = self.subscribers.get_mut(canister_id)?;
+
+        match subscriber.retry_state.mark_failed(args, max_attempts, now) {
+            FailedResult::RetryAt(retry_at) => Some(NotifyFailedResult::RetryAt(retry_at)),
+            FailedResult::GaveUp(args, _) => {
+                subscriber.batches_dropped += 1;
+                Some(NotifyFailedResult::Dropped(args.events.len()))
+            }
+        }
+    }
+
+    pub fn next_retry_at(&self) -> Option<TimestampMillis> {
+        self.subscribers.values().filter_map(|s| s.retry_state.next_retry_at()).min()
+    }
+
+    pub fn summaries(&self) -> Vec<SubscriberSummary> {
+        self.subscribers
+            .iter()
+            .map(|(canister_id, s)| SubscriberSummary {
+                canister_id: *canister_id,
+                event_types: s.event_types.clone(),
+                users: s.users.as_ref().map(|u| u.iter().copied().collect()),
+                queue_length: s.queue.len() as u32,
+                failed_attempts: s.retry_state.failed_attempts(),
+                next_retry_at: s.retry_state.next_retry_at(),
+                batches_dropped: s.batches_dropped,
+                last_delivered_index: s.last_delivered_index,
+                events_expired: s.events_expired,
+            })
+            .collect()
+    }
+}
+
+impl Subscriber {
+    fn matches(&self, event: &Event) -> bool {
+        self.event_types.contains(&event_type(event))
+            && self
+                .users
+                .as_ref()
+                .map_or(true, |u| u.iter().any(|u| involves_user(event, u)))
+    }
+
+    fn pop_args_for_next_notification(&mut self, max_events: usize, now: TimestampMillis) -> Option<Args> {
+        match self.retry_state.next_attempt(now) {
+            NextAttempt::Wait => None,
+            NextAttempt::Retry(args) => Some(args),
+            NextAttempt::Ready if self.queue.is_empty() => None,
+            NextAttempt::Ready => {
+                let count = batch_len(&self.queue, max_events, MAX_BATCH_SIZE_BYTES);
+                self.retry_state.mark_started();
+                Some(Args {
+                    events: self.queue.drain(..count).collect(),
+                    correlation_id: None,
+                })
+            }
+        }
+    }
+}
+
+// The number of events from the front of the queue to send in the next batch. This is always at least
+// one so that an event which is too large on its own can't block the queue forever.
+fn batch_len(queue: &VecDeque<IndexedEvent>, max_events: usize, max_bytes: usize) -> usize {
+    let mut total_bytes = 0;
+    let count = queue
+        .iter()
+        .take(max_events)
+        .take_while(|e| {
+            total_bytes += candid::encode_one(e).map_or(0, |bytes| bytes.len());
+            total_bytes <= max_bytes
+        })
+        .count();
+
+    count.max(1).min(queue.len())
+}
+
+fn event_type(event: &Event) -> EventType {
+    match event {
+        Event::FileAdded(_) => EventType::FileAdded,
+        Event::FileRemoved(_) => EventType::FileRemoved,
+        Event::FileRejected(_) => EventType::FileRejected,
+        Event::UserAdded(_) => EventType::UserAdded,
+        Event::UserRemoved(_) => EventType::UserRemoved,
+        Event::UserIdUpdated(_) => EventType::UserIdUpdated,
+        Event::QuotaThresholdCrossed(_) => EventType::QuotaThresholdCrossed,
+    }
+}
+
+fn involves_user(event: &Event, user_id: &UserId) -> bool {
+    match event {
+        Event::FileAdded(f) => f.owner == *user_id,
+        Event::FileRemoved(f) => f.owner == *user_id,
+        Event::FileRejected(f) => f.owner.as_ref() == Some(user_id),
+        Event::UserAdded(u) | Event::UserRemoved(u) => u == user_id,
+        Event::UserIdUpdated(u) => u.old_user_id == *user_id || u.new_user_id == *user_id,
+        Event::QuotaThresholdCrossed(q) => q.user_id == *user_id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+    use utils::time::MINUTE_IN_MS;
+
+    fn user(id: u8) -> Principal {
+        Principal::from_slice(&[id])
+    }
+
+    fn indexed(index: u64, event: Event) -> IndexedEvent {
+        IndexedEvent {
+            index,
+            timestamp: 0,
+            bucket: None,
+            event,
+        }
+    }
+
+    #[test]
+    fn events_filtered_by_type_and_user() {
+        let mut subscribers = Subscribers::default();
+        subscribers.set(user(10), vec![EventType::UserRemoved], Some(vec![user(1)]));
+
+        subscribers.enqueue(&indexed(0, Event::UserAdded(user(1))));
+        subscribers.enqueue(&indexed(1, Event::UserRemoved(user(2))));
+        subscribers.enqueue(&indexed(2, Event::UserRemoved(user(1))));
+
+        let batches = subscribers.pop_args_for_next_notifications(10, 0);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].1.events.iter().map(|e| e.index).collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn failing_batch_retried_with_backoff_then_dropped() {
+        let subscriber = user(10);
+        let mut subscribers = Subscribers::default();
+        subscribers.set(subscriber, vec![EventType::UserAdded], None);
+        subscribers.enqueue(&indexed(0, Event::UserAdded(user(1))));
+
+        let (_, args) = subscribers.pop_args_for_next_notifications(10, 0).pop().unwrap();
+        assert!(matches!(
+            subscribers.mark_notify_failed(&subscriber, args, 2, 0),
+            Some(NotifyFailedResult::RetryAt(t)) if t == MINUTE_IN_MS
+        ));
+
+        // Events queued behind the failed batch wait until it has been retried
+        subscribers.enqueue(&indexed(1, Event::UserAdded(user(2))));
+        assert!(subscribers.pop_args_for_next_notifications(10, MINUTE_IN_MS - 1).is_empty());
+        let (_, args) = subscribers.pop_args_for_next_notifications(10, MINUTE_IN_MS).pop().unwrap();
+        assert_eq!(args.events[0].index, 0);
+        assert!(matches!(
+            subscribers.mark_notify_failed(&subscriber, args, 2, MINUTE_IN_MS),
+            Some(NotifyFailedResult::Dropped(1))
+        ));
+
+        let (_, args) = subscribers.pop_args_for_next_notifications(10, MINUTE_IN_MS).pop().unwrap();
+        assert_eq!(args.events[0].index, 1);
+        subscribers.mark_notify_completed(&subscriber, &args);
+
+        let summary = &subscribers.summaries()[0];
+        assert_eq!(summary.batches_dropped, 1);
+        assert_eq!(summary.last_delivered_index, Some(1));
+        assert_eq!(summary.queue_length, 0);
+    }
+
+    #[test]
+    fn batches_capped_by_encoded_size() {
+        let queue: VecDeque<_> = (0..10).map(|i| indexed(i, Event::UserAdded(user(1)))).collect();
+        let event_size = candid::encode_one(&queue[0]).unwrap().len();
+
+        assert_eq!(batch_len(&queue, 100, 3 * event_size), 3);
+        assert_eq!(batch_len(&queue, 2, 3 * event_size), 2);
+        assert_eq!(batch_len(&queue, 100, event_size - 1), 1);
+        assert_eq!(batch_len(&VecDeque::new(), 100, event_size), 0);
+    }
+
+    #[test]
+    fn next_retry_at_is_earliest_across_subscribers() {
+        let mut subscribers = Subscribers::default();
+        for id in [10, 11] {
+            subscribers.set(user(id), vec![EventType::UserAdded], None);
+        }
+        subscribers.enqueue(&indexed(0, Event::UserAdded(user(1))));
+        assert_eq!(subscribers.next_retry_at(), None);
+
+        for (i, (canister_id, args)) in subscribers.pop_args_for_next_notifications(10, 0).into_iter().enumerate() {
+            subscribers.mark_notify_failed(&canister_id, args, 5, i as u64 * 1000);
+        }
+        assert_eq!(subscribers.next_retry_at(), Some(MINUTE_IN_MS));
+    }
+
+    #[test]
+    fn events_pruned_from_feed_expire_from_queue() {
+        let mut subscribers = Subscribers::default();
+        subscribers.set(user(10), vec![EventType::UserAdded], None);
+        for index in 0..5 {
+            subscribers.enqueue(&indexed(index, Event::UserAdded(user(1))));
+        }
+
+        subscribers.expire_events(3);
+
+        let summary = &subscribers.summaries()[0];
+        assert_eq!(summary.queue_length, 2);
+        assert_eq!(summary.events_expired, 3);
+        let (_, args) = subscribers.pop_args_for_next_notifications(10, 0).pop().unwrap();
+        assert_eq!(args.events[0].index, 3);
+    }
+}
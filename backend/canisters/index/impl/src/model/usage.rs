@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use types::{Milliseconds, TimestampMillis};
+
+// Accrues a user's storage usage over time as byte-milliseconds, so that they can be billed for the
+// storage they have actually used over a period. The total is brought up to date each time the user's
+// usage changes. Snapshots of the total are taken at each interval boundary (eg. at midnight each day)
+// so that the usage between any two boundaries can be calculated exactly.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct UsageAccrual {
+    // When usage started to be accrued, either when the user was added or when usage accounting was
+    // first introduced. Zero if accrual hasn't started.
+    started_at: TimestampMillis,
+    // If true the user had no usage before `started_at`, otherwise their usage before then is unknown
+    #[serde(default)]
+    started_when_user_added: bool,
+    byte_ms: u128,
+    bytes_used: u64,
+    last_updated: TimestampMillis,
+    snapshots: VecDeque<UsageSnapshot>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct UsageSnapshot {
+    timestamp: TimestampMillis,
+    byte_ms: u128,
+}
+
+impl UsageAccrual {
+    pub fn new(bytes_used: u64, now: TimestampMillis) -> UsageAccrual {
+        UsageAccrual {
+            started_at: now,
+            started_when_user_added: false,
+            byte_ms: 0,
+            bytes_used,
+            last_updated: now,
+            snapshots: VecDeque::new(),
+        }
+    }
+
+    pub fn new_user(now: TimestampMillis) -> UsageAccrual {
+        UsageAccrual {
+            started_when_user_added: true,
+            ..UsageAccrual::new(0, now)
+        }
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.started_at > 0
+    }
+
+    pub fn last_updated(&self) -> TimestampMillis {
+        self.last_updated
+    }
+
+    pub fn record(&mut self, bytes_used: u64, now: TimestampMillis, interval: Milliseconds, max_snapshots: u32) {
+        // Any boundaries passed since the last change must be snapshotted before the change is applied
+        self.take_snapshots(now, interval, max_snapshots);
+        self.byte_ms = self.accrued_at(now);
+        self.bytes_used = bytes_used;
+        self.last_updated = now;
+    }
+
+    // Snapshots the total at each boundary passed since the last snapshot, keeping only the most recent
+    // `max_snapshots` snapshots
+    pub fn take_snapshots(&mut self, now: TimestampMillis, interval: Milliseconds, max_snapshots: u32) {
+        if !self.is_started() {
+            return;
+        }
+
+        let latest_boundary = now / interval * interval;
+        let oldest_boundary_to_keep = latest_boundary.saturating_sub(max_snapshots.saturating_sub(1) as u64 * interval);
+        let after = self.snapshots.back().map_or(self.started_at, |s| s.timestamp);
+        let mut boundary = ((after / interval + 1) * interval).max(oldest_boundary_to_keep);
+
+        while boundary <= latest_boundary {
+            // The total can only be calculated at times since the last change
+            if boundary >= self.last_updated {
+                self.snapshots.push_back(UsageSnapshot {
+                    timestamp: boundary,
+                    byte_ms: self.accrued_at(boundary),
+                });
+            }
+            boundary += interval;
+        }
+
+        while self.snapshots.len() > max_snapshots as usize {
+            self.snapshots.pop_front();
+        }
+    }
+
+    // Returns the total accrued up to `timestamp`, which must either be a snapshot boundary or a time
+    // since the last change, otherwise None is returned. None is also returned for times before accrual
+    // started, unless it started when the user was added.
+    pub fn byte_ms_at(&self, timestamp: TimestampMillis) -> Option<u128> {
+        if timestamp < self.started_at {
+            self.started_when_user_added.then(|| 0)
+        } else if timestamp == self.started_at {
+            Some(0)
+        } else if timestamp >= self.last_updated {
+            Some(self.accrued_at(timestamp))
+        } else {
+            self.snapshots.iter().find(|s| s.timestamp == timestamp).map(|s| s.byte_ms)
+        }
+    }
+
+    fn accrued_at(&self, timestamp: TimestampMillis) -> u128 {
+        self.byte_ms + self.bytes_used as u128 * timestamp.saturating_sub(self.last_updated) as u128
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Milliseconds = 100;
+
+    #[test]
+    fn usage_accrued_between_changes() {
+        let mut usage = UsageAccrual::new(0, 50);
+        usage.record(10, 60, INTERVAL, 10);
+        usage.record(30, 130, INTERVAL, 10);
+        usage.record(0, 150, INTERVAL, 10);
+
+        // 10 bytes from 60 to 130 then 30 bytes from 130 to 150
+        assert_eq!(usage.byte_ms_at(150), Some(700 + 600));
+        assert_eq!(usage.byte_ms_at(1000), Some(1300));
+        assert_eq!(usage.byte_ms_at(50), Some(0));
+        assert_eq!(usage.byte_ms_at(0), None);
+
+        // The boundary at 100 was snapshotted when the usage changed at 130
+        assert_eq!(usage.byte_ms_at(100), Some(400));
+        assert_eq!(usage.byte_ms_at(120), None);
+    }
+
+    #[test]
+    fn usage_before_accrual_started_only_known_for_new_users() {
+        let existing_user = UsageAccrual::new(10, 150);
+        let new_user = UsageAccrual::new_user(150);
+
+        assert_eq!(existing_user.byte_ms_at(100), None);
+        assert_eq!(new_user.byte_ms_at(100), Some(0));
+        assert_eq!(existing_user.byte_ms_at(200), Some(500));
+        assert_eq!(new_user.byte_ms_at(200), Some(0));
+    }
+
+    #[test]
+    fn snapshots_taken_at_each_boundary_and_oldest_dropped() {
+        let mut usage = UsageAccrual::new(10, 50);
+        usage.take_snapshots(420, INTERVAL, 3);
+
+        assert_eq!(
+            usage.snapshots.iter().map(|s| s.timestamp).collect::<Vec<_>>(),
+            vec![200, 300, 400]
+        );
+        assert_eq!(usage.byte_ms_at(300), Some(2500));
+
+        usage.record(20, 450, INTERVAL, 3);
+        usage.take_snapshots(620, INTERVAL, 3);
+        assert_eq!(
+            usage.snapshots.iter().map(|s| s.timestamp).collect::<Vec<_>>(),
+            vec![400, 500, 600]
+        );
+        assert_eq!(usage.byte_ms_at(600), Some(4000 + 3000));
+        assert_eq!(usage.byte_ms_at(200), None);
+    }
+}
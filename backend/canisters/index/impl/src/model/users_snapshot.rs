@@ -0,0 +1,83 @@
+use bucket_canister::c2c_sync_users_snapshot::Args;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use types::UserId;
+
+// The complete set of users a bucket should know about, which is sent to the bucket a page at a time
+// so that the bucket can bring its own set of users back in line with the index
+#[derive(Serialize, Deserialize)]
+pub struct UsersSnapshot {
+    id: u64,
+    users_remaining: Vec<UserId>,
+    in_progress: bool,
+}
+
+impl UsersSnapshot {
+    pub fn new(id: u64, users: Vec<UserId>) -> UsersSnapshot {
+        UsersSnapshot {
+            id,
+            users_remaining: users,
+            in_progress: false,
+        }
+    }
+
+    pub fn pop_args_for_next_page(&mut self, page_size: usize) -> Option<Args> {
+        if self.in_progress {
+            None
+        } else {
+            let count = self.users_remaining.len().min(page_size);
+            self.in_progress = true;
+
+            Some(Args {
+                snapshot_id: self.id,
+                users: self.users_remaining[..count].to_vec(),
+                is_last_page: count == self.users_remaining.len(),
+            })
+        }
+    }
+
+    pub fn mark_page_sent(&mut self, users: &[UserId]) {
+        let users: HashSet<_> = users.iter().collect();
+        self.users_remaining.retain(|u| !users.contains(u));
+        self.in_progress = false;
+    }
+
+    pub fn mark_page_failed(&mut self) {
+        self.in_progress = false;
+    }
+
+    // Users removed from the index after the snapshot was taken must not be sent to the bucket
+    pub fn remove_user(&mut self, user_id: &UserId) {
+        self.users_remaining.retain(|u| u != user_id);
+    }
+
+    pub fn update_user_id(&mut self, old_user_id: UserId, new_user_id: UserId) {
+        for user_id in self.users_remaining.iter_mut().filter(|u| **u == old_user_id) {
+            *user_id = new_user_id;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+
+    #[test]
+    fn users_sent_in_pages() {
+        let users: Vec<_> = (0..5).map(|i| Principal::from_slice(&[i])).collect();
+        let mut snapshot = UsersSnapshot::new(1, users.clone());
+
+        let args = snapshot.pop_args_for_next_page(3).unwrap();
+        assert!(snapshot.pop_args_for_next_page(3).is_none());
+        assert_eq!(args.users, users[..3]);
+        assert!(!args.is_last_page);
+
+        snapshot.remove_user(&users[4]);
+        snapshot.mark_page_sent(&args.users);
+
+        let args = snapshot.pop_args_for_next_page(3).unwrap();
+        assert_eq!(args.users, users[3..4]);
+        assert!(args.is_last_page);
+    }
+}
@@ -1,8 +1,9 @@
-use crate::{read_state, RuntimeState, DEFAULT_CHUNK_SIZE_BYTES};
+use crate::{read_state, RuntimeState};
 use canister_api_macros::trace;
 use ic_cdk_macros::query;
 use index_canister::allocated_bucket_v2::{Response::*, *};
 use index_canister::ProjectedAllowance;
+use types::FileRejectedReason;
 
 #[query]
 #[trace]
@@ -22,14 +23,25 @@ fn allocated_bucket_impl(args: Args, runtime_state: &RuntimeState) -> Response {
                 .checked_add(args.file_size)
                 .unwrap_or_else(|| panic!("'bytes_used' overflowed for {}", user_id))
         };
+        let projected_allowance = || ProjectedAllowance {
+            byte_limit,
+            bytes_used,
+            bytes_used_after_upload,
+            bytes_used_after_operation: bytes_used_after_upload,
+        };
+
+        if let Err(reason) = user.check_new_file(args.file_size, args.mime_type.as_deref()) {
+            return match reason {
+                FileRejectedReason::AllowanceExceeded => AllowanceExceeded(projected_allowance()),
+                FileRejectedReason::UserNotFound => UserNotFound,
+                FileRejectedReason::FileCountExceeded => FileCountExceeded(user.limits.max_files.unwrap_or_default()),
+                FileRejectedReason::FileTooBig => FileTooBig(user.limits.max_file_size.unwrap_or_default()),
+                FileRejectedReason::MimeTypeNotAllowed => MimeTypeNotAllowed,
+            };
+        }
 
         if bytes_used_after_upload > byte_limit {
-            return AllowanceExceeded(ProjectedAllowance {
-                byte_limit,
-                bytes_used,
-                bytes_used_after_upload,
-                bytes_used_after_operation: bytes_used_after_upload,
-            });
+            return AllowanceExceeded(projected_allowance());
         }
 
         let bucket = runtime_state
@@ -41,16 +53,11 @@ fn allocated_bucket_impl(args: Args, runtime_state: &RuntimeState) -> Response {
         if let Some(canister_id) = bucket {
             Success(SuccessResult {
                 canister_id,
-                chunk_size: DEFAULT_CHUNK_SIZE_BYTES,
+                chunk_size: runtime_state.data.config.chunk_size_bytes,
                 byte_limit,
                 bytes_used,
                 bytes_used_after_upload,
-                projected_allowance: ProjectedAllowance {
-                    byte_limit,
-                    bytes_used,
-                    bytes_used_after_upload,
-                    bytes_used_after_operation: bytes_used_after_upload,
-                },
+                projected_allowance: projected_allowance(),
             })
         } else {
             BucketUnavailable
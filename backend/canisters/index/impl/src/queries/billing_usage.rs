@@ -0,0 +1,85 @@
+use crate::guards::caller_is_service_principal;
+use crate::{read_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::query;
+use index_canister::billing_usage::{Response::*, *};
+use std::ops::Bound::{Excluded, Unbounded};
+
+const MAX_RESULTS_LIMIT: u32 = 1000;
+
+#[query(guard = "caller_is_service_principal")]
+#[trace]
+fn billing_usage(args: Args) -> Response {
+    read_state(|state| billing_usage_impl(args, state))
+}
+
+fn billing_usage_impl(args: Args, runtime_state: &RuntimeState) -> Response {
+    let now = runtime_state.env.now();
+    let interval = runtime_state.data.config.usage_snapshot_interval;
+
+    // Usage can only be calculated exactly at snapshot boundaries and at the current time
+    let period_start = args.from / interval * interval;
+    let period_end = if args.to >= now { now } else { args.to / interval * interval };
+    if period_end <= period_start {
+        return InvalidPeriod;
+    }
+
+    let oldest_available = runtime_state.data.oldest_usage_period_available(now);
+    if period_start < oldest_available {
+        return PeriodNotAvailable(PeriodNotAvailableResult { oldest_available });
+    }
+
+    let max_results = args.max_results.min(MAX_RESULTS_LIMIT) as usize;
+    let start_bound = args.after.as_ref().map_or(Unbounded, Excluded);
+    // Removed users are included since they may have held files during the period
+    let current_users = runtime_state
+        .data
+        .users
+        .range((start_bound, Unbounded))
+        .take(max_results + 1)
+        .map(|(user_id, user)| (*user_id, &user.usage, user.bytes_used));
+    let removed_users = runtime_state
+        .data
+        .removed_users_usage
+        .range((start_bound, Unbounded))
+        .take(max_results + 1)
+        .map(|(user_id, usage)| (*user_id, usage, 0));
+
+    let mut users_in_page: Vec<_> = current_users.chain(removed_users).collect();
+    users_in_page.sort_unstable_by_key(|(user_id, ..)| *user_id);
+
+    let next_cursor = if users_in_page.len() > max_results {
+        users_in_page.truncate(max_results);
+        users_in_page.last().map(|(user_id, ..)| *user_id)
+    } else {
+        None
+    };
+
+    let period_length = (period_end - period_start) as u128;
+    let users = users_in_page
+        .into_iter()
+        .map(|(user_id, usage, bytes_used)| {
+            let start = usage.byte_ms_at(period_start);
+            let end = usage.byte_ms_at(period_end);
+
+            if let (Some(start), Some(end)) = (start, end) {
+                let byte_ms = end.saturating_sub(start);
+                UserUsage::Available(UsageOverPeriod {
+                    user_id,
+                    byte_ms,
+                    average_bytes: (byte_ms / period_length) as u64,
+                    bytes_used,
+                })
+            } else {
+                UserUsage::Unavailable(UnavailableUsage { user_id, bytes_used })
+            }
+        })
+        .collect();
+
+    Success(SuccessResult {
+        period_start,
+        period_end,
+        users,
+        next_cursor,
+    })
+}
@@ -0,0 +1,35 @@
+use crate::guards::caller_is_service_principal;
+use crate::{read_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::query;
+use index_canister::bucket_status::{Response::*, *};
+
+#[query(guard = "caller_is_service_principal")]
+#[trace]
+fn bucket_status(args: Args) -> Response {
+    read_state(|state| bucket_status_impl(args, state))
+}
+
+fn bucket_status_impl(args: Args, runtime_state: &RuntimeState) -> Response {
+    if let Some(bucket) = runtime_state.data.buckets.get(&args.canister_id) {
+        Success(BucketStatus {
+            canister_id: bucket.canister_id,
+            wasm_version: bucket.wasm_version,
+            expected_module_hash: runtime_state
+                .data
+                .bucket_canister_wasm_hashes
+                .get(&bucket.wasm_version)
+                .copied(),
+            module_hash: bucket.module_hash,
+            module_hash_checked: bucket.module_hash_checked,
+            module_hash_drift: runtime_state.data.has_module_hash_drift(bucket),
+            bytes_used: bucket.bytes_used,
+            upgrade_in_progress: runtime_state
+                .data
+                .canisters_requiring_upgrade
+                .is_in_progress(&bucket.canister_id),
+        })
+    } else {
+        BucketNotFound
+    }
+}
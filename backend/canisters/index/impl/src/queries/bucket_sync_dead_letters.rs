@@ -0,0 +1,27 @@
+use crate::guards::caller_is_service_principal;
+use crate::{read_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::query;
+use index_canister::bucket_sync_dead_letters::{Response::*, *};
+use std::cmp::min;
+
+// Each dead letter can hold a full batch of events, so only a few are returned at a time to keep the
+// response within the size limit
+const MAX_RESULTS: u32 = 10;
+
+#[query(guard = "caller_is_service_principal")]
+#[trace]
+fn bucket_sync_dead_letters(args: Args) -> Response {
+    read_state(|state| bucket_sync_dead_letters_impl(args, state))
+}
+
+fn bucket_sync_dead_letters_impl(args: Args, runtime_state: &RuntimeState) -> Response {
+    if let Some(bucket) = runtime_state.data.buckets.get(&args.canister_id) {
+        let max_results = min(args.max_results, MAX_RESULTS) as usize;
+        let (dead_letters, total) = bucket.sync_state.dead_letters(args.start_after, max_results);
+
+        Success(SuccessResult { dead_letters, total })
+    } else {
+        BucketNotFound
+    }
+}
@@ -0,0 +1,31 @@
+use crate::guards::caller_is_service_principal;
+use crate::model::event_feed::EventsResult;
+use crate::{read_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::query;
+use index_canister::events::{Response::*, *};
+
+const MAX_RESULTS_LIMIT: u32 = 1000;
+
+#[query(guard = "caller_is_service_principal")]
+#[trace]
+fn events(args: Args) -> Response {
+    read_state(|state| events_impl(args, state))
+}
+
+fn events_impl(args: Args, runtime_state: &RuntimeState) -> Response {
+    let feed = &runtime_state.data.events;
+    let max_results = args.max_results.min(MAX_RESULTS_LIMIT) as usize;
+
+    match feed.events(args.after, args.bucket, max_results) {
+        EventsResult::Success(page) => Success(SuccessResult {
+            events: page.events,
+            next_cursor: page.next_cursor,
+            caught_up: page.caught_up,
+        }),
+        EventsResult::CursorTooOld(oldest_index) => CursorTooOld(CursorTooOldResult {
+            oldest_index,
+            latest_index: feed.latest_index(),
+        }),
+    }
+}
@@ -1,23 +1,184 @@
-use crate::{read_state, RuntimeState, LOG_MESSAGES};
+use crate::{read_state, BucketMetrics, Metrics, RuntimeState, LOG_MESSAGES};
 use canister_logger::LogMessagesContainer;
-use http_request::{encode_logs, extract_route, get_metrics, HttpRequest, HttpResponse, Route};
+use http_request::{
+    accepts_prometheus_metrics, encode_logs, extract_route, get_metrics, get_prometheus_metrics, HttpRequest, HttpResponse,
+    LogQuery, PrometheusEncoder, PrometheusMetrics, Route,
+};
 use ic_cdk_macros::query;
-use types::TimestampMillis;
 
 #[query]
 fn http_request(request: HttpRequest) -> HttpResponse {
-    fn get_logs_impl(since: Option<TimestampMillis>, messages_container: &LogMessagesContainer) -> HttpResponse {
-        encode_logs(messages_container.get(since.unwrap_or(0)))
+    fn get_logs_impl(query: LogQuery, messages_container: &LogMessagesContainer) -> HttpResponse {
+        encode_logs(messages_container.query(&query.filter, query.after, query.limit()))
     }
 
     fn get_metrics_impl(runtime_state: &RuntimeState) -> HttpResponse {
         get_metrics(&runtime_state.metrics())
     }
 
+    fn get_prometheus_metrics_impl(runtime_state: &RuntimeState) -> HttpResponse {
+        get_prometheus_metrics(&runtime_state.metrics(), "index")
+    }
+
     match extract_route(&request.url) {
-        Route::Logs(since) => LOG_MESSAGES.with(|l| get_logs_impl(since, &l.borrow().logs)),
-        Route::Traces(since) => LOG_MESSAGES.with(|l| get_logs_impl(since, &l.borrow().traces)),
+        Route::Logs(query) => LOG_MESSAGES.with(|l| get_logs_impl(query, &l.borrow().logs)),
+        Route::Traces(query) => LOG_MESSAGES.with(|l| get_logs_impl(query, &l.borrow().traces)),
+        Route::Metrics if accepts_prometheus_metrics(&request) => read_state(get_prometheus_metrics_impl),
         Route::Metrics => read_state(get_metrics_impl),
+        Route::PrometheusMetrics => read_state(get_prometheus_metrics_impl),
+        Route::BadRequest(message) => HttpResponse::bad_request(&message),
         _ => HttpResponse::not_found(),
     }
 }
+
+impl PrometheusMetrics for Metrics {
+    fn encode(&self, encoder: &mut PrometheusEncoder) {
+        encoder
+            .gauge("memory_used_bytes", "Memory used by the canister", self.memory_used as f64)
+            .gauge("cycles_balance", "Cycles balance of the canister", self.cycles_balance as f64)
+            .gauge_with_labels(
+                "wasm_version_info",
+                "Wasm version of the canister",
+                &[("version", &self.wasm_version.to_string())],
+                1.0,
+            )
+            .gauge("blob_count", "Number of distinct blobs", self.blob_count as f64)
+            .gauge("blob_bytes", "Total size of distinct blobs", self.total_blob_bytes as f64)
+            .gauge("file_count", "Number of files", self.file_count as f64)
+            .gauge("file_bytes", "Total size of files", self.total_file_bytes as f64)
+            .gauge(
+                "bucket_pool_size",
+                "Number of buckets in the pool",
+                self.bucket_pool_size as f64,
+            )
+            .gauge(
+                "bucket_upgrades_pending",
+                "Number of buckets waiting to be upgraded",
+                self.bucket_upgrades_pending as f64,
+            )
+            .gauge(
+                "bucket_upgrades_in_progress",
+                "Number of buckets being upgraded",
+                self.bucket_upgrades_in_progress as f64,
+            )
+            .gauge(
+                "bucket_upgrades_paused",
+                "Whether bucket upgrades are paused",
+                if self.bucket_upgrades_paused { 1.0 } else { 0.0 },
+            )
+            .gauge_with_labels(
+                "bucket_canister_wasm_version_info",
+                "Wasm version which buckets are upgraded to",
+                &[("version", &self.bucket_canister_wasm.to_string())],
+                1.0,
+            )
+            .gauge(
+                "buckets_with_module_hash_drift",
+                "Number of buckets whose module hash doesn't match their wasm version",
+                self.buckets_with_module_hash_drift.len() as f64,
+            )
+            .gauge(
+                "module_hash_audit_last_run_timestamp_ms",
+                "Time the bucket module hashes were last audited",
+                self.module_hash_audit_last_run as f64,
+            )
+            .counter(
+                "targeted_sync_events_total",
+                "Number of events synced to buckets",
+                self.targeted_sync.events as f64,
+            )
+            .counter(
+                "targeted_sync_bucket_enqueues_total",
+                "Number of events enqueued for a bucket",
+                self.targeted_sync.bucket_enqueues as f64,
+            )
+            .counter(
+                "targeted_sync_bucket_enqueues_skipped_total",
+                "Number of bucket enqueues skipped because the bucket had no interest in the event",
+                self.targeted_sync.bucket_enqueues_skipped as f64,
+            )
+            .counter(
+                "targeted_sync_broadcasts_total",
+                "Number of events sent to every bucket because the buckets they apply to weren't known",
+                self.targeted_sync.broadcasts as f64,
+            )
+            .gauge(
+                "all_accessors_tracked",
+                "Whether the buckets holding each accessor's files are all known",
+                if self.all_accessors_tracked { 1.0 } else { 0.0 },
+            );
+
+        for failed in self.bucket_upgrades_failed.iter() {
+            encoder.gauge_with_labels(
+                "bucket_upgrades_failed",
+                "Number of buckets whose upgrade failed",
+                &[
+                    ("from_version", &failed.from_version.to_string()),
+                    ("to_version", &failed.to_version.to_string()),
+                ],
+                failed.count as f64,
+            );
+        }
+
+        for (state, buckets) in [("active", &self.active_buckets), ("full", &self.full_buckets)] {
+            for bucket in buckets.iter() {
+                encode_bucket_metrics(bucket, state, encoder);
+            }
+        }
+
+        for (name, value) in self.operations.counters() {
+            encoder.counter(&format!("{}_total", name), name, value as f64);
+        }
+        for (name, histogram) in self.operations.histograms() {
+            encoder.histogram(
+                name,
+                name,
+                &histogram.bucket_bounds,
+                &histogram.bucket_counts,
+                histogram.sum as f64,
+            );
+        }
+    }
+}
+
+fn encode_bucket_metrics(bucket: &BucketMetrics, state: &str, encoder: &mut PrometheusEncoder) {
+    let canister_id = bucket.canister_id.to_string();
+    let wasm_version = bucket.wasm_version.to_string();
+    let labels = [
+        ("canister_id", canister_id.as_str()),
+        ("wasm_version", wasm_version.as_str()),
+        ("state", state),
+    ];
+
+    encoder
+        .gauge_with_labels(
+            "bucket_bytes_used",
+            "Bytes used by the bucket",
+            &labels,
+            bucket.bytes_used as f64,
+        )
+        .gauge_with_labels(
+            "bucket_sync_queue_length",
+            "Number of events waiting to be synced to the bucket",
+            &labels,
+            bucket.sync.queue_length as f64,
+        )
+        .gauge_with_labels(
+            "bucket_sync_oldest_queued_event_age_ms",
+            "Age of the oldest event waiting to be synced to the bucket",
+            &labels,
+            bucket.sync.oldest_queued_event_age.unwrap_or_default() as f64,
+        )
+        .gauge_with_labels(
+            "bucket_sync_failed_attempts",
+            "Number of consecutive failed attempts to sync to the bucket",
+            &labels,
+            bucket.sync.failed_attempts as f64,
+        )
+        .gauge_with_labels(
+            "bucket_sync_dead_letters",
+            "Number of batches which could not be synced to the bucket",
+            &labels,
+            bucket.sync.dead_letters as f64,
+        );
+}
@@ -0,0 +1,50 @@
+use crate::guards::caller_is_service_principal;
+use crate::LOG_MESSAGES;
+use canister_api_macros::trace;
+use canister_logger::{LogFilter, LogMessage};
+use ic_cdk_macros::query;
+use index_canister::logs::{Response::*, *};
+use std::cmp::min;
+use std::str::FromStr;
+use tracing::Level;
+
+const MAX_RESULTS_LIMIT: u32 = 1000;
+
+#[query(guard = "caller_is_service_principal")]
+#[trace]
+fn logs(args: Args) -> Response {
+    let level = match args.level.as_deref().map(Level::from_str).transpose() {
+        Ok(level) => level,
+        Err(_) => return InvalidLevel,
+    };
+
+    let filter = LogFilter {
+        since: args.since,
+        level,
+        contains: args.contains,
+        fields: args.fields,
+    };
+    let max_results = min(args.max_results, MAX_RESULTS_LIMIT) as usize;
+
+    let page = LOG_MESSAGES.with(|l| {
+        let messages = l.borrow();
+        let container = if args.traces { &messages.traces } else { &messages.logs };
+        container.query(&filter, args.after, max_results)
+    });
+
+    Success(SuccessResult {
+        messages: page.messages.into_iter().map(log_entry).collect(),
+        next_cursor: page.next_cursor,
+    })
+}
+
+fn log_entry(message: LogMessage) -> LogEntry {
+    LogEntry {
+        index: message.index,
+        timestamp: message.timestamp,
+        level: message.level,
+        target: message.target,
+        message: message.message,
+        fields: message.fields,
+    }
+}
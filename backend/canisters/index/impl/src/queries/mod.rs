@@ -1,4 +1,12 @@
 pub mod allocated_bucket;
+pub mod billing_usage;
+pub mod bucket_status;
+pub mod bucket_sync_dead_letters;
 pub mod can_forward;
+pub mod events;
 pub mod http_request;
+pub mod logs;
+pub mod reconciliation_report;
+pub mod subscribers;
 pub mod user;
+pub mod users_near_limit;
@@ -0,0 +1,19 @@
+use crate::guards::caller_is_service_principal;
+use crate::{read_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::query;
+use index_canister::reconciliation_report::{Response::*, *};
+
+#[query(guard = "caller_is_service_principal")]
+#[trace]
+fn reconciliation_report(_args: Args) -> Response {
+    read_state(reconciliation_report_impl)
+}
+
+fn reconciliation_report_impl(runtime_state: &RuntimeState) -> Response {
+    if let Some(report) = runtime_state.data.reconciliation.report() {
+        Success(report.clone())
+    } else {
+        NoReport
+    }
+}
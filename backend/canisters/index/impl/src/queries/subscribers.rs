@@ -0,0 +1,15 @@
+use crate::guards::caller_is_service_principal;
+use crate::{read_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::query;
+use index_canister::subscribers::{Response::*, *};
+
+#[query(guard = "caller_is_service_principal")]
+#[trace]
+fn subscribers(_args: Args) -> Response {
+    read_state(subscribers_impl)
+}
+
+fn subscribers_impl(runtime_state: &RuntimeState) -> Response {
+    Success(runtime_state.data.subscribers.summaries())
+}
@@ -0,0 +1,39 @@
+use crate::guards::caller_is_service_principal;
+use crate::{read_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::query;
+use index_canister::users_near_limit::{Response::*, *};
+use std::cmp::Reverse;
+
+const MAX_RESULTS_LIMIT: u32 = 1000;
+
+#[query(guard = "caller_is_service_principal")]
+#[trace]
+fn users_near_limit(args: Args) -> Response {
+    read_state(|state| users_near_limit_impl(args, state))
+}
+
+fn users_near_limit_impl(args: Args, runtime_state: &RuntimeState) -> Response {
+    let min_threshold = args.min_threshold.unwrap_or_default();
+
+    let mut users: Vec<_> = runtime_state
+        .data
+        .users
+        .iter()
+        .filter_map(|(user_id, user)| {
+            user.quota_threshold_reached
+                .filter(|t| *t >= min_threshold)
+                .map(|threshold| UserNearLimit {
+                    user_id: *user_id,
+                    bytes_used: user.bytes_used,
+                    byte_limit: user.byte_limit,
+                    threshold,
+                })
+        })
+        .collect();
+
+    users.sort_unstable_by_key(|u| (Reverse(u.threshold), Reverse(u.bytes_used)));
+    users.truncate(args.max_results.min(MAX_RESULTS_LIMIT) as usize);
+
+    Success(users)
+}
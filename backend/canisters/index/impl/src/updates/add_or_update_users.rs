@@ -1,10 +1,14 @@
 use crate::guards::caller_is_service_principal;
+use crate::lifecycle::jobs;
 use crate::model::bucket_sync_state::EventToSync;
+use crate::model::usage::UsageAccrual;
 use crate::{mutate_state, RuntimeState, UserRecordInternal};
 use canister_api_macros::trace;
 use ic_cdk_macros::update;
 use index_canister::add_or_update_users::{Response::*, *};
+use index_canister::events::Event;
 use std::collections::HashSet;
+use types::UserLimits;
 
 #[update(guard = "caller_is_service_principal")]
 #[trace]
@@ -13,25 +17,59 @@ fn add_or_update_users(args: Args) -> Response {
 }
 
 fn add_or_update_users_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
+    let now = runtime_state.env.now();
+    let mut limits_updated = false;
+
     for user_config in args.users {
+        let limits = UserLimits {
+            max_files: user_config.max_files,
+            max_file_size: user_config.max_file_size,
+            allowed_mime_types: user_config.allowed_mime_types,
+        };
+
         if let Some(user) = runtime_state.data.users.get_mut(&user_config.user_id) {
             user.byte_limit = user_config.byte_limit;
+            if user.limits != limits {
+                // The buckets holding the user's files enforce the limits too so must be told about them
+                runtime_state.data.buckets.sync_event_to_buckets(
+                    &user.buckets,
+                    EventToSync::UserLimitsUpdated(user_config.user_id, limits.clone()),
+                    now,
+                );
+                user.limits = limits;
+                limits_updated = true;
+            }
+            runtime_state.data.update_quota_threshold(user_config.user_id, now);
         } else {
+            // If the user was removed and is now being added back, carry on from their previous usage
+            let usage = runtime_state
+                .data
+                .removed_users_usage
+                .remove(&user_config.user_id)
+                .unwrap_or_else(|| UsageAccrual::new_user(now));
+
             runtime_state.data.users.insert(
                 user_config.user_id,
                 UserRecordInternal {
                     byte_limit: user_config.byte_limit,
                     bytes_used: 0,
                     blobs_owned: HashSet::new(),
+                    file_count: 0,
+                    buckets: HashSet::new(),
+                    quota_threshold_reached: None,
+                    limits,
+                    usage,
                 },
             );
-
             runtime_state
                 .data
-                .buckets
-                .sync_event(EventToSync::UserAdded(user_config.user_id));
+                .push_event(None, Event::UserAdded(user_config.user_id), now);
         }
     }
+    if limits_updated {
+        jobs::sync_users_with_buckets::trigger();
+    }
+    jobs::notify_subscribers::trigger();
 
     Success
 }
@@ -0,0 +1,29 @@
+use crate::guards::caller_is_service_principal;
+use crate::read_state;
+use bucket_canister::c2c_audit_log;
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::bucket_audit_log::{Response::*, *};
+
+// This is an update call because it has to query the bucket
+#[update(guard = "caller_is_service_principal")]
+#[trace]
+async fn bucket_audit_log(args: Args) -> Response {
+    if read_state(|state| state.data.buckets.get(&args.canister_id).is_none()) {
+        return BucketNotFound;
+    }
+
+    let c2c_args = c2c_audit_log::Args {
+        file_id: args.file_id,
+        user_id: args.user_id,
+        before: args.before,
+        max_results: args.max_results,
+    };
+    match bucket_canister_c2c_client::c2c_audit_log(args.canister_id, &c2c_args).await {
+        Ok(c2c_audit_log::Response::Success(result)) => Success(SuccessResult {
+            records: result.records,
+            next_cursor: result.next_cursor,
+        }),
+        Err(error) => InternalError(format!("{:?}", error)),
+    }
+}
@@ -0,0 +1,32 @@
+use crate::guards::caller_is_bucket;
+use crate::lifecycle::jobs;
+use crate::model::bucket_sync_state::EventToSync;
+use crate::{mutate_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::c2c_lookup_user::{Response::*, *};
+use types::UserLimits;
+
+// Called by a bucket the first time a user interacts with it. The bucket is recorded against the
+// user so that it receives any subsequent events relating to that user, and is sent the user's limits.
+#[update(guard = "caller_is_bucket")]
+#[trace]
+fn c2c_lookup_user(args: Args) -> Response {
+    mutate_state(|state| c2c_lookup_user_impl(args, state))
+}
+
+fn c2c_lookup_user_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
+    let bucket = runtime_state.env.caller();
+
+    if let Some(user) = runtime_state.data.users.get_mut(&args.user_id) {
+        if user.buckets.insert(bucket) && user.limits != UserLimits::default() {
+            let now = runtime_state.env.now();
+            let event = EventToSync::UserLimitsUpdated(args.user_id, user.limits.clone());
+            runtime_state.data.buckets.sync_event_to_buckets(&[bucket], event, now);
+            jobs::sync_users_with_buckets::trigger();
+        }
+        Success
+    } else {
+        UserNotFound
+    }
+}
@@ -1,8 +1,15 @@
-use crate::{mutate_state, read_state, RuntimeState, BUCKET_CANISTER_TOP_UP_AMOUNT, MIN_CYCLES_BALANCE};
+use crate::{mutate_state, read_state, RuntimeState};
 use canister_api_macros::trace;
 use ic_cdk_macros::update;
 use types::{CanisterId, CyclesTopUp, NotifyLowBalanceResponse};
 use utils::cycles::{can_spend_cycles, top_up_canister};
+use utils::metrics::CallMetricNames;
+
+const CALL_METRICS: CallMetricNames = CallMetricNames {
+    calls: "cycles_top_ups_sent",
+    failures: "cycles_top_ups_failed",
+    latency_ms: "cycles_top_up_latency_ms",
+};
 
 #[update]
 #[trace]
@@ -13,7 +20,13 @@ async fn c2c_notify_low_balance() -> NotifyLowBalanceResponse {
     };
     let amount = prepare_ok.top_up.amount;
 
-    if top_up_canister(prepare_ok.bucket, amount).await.is_ok() {
+    let success = top_up_canister(prepare_ok.bucket, amount).await.is_ok();
+    mutate_state(|state| {
+        let latency = state.env.now().saturating_sub(prepare_ok.top_up.date);
+        state.data.operations.record_call(&CALL_METRICS, success, latency);
+    });
+
+    if success {
         mutate_state(|state| commit(prepare_ok.bucket, prepare_ok.top_up, state));
         NotifyLowBalanceResponse::Success(amount)
     } else {
@@ -28,13 +41,13 @@ struct PrepareResult {
 
 fn prepare(runtime_state: &RuntimeState) -> Result<PrepareResult, NotifyLowBalanceResponse> {
     let caller = runtime_state.env.caller();
-    let top_up_amount = BUCKET_CANISTER_TOP_UP_AMOUNT;
+    let top_up_amount = runtime_state.data.config.bucket_canister_top_up_amount;
     let top_up = CyclesTopUp {
         date: runtime_state.env.now(),
         amount: top_up_amount,
     };
 
-    if !can_spend_cycles(top_up_amount, MIN_CYCLES_BALANCE) {
+    if !can_spend_cycles(top_up_amount, runtime_state.data.config.min_cycles_balance_for_top_ups) {
         Err(NotifyLowBalanceResponse::NotEnoughCyclesRemaining)
     } else if runtime_state.data.buckets.get(&caller).is_some() {
         Ok(PrepareResult { bucket: caller, top_up })
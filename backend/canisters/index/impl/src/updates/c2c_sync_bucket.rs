@@ -1,31 +1,95 @@
 use crate::guards::caller_is_bucket;
+use crate::lifecycle::jobs;
 use crate::{mutate_state, RuntimeState};
 use canister_api_macros::trace;
 use ic_cdk_macros::update;
-use index_canister::c2c_sync_bucket::*;
+use index_canister::c2c_sync_bucket::{Response::*, *};
+use index_canister::events::Event;
+use tracing::info;
+use types::{FileRejected, FileRejectedReason};
+use utils::sync_receiver::SyncCheckResult;
 
 #[update(guard = "caller_is_bucket")]
 #[trace]
 fn c2c_sync_bucket(args: Args) -> Response {
-    mutate_state(|state| c2c_sync_bucket_impl(args, state))
+    canister_logger::with_correlation_id(args.correlation_id.clone(), || {
+        mutate_state(|state| c2c_sync_bucket_impl(args, state))
+    })
 }
 
 fn c2c_sync_bucket_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
     let bucket = runtime_state.env.caller();
+    let sequence_number = args.sequence_number;
 
-    let files_rejected = args
-        .files_added
-        .into_iter()
-        .filter_map(|file| runtime_state.data.add_file_reference(bucket, file).err())
-        .collect();
+    if let Some(record) = runtime_state.data.buckets.get(&bucket) {
+        match record.sync_receiver.check(sequence_number) {
+            SyncCheckResult::Apply => {}
+            SyncCheckResult::Duplicate(result) => return Success(result),
+            SyncCheckResult::OutOfOrder(last_applied) => return OutOfOrder(last_applied),
+        }
+    }
+
+    let now = runtime_state.env.now();
+    let mut files_rejected = Vec::new();
+
+    // Each file is processed with the correlation id of the call which added or removed it in the bucket
+    for file in args.files_added {
+        canister_logger::with_correlation_id(file.correlation_id.clone(), || {
+            match runtime_state.data.add_file_reference(bucket, file.clone()) {
+                Ok(()) => {
+                    let owner = file.owner;
+                    runtime_state.data.push_event(Some(bucket), Event::FileAdded(file), now);
+                    runtime_state.data.on_usage_changed(owner, now);
+                }
+                Err(rejected) => {
+                    info!(file_id = %rejected.file_id, reason = ?rejected.reason, "File rejected");
+                    runtime_state
+                        .data
+                        .push_event(Some(bucket), Event::FileRejected(rejected.clone()), now);
+                    files_rejected.push(FileRejected {
+                        reason: reason_understood_by_all_buckets(rejected.reason),
+                        ..rejected
+                    });
+                }
+            }
+        });
+    }
 
     for file in args.files_removed {
-        runtime_state.data.remove_file_reference(bucket, file);
+        canister_logger::with_correlation_id(file.correlation_id.clone(), || {
+            if runtime_state.data.remove_file_reference(bucket, file.clone(), now) {
+                runtime_state.data.push_event(Some(bucket), Event::FileRemoved(file), now);
+            }
+        });
+    }
+    jobs::notify_subscribers::trigger();
+
+    for accessor_id in args.accessors_unlinked {
+        runtime_state.data.unlink_accessor(accessor_id, bucket);
     }
 
     if args.bytes_remaining <= 0 {
-        runtime_state.data.buckets.archive(bucket);
+        let target_active_buckets = runtime_state.data.config.target_active_buckets as usize;
+        runtime_state.data.buckets.archive(bucket, target_active_buckets);
+        jobs::ensure_sufficient_active_buckets::trigger();
     }
 
-    Response::Success(SuccessResult { files_rejected })
+    let result = SuccessResult { files_rejected };
+    if let Some(record) = runtime_state.data.buckets.get_mut(&bucket) {
+        record.sync_receiver.record(sequence_number, result.clone());
+    }
+    Success(result)
+}
+
+// Buckets which haven't yet been upgraded can't decode the reasons which were added for the per-user
+// limits, which would cause every sync from them to fail. So until all buckets have been upgraded those
+// reasons are reported to the bucket as `AllowanceExceeded`, while the event feed records the actual
+// reason.
+fn reason_understood_by_all_buckets(reason: FileRejectedReason) -> FileRejectedReason {
+    match reason {
+        FileRejectedReason::AllowanceExceeded | FileRejectedReason::UserNotFound => reason,
+        FileRejectedReason::FileCountExceeded | FileRejectedReason::FileTooBig | FileRejectedReason::MimeTypeNotAllowed => {
+            FileRejectedReason::AllowanceExceeded
+        }
+    }
 }
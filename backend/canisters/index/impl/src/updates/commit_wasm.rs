@@ -0,0 +1,41 @@
+use crate::guards::caller_is_service_principal;
+use crate::model::staged_wasm::TakeResult;
+use crate::updates::update_bucket_canister_wasm::update_bucket_canister_wasm_impl;
+use crate::{mutate_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::commit_wasm::{Response::*, *};
+use index_canister::update_bucket_canister_wasm;
+use types::CanisterWasm;
+
+#[update(guard = "caller_is_service_principal")]
+#[trace]
+fn commit_wasm(args: Args) -> Response {
+    mutate_state(|state| commit_wasm_impl(args, state))
+}
+
+fn commit_wasm_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
+    let module = match runtime_state.data.staged_bucket_canister_wasm.take(args.version, args.sha256) {
+        TakeResult::Success(module) => module,
+        TakeResult::NoWasmStaged => return NoWasmStaged,
+        TakeResult::VersionMismatch(version) => return VersionMismatch(version),
+        TakeResult::HashMismatch(sha256) => return HashMismatch(sha256),
+    };
+
+    let update_args = update_bucket_canister_wasm::Args {
+        bucket_canister_wasm: CanisterWasm {
+            version: args.version,
+            compressed: args.compressed,
+            module,
+        },
+        canary: args.canary,
+        max_failures: args.max_failures,
+    };
+
+    match update_bucket_canister_wasm_impl(update_args, runtime_state) {
+        update_bucket_canister_wasm::Response::Success => Success,
+        update_bucket_canister_wasm::Response::VersionNotHigher => VersionNotHigher,
+        update_bucket_canister_wasm::Response::InvalidCanaryPercentage => InvalidCanaryPercentage,
+        update_bucket_canister_wasm::Response::CanaryBucketNotFound(canister_id) => CanaryBucketNotFound(canister_id),
+    }
+}
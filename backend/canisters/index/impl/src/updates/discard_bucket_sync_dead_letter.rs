@@ -0,0 +1,29 @@
+use crate::guards::caller_is_service_principal;
+use crate::{mutate_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::discard_bucket_sync_dead_letter::{Response::*, *};
+use tracing::info;
+
+#[update(guard = "caller_is_service_principal")]
+#[trace]
+fn discard_bucket_sync_dead_letter(args: Args) -> Response {
+    mutate_state(|state| discard_bucket_sync_dead_letter_impl(args, state))
+}
+
+fn discard_bucket_sync_dead_letter_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
+    if let Some(bucket) = runtime_state.data.buckets.get_mut(&args.canister_id) {
+        if bucket.sync_state.discard_dead_letter(args.dead_letter_id) {
+            info!(
+                canister_id = args.canister_id.to_string().as_str(),
+                dead_letter_id = args.dead_letter_id,
+                "Sync dead letter discarded"
+            );
+            Success
+        } else {
+            DeadLetterNotFound
+        }
+    } else {
+        BucketNotFound
+    }
+}
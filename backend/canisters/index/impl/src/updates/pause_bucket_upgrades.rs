@@ -0,0 +1,16 @@
+use crate::guards::caller_is_service_principal;
+use crate::{mutate_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::pause_bucket_upgrades::{Response::*, *};
+
+#[update(guard = "caller_is_service_principal")]
+#[trace]
+fn pause_bucket_upgrades(_args: Args) -> Response {
+    mutate_state(pause_bucket_upgrades_impl)
+}
+
+fn pause_bucket_upgrades_impl(runtime_state: &mut RuntimeState) -> Response {
+    runtime_state.data.canisters_requiring_upgrade.pause();
+    Success
+}
@@ -1,4 +1,5 @@
 use crate::guards::caller_is_service_principal;
+use crate::lifecycle::jobs;
 use crate::model::bucket_sync_state::EventToSync;
 use crate::{mutate_state, RuntimeState};
 use canister_api_macros::trace;
@@ -12,10 +13,21 @@ fn remove_accessor(args: Args) -> Response {
 }
 
 fn remove_accessor_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
-    runtime_state
-        .data
-        .buckets
-        .sync_event(EventToSync::AccessorRemoved(args.accessor_id));
+    let event = EventToSync::AccessorRemoved(args.accessor_id);
+    let now = runtime_state.env.now();
+
+    let buckets = runtime_state.data.accessors.remove(&args.accessor_id);
+
+    // Until every accessor is tracked, buckets may hold access which the index doesn't know about, so
+    // the event must be sent to every bucket
+    if !runtime_state.data.all_accessors_tracked {
+        runtime_state.data.buckets.sync_untargeted_event(event, now);
+    } else if let Some(buckets) = buckets {
+        runtime_state.data.buckets.sync_event_to_buckets(&buckets, event, now);
+    } else {
+        return Response::Success;
+    }
+    jobs::sync_users_with_buckets::trigger();
 
     Response::Success
 }
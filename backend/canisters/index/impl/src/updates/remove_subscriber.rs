@@ -0,0 +1,19 @@
+use crate::guards::caller_is_service_principal;
+use crate::{mutate_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::remove_subscriber::{Response::*, *};
+
+#[update(guard = "caller_is_service_principal")]
+#[trace]
+fn remove_subscriber(args: Args) -> Response {
+    mutate_state(|state| remove_subscriber_impl(args, state))
+}
+
+fn remove_subscriber_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
+    if runtime_state.data.subscribers.remove(&args.canister_id) {
+        Success
+    } else {
+        SubscriberNotFound
+    }
+}
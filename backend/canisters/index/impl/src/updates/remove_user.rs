@@ -1,8 +1,10 @@
 use crate::guards::caller_is_service_principal;
+use crate::lifecycle::jobs;
 use crate::model::bucket_sync_state::EventToSync;
 use crate::{mutate_state, RuntimeState};
 use canister_api_macros::trace;
 use ic_cdk_macros::update;
+use index_canister::events::Event;
 use index_canister::remove_user::*;
 
 #[update(guard = "caller_is_service_principal")]
@@ -12,7 +14,17 @@ fn remove_user(args: Args) -> Response {
 }
 
 fn remove_user_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
-    runtime_state.data.users.remove(&args.user_id);
-    runtime_state.data.buckets.sync_event(EventToSync::UserRemoved(args.user_id));
+    if let Some(user) = runtime_state.data.users.remove(&args.user_id) {
+        let now = runtime_state.env.now();
+        runtime_state
+            .data
+            .buckets
+            .sync_event_to_buckets(&user.buckets, EventToSync::UserRemoved(args.user_id), now);
+        runtime_state.data.buckets.remove_user_from_users_snapshots(&args.user_id);
+        runtime_state.data.push_event(None, Event::UserRemoved(args.user_id), now);
+        runtime_state.data.retain_removed_user_usage(args.user_id, user.usage, now);
+        jobs::sync_users_with_buckets::trigger();
+        jobs::notify_subscribers::trigger();
+    }
     Response::Success
 }
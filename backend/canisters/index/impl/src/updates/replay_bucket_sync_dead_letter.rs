@@ -0,0 +1,25 @@
+use crate::guards::caller_is_service_principal;
+use crate::lifecycle::jobs;
+use crate::{mutate_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::replay_bucket_sync_dead_letter::{Response::*, *};
+
+#[update(guard = "caller_is_service_principal")]
+#[trace]
+fn replay_bucket_sync_dead_letter(args: Args) -> Response {
+    mutate_state(|state| replay_bucket_sync_dead_letter_impl(args, state))
+}
+
+fn replay_bucket_sync_dead_letter_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
+    if let Some(bucket) = runtime_state.data.buckets.get_mut(&args.canister_id) {
+        if bucket.sync_state.replay_dead_letter(args.dead_letter_id) {
+            jobs::sync_users_with_buckets::trigger();
+            Success
+        } else {
+            DeadLetterNotFound
+        }
+    } else {
+        BucketNotFound
+    }
+}
@@ -0,0 +1,19 @@
+use crate::guards::caller_is_service_principal;
+use crate::lifecycle::jobs;
+use crate::{mutate_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::resume_bucket_upgrades::{Response::*, *};
+
+#[update(guard = "caller_is_service_principal")]
+#[trace]
+fn resume_bucket_upgrades(_args: Args) -> Response {
+    mutate_state(resume_bucket_upgrades_impl)
+}
+
+fn resume_bucket_upgrades_impl(runtime_state: &mut RuntimeState) -> Response {
+    runtime_state.data.bucket_upgrade_rollout.resume();
+    runtime_state.data.canisters_requiring_upgrade.resume();
+    jobs::upgrade_canisters::trigger();
+    Success
+}
@@ -0,0 +1,37 @@
+use crate::guards::caller_is_service_principal;
+use crate::lifecycle::jobs;
+use crate::model::users_snapshot::UsersSnapshot;
+use crate::{mutate_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::resync_bucket_users::{Response::*, *};
+
+#[update(guard = "caller_is_service_principal")]
+#[trace]
+fn resync_bucket_users(args: Args) -> Response {
+    mutate_state(|state| resync_bucket_users_impl(args, state))
+}
+
+fn resync_bucket_users_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
+    let now = runtime_state.env.now();
+    let users: Vec<_> = runtime_state
+        .data
+        .users
+        .iter()
+        .filter(|(_, u)| u.buckets.contains(&args.canister_id))
+        .map(|(user_id, _)| *user_id)
+        .collect();
+
+    if let Some(bucket) = runtime_state.data.buckets.get_mut(&args.canister_id) {
+        if bucket.users_snapshot.is_some() {
+            AlreadyInProgress
+        } else {
+            // Snapshot ids must increase so the timestamp is used as the id
+            bucket.users_snapshot = Some(UsersSnapshot::new(now, users));
+            jobs::sync_users_snapshots::trigger();
+            Success
+        }
+    } else {
+        BucketNotFound
+    }
+}
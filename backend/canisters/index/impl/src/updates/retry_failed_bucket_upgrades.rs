@@ -0,0 +1,18 @@
+use crate::guards::caller_is_service_principal;
+use crate::lifecycle::jobs;
+use crate::{mutate_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::retry_failed_bucket_upgrades::{Response::*, *};
+
+#[update(guard = "caller_is_service_principal")]
+#[trace]
+fn retry_failed_bucket_upgrades(_args: Args) -> Response {
+    mutate_state(retry_failed_bucket_upgrades_impl)
+}
+
+fn retry_failed_bucket_upgrades_impl(runtime_state: &mut RuntimeState) -> Response {
+    let upgrades_requeued = runtime_state.data.canisters_requiring_upgrade.retry_failed() as u32;
+    jobs::upgrade_canisters::trigger();
+    Success(SuccessResult { upgrades_requeued })
+}
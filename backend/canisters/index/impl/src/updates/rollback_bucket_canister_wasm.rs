@@ -0,0 +1,50 @@
+use crate::guards::caller_is_service_principal;
+use crate::lifecycle::jobs;
+use crate::model::bucket_upgrade_rollout::{BucketUpgradeRollout, DEFAULT_MAX_UPGRADE_FAILURES};
+use crate::{mutate_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::rollback_bucket_canister_wasm::{Response::*, *};
+use tracing::info;
+
+#[update(guard = "caller_is_service_principal")]
+#[trace]
+fn rollback_bucket_canister_wasm(_args: Args) -> Response {
+    mutate_state(rollback_bucket_canister_wasm_impl)
+}
+
+fn rollback_bucket_canister_wasm_impl(runtime_state: &mut RuntimeState) -> Response {
+    let previous_wasm = match runtime_state.data.previous_bucket_canister_wasm.take() {
+        Some(wasm) => wasm,
+        None => return NoPreviousWasm,
+    };
+
+    let version = previous_wasm.version;
+    let rolled_back_from = std::mem::replace(&mut runtime_state.data.bucket_canister_wasm, previous_wasm).version;
+    runtime_state.data.record_bucket_canister_wasm_hash();
+
+    // Any buckets whose upgrades are still in progress will join the rollout once they complete
+    let canisters_to_downgrade: Vec<_> = runtime_state
+        .data
+        .buckets
+        .iter()
+        .filter(|b| b.wasm_version != version)
+        .map(|b| b.canister_id)
+        .filter(|c| !runtime_state.data.canisters_requiring_upgrade.is_in_progress(c))
+        .collect();
+
+    let (rollout, to_upgrade_now) =
+        BucketUpgradeRollout::start(version, Vec::new(), canisters_to_downgrade, DEFAULT_MAX_UPGRADE_FAILURES);
+    runtime_state.data.bucket_upgrade_rollout = rollout;
+
+    let canisters_requiring_upgrade = &mut runtime_state.data.canisters_requiring_upgrade;
+    canisters_requiring_upgrade.reset();
+    canisters_requiring_upgrade.resume();
+    for canister_id in to_upgrade_now {
+        canisters_requiring_upgrade.enqueue(canister_id);
+    }
+    jobs::upgrade_canisters::trigger();
+
+    info!(%rolled_back_from, %version, "Bucket canister wasm rolled back");
+    Success(version)
+}
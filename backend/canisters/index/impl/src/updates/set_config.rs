@@ -0,0 +1,43 @@
+use crate::guards::caller_is_service_principal;
+use crate::lifecycle::jobs;
+use crate::model::bucket_sync_state::EventToSync;
+use crate::{mutate_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::set_config::{Response::*, *};
+use tracing::info;
+
+#[update(guard = "caller_is_service_principal")]
+#[trace]
+fn set_config(args: Args) -> Response {
+    mutate_state(|state| set_config_impl(args, state))
+}
+
+fn set_config_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
+    let config = match runtime_state.data.config.with_changes(args) {
+        Ok(c) => c,
+        Err(error) => return InvalidConfig(error),
+    };
+
+    let bucket_config_changed = config.bucket != runtime_state.data.config.bucket;
+    let quota_alert_thresholds_changed = config.quota_alert_thresholds != runtime_state.data.config.quota_alert_thresholds;
+    let now = runtime_state.env.now();
+
+    let buckets = &mut runtime_state.data.buckets;
+    buckets.activate_pooled_buckets(config.target_active_buckets as usize);
+
+    if bucket_config_changed {
+        buckets.sync_event(EventToSync::ConfigUpdated(config.bucket.clone()), now);
+        jobs::sync_users_with_buckets::trigger();
+    }
+    jobs::ensure_sufficient_active_buckets::trigger();
+
+    info!(?config, "Config updated");
+    runtime_state.data.config = config;
+
+    if quota_alert_thresholds_changed {
+        runtime_state.data.queue_quota_threshold_updates();
+        jobs::update_quota_thresholds::trigger();
+    }
+    Success
+}
@@ -0,0 +1,31 @@
+use crate::guards::caller_is_service_principal;
+use crate::lifecycle::jobs;
+use crate::model::bucket_sync_state::EventToSync;
+use crate::{mutate_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::set_log_settings::{Response::*, *};
+
+#[update(guard = "caller_is_service_principal")]
+#[trace]
+fn set_log_settings(args: Args) -> Response {
+    mutate_state(|state| set_log_settings_impl(args, state))
+}
+
+// Applies the settings to the index, then if requested queues them to be synced to every bucket
+fn set_log_settings_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
+    canister_logger::apply_settings(&args.settings);
+
+    if args.apply_to_buckets {
+        let now = runtime_state.env.now();
+        runtime_state
+            .data
+            .buckets
+            .sync_event(EventToSync::LogSettingsUpdated(args.settings.clone()), now);
+        jobs::sync_users_with_buckets::trigger();
+        runtime_state.data.bucket_log_settings = args.settings.clone();
+    }
+
+    runtime_state.data.log_settings = args.settings;
+    Success
+}
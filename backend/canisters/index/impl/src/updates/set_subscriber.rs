@@ -0,0 +1,23 @@
+use crate::guards::caller_is_service_principal;
+use crate::{mutate_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::set_subscriber::{Response::*, *};
+
+#[update(guard = "caller_is_service_principal")]
+#[trace]
+fn set_subscriber(args: Args) -> Response {
+    mutate_state(|state| set_subscriber_impl(args, state))
+}
+
+fn set_subscriber_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
+    if args.event_types.is_empty() {
+        NoEventTypes
+    } else {
+        runtime_state
+            .data
+            .subscribers
+            .set(args.canister_id, args.event_types, args.users);
+        Success
+    }
+}
@@ -0,0 +1,23 @@
+use crate::guards::caller_is_service_principal;
+use crate::{mutate_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::split_bucket_sync_dead_letter::{Response::*, *};
+
+#[update(guard = "caller_is_service_principal")]
+#[trace]
+fn split_bucket_sync_dead_letter(args: Args) -> Response {
+    mutate_state(|state| split_bucket_sync_dead_letter_impl(args, state))
+}
+
+fn split_bucket_sync_dead_letter_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
+    if let Some(bucket) = runtime_state.data.buckets.get_mut(&args.canister_id) {
+        if let Some(dead_letter_ids) = bucket.sync_state.split_dead_letter(args.dead_letter_id) {
+            Success(SuccessResult { dead_letter_ids })
+        } else {
+            DeadLetterNotFoundOrTooSmall
+        }
+    } else {
+        BucketNotFound
+    }
+}
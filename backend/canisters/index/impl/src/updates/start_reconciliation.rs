@@ -0,0 +1,24 @@
+use crate::guards::caller_is_service_principal;
+use crate::lifecycle::jobs;
+use crate::{mutate_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::start_reconciliation::{Response::*, *};
+
+#[update(guard = "caller_is_service_principal")]
+#[trace]
+fn start_reconciliation(args: Args) -> Response {
+    mutate_state(|state| start_reconciliation_impl(args, state))
+}
+
+fn start_reconciliation_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
+    let now = runtime_state.env.now();
+    let buckets = runtime_state.data.buckets.iter().map(|b| b.canister_id).collect();
+
+    if runtime_state.data.reconciliation.start(buckets, args.repair, now) {
+        jobs::reconcile_buckets::trigger();
+        Success
+    } else {
+        AlreadyInProgress
+    }
+}
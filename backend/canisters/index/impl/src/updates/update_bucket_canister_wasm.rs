@@ -1,4 +1,6 @@
 use crate::guards::caller_is_service_principal;
+use crate::lifecycle::jobs;
+use crate::model::bucket_upgrade_rollout::{BucketUpgradeRollout, DEFAULT_MAX_UPGRADE_FAILURES};
 use crate::{mutate_state, RuntimeState};
 use canister_api_macros::trace;
 use ic_cdk_macros::update;
@@ -10,7 +12,7 @@ fn update_bucket_canister_wasm(args: Args) -> Response {
     mutate_state(|state| update_bucket_canister_wasm_impl(args, state))
 }
 
-fn update_bucket_canister_wasm_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
+pub(crate) fn update_bucket_canister_wasm_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
     let canisters_to_upgrade: Vec<_> = runtime_state
         .data
         .buckets
@@ -20,12 +22,63 @@ fn update_bucket_canister_wasm_impl(args: Args, runtime_state: &mut RuntimeState
         .collect();
 
     if canisters_to_upgrade.is_empty() {
-        VersionNotHigher
+        return VersionNotHigher;
+    }
+
+    // Any buckets whose upgrades are still in progress will join the rollout once they complete
+    let canisters_to_upgrade: Vec<_> = canisters_to_upgrade
+        .into_iter()
+        .filter(|c| !runtime_state.data.canisters_requiring_upgrade.is_in_progress(c))
+        .collect();
+
+    let (canaries, others): (Vec<_>, Vec<_>) = match args.canary {
+        None => (Vec::new(), canisters_to_upgrade),
+        Some(Canary::Percentage(percentage)) => {
+            if percentage == 0 || percentage > 100 {
+                return InvalidCanaryPercentage;
+            }
+            let canary_count = ((canisters_to_upgrade.len() * percentage as usize) + 99) / 100;
+            let mut canisters_to_upgrade = canisters_to_upgrade;
+            let others = canisters_to_upgrade.split_off(canary_count);
+            (canisters_to_upgrade, others)
+        }
+        Some(Canary::Buckets(canaries)) => {
+            if let Some(canister_id) = canaries.iter().find(|c| !canisters_to_upgrade.contains(c)) {
+                return CanaryBucketNotFound(*canister_id);
+            }
+            canisters_to_upgrade.into_iter().partition(|c| canaries.contains(c))
+        }
+    };
+
+    // The current wasm is only kept as the rollback target if every bucket is running it
+    let current_wasm_version = runtime_state.data.bucket_canister_wasm.version;
+    if runtime_state
+        .data
+        .buckets
+        .iter()
+        .all(|b| b.wasm_version == current_wasm_version)
+    {
+        let current_wasm = std::mem::replace(&mut runtime_state.data.bucket_canister_wasm, args.bucket_canister_wasm);
+        runtime_state.data.previous_bucket_canister_wasm = Some(current_wasm);
     } else {
         runtime_state.data.bucket_canister_wasm = args.bucket_canister_wasm;
-        for canister_id in canisters_to_upgrade {
-            runtime_state.data.canisters_requiring_upgrade.enqueue(canister_id)
-        }
-        Success
     }
+    runtime_state.data.record_bucket_canister_wasm_hash();
+
+    let (rollout, to_upgrade_now) = BucketUpgradeRollout::start(
+        runtime_state.data.bucket_canister_wasm.version,
+        canaries,
+        others,
+        args.max_failures.unwrap_or(DEFAULT_MAX_UPGRADE_FAILURES),
+    );
+    runtime_state.data.bucket_upgrade_rollout = rollout;
+
+    let canisters_requiring_upgrade = &mut runtime_state.data.canisters_requiring_upgrade;
+    canisters_requiring_upgrade.reset();
+    canisters_requiring_upgrade.resume();
+    for canister_id in to_upgrade_now {
+        canisters_requiring_upgrade.enqueue(canister_id);
+    }
+    jobs::upgrade_canisters::trigger();
+    Success
 }
@@ -1,8 +1,10 @@
 use crate::guards::caller_is_service_principal;
+use crate::lifecycle::jobs;
 use crate::model::bucket_sync_state::EventToSync;
 use crate::{mutate_state, RuntimeState};
 use canister_api_macros::trace;
 use ic_cdk_macros::update;
+use index_canister::events::{Event, UserIdUpdated};
 use index_canister::update_user_id::{Response::*, *};
 
 #[update(guard = "caller_is_service_principal")]
@@ -22,11 +24,33 @@ fn update_user_id_impl(args: Args, runtime_state: &mut RuntimeState) -> Response
                 .update_user_id(hash, args.old_user_id, args.new_user_id);
         }
 
-        runtime_state.data.users.insert(args.new_user_id, user);
+        // The user's id must also be updated on any buckets where they are an accessor of files
+        let mut buckets = user.buckets.clone();
+        if let Some(accessor_buckets) = runtime_state.data.accessors.remove(&args.old_user_id) {
+            buckets.extend(accessor_buckets.iter().copied());
+            runtime_state.data.accessors.insert(args.new_user_id, accessor_buckets);
+        }
+
+        let now = runtime_state.env.now();
+        // Until every accessor is tracked, the user may be an accessor of files on any bucket
+        let event = EventToSync::UserIdUpdated(args.old_user_id, args.new_user_id);
+        if runtime_state.data.all_accessors_tracked {
+            runtime_state.data.buckets.sync_event_to_buckets(&buckets, event, now);
+        } else {
+            runtime_state.data.buckets.sync_untargeted_event(event, now);
+        }
         runtime_state
             .data
             .buckets
-            .sync_event(EventToSync::UserIdUpdated(args.old_user_id, args.new_user_id));
+            .update_user_id_in_users_snapshots(args.old_user_id, args.new_user_id);
+        runtime_state.data.users.insert(args.new_user_id, user);
+        let event = Event::UserIdUpdated(UserIdUpdated {
+            old_user_id: args.old_user_id,
+            new_user_id: args.new_user_id,
+        });
+        runtime_state.data.push_event(None, event, now);
+        jobs::sync_users_with_buckets::trigger();
+        jobs::notify_subscribers::trigger();
 
         Success
     } else {
@@ -0,0 +1,24 @@
+use crate::guards::caller_is_service_principal;
+use crate::model::staged_wasm::PutChunkResult;
+use crate::{mutate_state, RuntimeState};
+use canister_api_macros::trace;
+use ic_cdk_macros::update;
+use index_canister::upload_wasm_chunk::{Response::*, *};
+
+#[update(guard = "caller_is_service_principal")]
+#[trace]
+fn upload_wasm_chunk(args: Args) -> Response {
+    mutate_state(|state| upload_wasm_chunk_impl(args, state))
+}
+
+fn upload_wasm_chunk_impl(args: Args, runtime_state: &mut RuntimeState) -> Response {
+    match runtime_state
+        .data
+        .staged_bucket_canister_wasm
+        .put_chunk(args.version, args.chunk_index, &args.bytes)
+    {
+        PutChunkResult::Success => Success,
+        PutChunkResult::UnexpectedChunkIndex(expected) => UnexpectedChunkIndex(expected),
+        PutChunkResult::WasmTooLarge(max_size) => WasmTooLarge(max_size),
+    }
+}
@@ -9,6 +9,12 @@ pub fn trace(_: TokenStream, item: TokenStream) -> TokenStream {
 
     // We will wrap the original fn in a new fn whose signature matches the original fn
     let wrapper_sig = inner.sig.clone();
+    let method_name = inner.sig.ident.to_string();
+
+    // The span is entered within a second fn so that the correlation id is set before the span starts
+    let traced_method_name = format_ident!("{}_traced_", inner.sig.ident);
+    let mut traced_sig = inner.sig.clone();
+    traced_sig.ident = traced_method_name.clone();
 
     // Change the name of the inner fn so that it doesn't clash with the wrapper fn
     let inner_method_name = format_ident!("{}_inner_", inner.sig.ident);
@@ -17,16 +23,34 @@ pub fn trace(_: TokenStream, item: TokenStream) -> TokenStream {
     let is_async = inner.sig.asyncness.is_some();
     let arg_names = get_arg_names(&inner.sig);
 
-    let function_call = if is_async {
-        quote! { #inner_method_name ( #(#arg_names),* ) .await }
+    let (function_call, correlated_call) = if is_async {
+        (
+            quote! { #inner_method_name ( #(#arg_names),* ) .await },
+            quote! {
+                canister_logger::with_correlation_id_async(
+                    Some(correlation_id),
+                    #traced_method_name ( #(#arg_names),* ),
+                ).await
+            },
+        )
     } else {
-        quote! { #inner_method_name ( #(#arg_names),* ) }
+        (
+            quote! { #inner_method_name ( #(#arg_names),* ) },
+            quote! {
+                canister_logger::with_correlation_id(Some(correlation_id), move || #traced_method_name ( #(#arg_names),* ))
+            },
+        )
     };
 
     let expanded = quote!(
         #[allow(unused_mut)]
-        #[tracing::instrument(level = "trace")]
         #wrapper_sig {
+            let correlation_id = canister_logger::correlation_id_for_call();
+            #correlated_call
+        }
+        #[allow(unused_mut)]
+        #[tracing::instrument(level = "trace", name = #method_name)]
+        #traced_sig {
             let result = #function_call;
             tracing::trace!(?result);
             result
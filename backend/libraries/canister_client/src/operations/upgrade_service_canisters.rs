@@ -5,7 +5,11 @@ use ic_agent::identity::BasicIdentity;
 use ic_utils::call::AsyncCall;
 use ic_utils::interfaces::management_canister::builders::InstallMode;
 use ic_utils::interfaces::ManagementCanister;
-use types::{CanisterId, CanisterWasm, Version};
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256};
+use types::{CanisterId, Hash, Version};
+
+const WASM_CHUNK_SIZE_BYTES: usize = 1 << 20; // 1Mb
 
 pub async fn upgrade_index_canister(identity: BasicIdentity, url: String, index_canister_id: CanisterId, version: Version) {
     let agent = build_ic_agent(url, identity).await;
@@ -20,19 +24,39 @@ pub async fn upgrade_index_canister(identity: BasicIdentity, url: String, index_
 pub async fn upgrade_bucket_canister(identity: BasicIdentity, url: String, index_canister_id: CanisterId, version: Version) {
     let agent = build_ic_agent(url, identity).await;
     let canister_wasm = get_canister_wasm(CanisterName::Bucket, version, true);
-    let args = index_canister::update_bucket_canister_wasm::Args {
-        bucket_canister_wasm: CanisterWasm {
+    let sha256: Hash = Sha256::digest(&canister_wasm.module).into();
+
+    // The wasm is uploaded in chunks to stay within the ingress message size limit
+    for (chunk_index, chunk) in canister_wasm.module.chunks(WASM_CHUNK_SIZE_BYTES).enumerate() {
+        let args = index_canister::upload_wasm_chunk::Args {
             version,
-            compressed: canister_wasm.compressed,
-            module: canister_wasm.module,
-        },
+            chunk_index: chunk_index as u32,
+            bytes: ByteBuf::from(chunk),
+        };
+
+        let response = index_canister_client::upload_wasm_chunk(&agent, &index_canister_id, &args)
+            .await
+            .unwrap();
+
+        if !matches!(response, index_canister::upload_wasm_chunk::Response::Success) {
+            panic!("{:?}", response);
+        }
+    }
+    println!("Bucket canister wasm uploaded");
+
+    let args = index_canister::commit_wasm::Args {
+        version,
+        compressed: canister_wasm.compressed,
+        sha256,
+        canary: None,
+        max_failures: None,
     };
 
-    let response = index_canister_client::update_bucket_canister_wasm(&agent, &index_canister_id, &args)
+    let response = index_canister_client::commit_wasm(&agent, &index_canister_id, &args)
         .await
         .unwrap();
 
-    if !matches!(response, index_canister::update_bucket_canister_wasm::Response::Success) {
+    if !matches!(response, index_canister::commit_wasm::Response::Success) {
         panic!("{:?}", response);
     }
     println!("Bucket canister wasm upgraded to version {version}");
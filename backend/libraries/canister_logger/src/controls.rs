@@ -0,0 +1,124 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::{Metadata, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, Filter};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{reload, Registry};
+use types::{LogLevel, LogSettings, TimestampMillis};
+
+thread_local! {
+    static CONTROLS: RefCell<Option<LogControls>> = RefCell::default();
+}
+
+// Handles onto the filters of the log and trace layers, allowing them to be changed at runtime
+pub(crate) struct LogControls {
+    pub log_level: reload::Handle<LevelFilter, Registry>,
+    pub trace_state: Arc<Mutex<TraceState>>,
+}
+
+pub(crate) fn set_controls(controls: LogControls) {
+    CONTROLS.with(|c| *c.borrow_mut() = Some(controls));
+}
+
+pub fn apply_settings(settings: &LogSettings) {
+    CONTROLS.with(|c| {
+        if let Some(controls) = c.borrow().as_ref() {
+            controls
+                .log_level
+                .reload(level_filter(settings.level))
+                .expect("Logger has been dropped");
+
+            let mut trace_state = controls.trace_state.lock().unwrap();
+            trace_state.trace_until = settings.trace_until;
+            trace_state.sampled_methods = settings
+                .sampled_methods
+                .iter()
+                .filter(|m| m.sample_every > 0)
+                .map(|m| (m.method_name.clone(), Sampler::new(m.sample_every)))
+                .collect();
+        }
+    });
+}
+
+pub(crate) fn level_filter(level: LogLevel) -> LevelFilter {
+    match level {
+        LogLevel::Error => LevelFilter::ERROR,
+        LogLevel::Warn => LevelFilter::WARN,
+        LogLevel::Info => LevelFilter::INFO,
+        LogLevel::Debug => LevelFilter::DEBUG,
+        LogLevel::Trace => LevelFilter::TRACE,
+    }
+}
+
+pub(crate) struct TraceState {
+    // Set for canisters in test mode, which capture every trace regardless of the settings
+    trace_always: bool,
+    trace_until: Option<TimestampMillis>,
+    sampled_methods: HashMap<String, Sampler>,
+}
+
+impl TraceState {
+    pub fn new(trace_always: bool) -> TraceState {
+        TraceState {
+            trace_always,
+            trace_until: None,
+            sampled_methods: HashMap::new(),
+        }
+    }
+}
+
+struct Sampler {
+    sample_every: u32,
+    calls: u64,
+}
+
+impl Sampler {
+    fn new(sample_every: u32) -> Sampler {
+        Sampler { sample_every, calls: 0 }
+    }
+
+    fn sample(&mut self) -> bool {
+        let sampled = self.calls % self.sample_every as u64 == 0;
+        self.calls += 1;
+        sampled
+    }
+}
+
+// Decides which spans and events are captured by the trace layer. Everything is captured during the
+// trace window, otherwise only the spans of sampled method calls, along with everything within them.
+pub(crate) struct TraceFilter {
+    pub state: Arc<Mutex<TraceState>>,
+    pub time_fn: fn() -> TimestampMillis,
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Filter<S> for TraceFilter {
+    fn enabled(&self, metadata: &Metadata<'_>, context: &Context<'_, S>) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        if state.trace_always || state.trace_until.map_or(false, |t| (self.time_fn)() < t) {
+            true
+        } else if context.lookup_current().is_some() {
+            // The current span is only visible here if it was itself captured
+            true
+        } else if metadata.is_span() {
+            state.sampled_methods.get_mut(metadata.name()).map_or(false, |s| s.sample())
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampler_captures_one_in_every_n_calls() {
+        let mut sampler = Sampler::new(3);
+        let sampled: Vec<_> = (0..6).map(|_| sampler.sample()).collect();
+
+        assert_eq!(sampled, vec![true, false, false, true, false, false]);
+    }
+}
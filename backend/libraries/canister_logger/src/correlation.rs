@@ -0,0 +1,86 @@
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+// A correlation id ties together everything logged while handling a call, so that an operation can be
+// followed across canisters by passing the id along with any events it causes. The current id is
+// added as a field to every log and trace message.
+thread_local! {
+    static CURRENT: RefCell<Option<String>> = RefCell::default();
+    static NEXT_ID: Cell<u64> = Cell::default();
+}
+
+// Ids are made up of the first part of the canister id, the time and a counter, so that they are
+// unique across canisters and upgrades
+pub fn new_correlation_id() -> String {
+    let canister_id = ic_cdk::id().to_string();
+    let prefix = canister_id.split('-').next().unwrap_or_default();
+    let count = NEXT_ID.with(|c| c.replace(c.get() + 1));
+
+    format!("{}-{:x}-{:x}", prefix, ic_cdk::api::time(), count)
+}
+
+pub fn current_correlation_id() -> Option<String> {
+    CURRENT.with(|c| c.borrow().clone())
+}
+
+// Used by the `trace` macro so that each call gets a new id unless it is nested within another call
+pub fn correlation_id_for_call() -> String {
+    current_correlation_id().unwrap_or_else(new_correlation_id)
+}
+
+// Runs `f` with the given correlation id, or with the current id if None is given
+pub fn with_correlation_id<T>(correlation_id: Option<String>, f: impl FnOnce() -> T) -> T {
+    if correlation_id.is_none() {
+        return f();
+    }
+
+    let previous = CURRENT.with(|c| c.replace(correlation_id));
+    let result = f();
+    CURRENT.with(|c| *c.borrow_mut() = previous);
+    result
+}
+
+// Each time an async call resumes after an await it is within a new message execution, during which
+// other calls may have run, so the id has to be set again every time the future is polled
+pub fn with_correlation_id_async<F: Future>(correlation_id: Option<String>, future: F) -> Correlated<F> {
+    Correlated {
+        correlation_id,
+        future: Box::pin(future),
+    }
+}
+
+pub struct Correlated<F> {
+    correlation_id: Option<String>,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for Correlated<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let future = this.future.as_mut();
+        with_correlation_id(this.correlation_id.clone(), || future.poll(context))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn previous_id_restored_after_nested_call() {
+        with_correlation_id(Some("a".to_string()), || {
+            with_correlation_id(Some("b".to_string()), || {
+                assert_eq!(current_correlation_id().as_deref(), Some("b"));
+            });
+            with_correlation_id(None, || {
+                assert_eq!(current_correlation_id().as_deref(), Some("a"));
+            });
+            assert_eq!(current_correlation_id().as_deref(), Some("a"));
+        });
+        assert!(current_correlation_id().is_none());
+    }
+}
@@ -1,28 +1,66 @@
+mod controls;
+mod correlation;
 mod panic_hook;
+mod stable_buffer;
 
+pub use controls::apply_settings;
+pub use correlation::{
+    correlation_id_for_call, current_correlation_id, new_correlation_id, with_correlation_id, with_correlation_id_async,
+    Correlated,
+};
 pub use panic_hook::set_panic_hook;
 
 use candid::CandidType;
+use controls::{LogControls, TraceFilter, TraceState};
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use stable_buffer::RingBuffer;
+use std::cmp::max;
+use std::collections::{BTreeMap, VecDeque};
 use std::iter::FromIterator;
 use std::ops::Deref;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 use tracing::Level;
 use tracing_subscriber::fmt::format::{FmtSpan, Writer};
 use tracing_subscriber::fmt::time::FormatTime;
-use tracing_subscriber::fmt::writer::MakeWriterExt;
 use tracing_subscriber::fmt::Layer;
-use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::layer::{Layer as _, SubscriberExt};
+use tracing_subscriber::reload;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::Registry;
-use types::TimestampMillis;
-
-const DEFAULT_MAX_MESSAGES: usize = 100;
-
-pub fn init_logger(enable_trace: bool, max_messages: Option<usize>, time_fn: fn() -> TimestampMillis) -> LogMessagesWrapper {
-    let log_messages_container = LogMessagesContainer::new(max_messages.unwrap_or(DEFAULT_MAX_MESSAGES));
-    let trace_messages_container = LogMessagesContainer::new(max_messages.unwrap_or(DEFAULT_MAX_MESSAGES));
+use types::{LogSettings, TimestampMillis};
+use utils::stable_memory::StableMemory;
+
+const DEFAULT_MAX_MESSAGES: usize = 1000;
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+// If `stable_buffer_size_mb` is set, messages are held in a ring buffer at the start of stable memory
+// rather than on the heap, so they survive upgrades without being serialized along with the state.
+// In that case the canister must store its own data in stable memory after `stable_state_offset()`.
+//
+// If `trace_always` is set every trace is captured, otherwise traces are only captured as specified by
+// the settings, which can be changed at any time by calling `apply_settings`.
+pub fn init_logger(
+    trace_always: bool,
+    settings: &LogSettings,
+    max_messages: Option<usize>,
+    stable_buffer_size_mb: Option<u32>,
+    time_fn: fn() -> TimestampMillis,
+) -> LogMessagesWrapper {
+    let (log_messages_container, trace_messages_container) = if let Some(size_mb) = stable_buffer_size_mb {
+        let mut rings = stable_buffer::init_region(|| Box::new(StableMemory), size_mb as u64 * BYTES_PER_MB, 2);
+        let trace_messages_container = LogMessagesContainer::with_stable_buffer(rings.pop().unwrap());
+        (
+            LogMessagesContainer::with_stable_buffer(rings.pop().unwrap()),
+            trace_messages_container,
+        )
+    } else {
+        stable_buffer::clear_region(&mut StableMemory);
+        (
+            LogMessagesContainer::new(max_messages.unwrap_or(DEFAULT_MAX_MESSAGES)),
+            LogMessagesContainer::new(max_messages.unwrap_or(DEFAULT_MAX_MESSAGES)),
+        )
+    };
 
     let log_messages_wrapper = LogMessagesWrapper {
         logs: log_messages_container.clone(),
@@ -43,25 +81,32 @@ pub fn init_logger(enable_trace: bool, max_messages: Option<usize>, time_fn: fn(
 
     let timer = Timer { time_fn };
 
+    let (log_level_filter, log_level) = reload::Layer::new(controls::level_filter(settings.level));
+    let trace_state = Arc::new(Mutex::new(TraceState::new(trace_always)));
+
     let log_layer = Layer::default()
-        .with_writer(make_log_writer.with_max_level(Level::INFO))
+        .with_writer(make_log_writer)
         .with_timer(timer.clone())
         .json()
         .with_current_span(false)
-        .with_span_list(false);
+        .with_span_list(false)
+        .with_filter(log_level_filter);
+
+    let trace_layer = Layer::default()
+        .with_writer(make_trace_writer)
+        .with_timer(timer)
+        .with_span_events(FmtSpan::ENTER)
+        .json()
+        .with_current_span(false)
+        .with_filter(TraceFilter {
+            state: trace_state.clone(),
+            time_fn,
+        });
 
-    if enable_trace {
-        let trace_layer = Layer::default()
-            .with_writer(make_trace_writer)
-            .with_timer(timer)
-            .with_span_events(FmtSpan::ENTER)
-            .json()
-            .with_current_span(false);
+    Registry::default().with(log_layer).with(trace_layer).init();
 
-        Registry::default().with(log_layer).with(trace_layer).init();
-    } else {
-        Registry::default().with(log_layer).init();
-    }
+    controls::set_controls(LogControls { log_level, trace_state });
+    apply_settings(settings);
 
     log_messages_wrapper
 }
@@ -74,10 +119,113 @@ pub struct LogMessagesWrapper {
 
 #[derive(CandidType, Serialize, Deserialize, Clone)]
 pub struct LogMessage {
+    // Increases with each message added, so can be used as a cursor when paging through messages
+    #[serde(default)]
+    pub index: u64,
     pub timestamp: TimestampMillis,
+    #[serde(default)]
+    pub level: String,
+    #[serde(default)]
+    pub target: String,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub fields: Vec<(String, String)>,
     pub json: String,
 }
 
+impl LogMessage {
+    // Extracts the structured fields from the json written by the tracing subscriber. If the json
+    // can't be parsed the message is still kept, just without any structured fields.
+    //
+    // The correlation id, if there is one, is added to the fields so that it is included in the json
+    // and can be filtered on like any other field.
+    pub fn new(timestamp: TimestampMillis, json: String, correlation_id: Option<String>) -> LogMessage {
+        let (parsed, json) = match serde_json::from_str::<JsonLogLine>(&json) {
+            Ok(mut parsed) => match correlation_id {
+                Some(id) => {
+                    parsed
+                        .fields
+                        .insert("correlation_id".to_string(), serde_json::Value::String(id));
+                    let json = serde_json::to_string(&parsed).unwrap_or(json);
+                    (parsed, json)
+                }
+                None => (parsed, json),
+            },
+            Err(_) => (JsonLogLine::default(), json),
+        };
+
+        let mut message = String::new();
+        let mut fields = Vec::new();
+        for (key, value) in parsed.fields {
+            let value = match value {
+                serde_json::Value::String(s) => s,
+                v => v.to_string(),
+            };
+            if key == "message" {
+                message = value;
+            } else {
+                fields.push((key, value));
+            }
+        }
+
+        LogMessage {
+            index: 0,
+            timestamp,
+            level: parsed.level,
+            target: parsed.target,
+            message,
+            fields,
+            json,
+        }
+    }
+
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct JsonLogLine {
+    #[serde(default)]
+    level: String,
+    #[serde(default)]
+    target: String,
+    #[serde(default)]
+    fields: BTreeMap<String, serde_json::Value>,
+    // Anything else written by the subscriber, eg. the timestamp, which is kept when the json is rewritten
+    #[serde(flatten)]
+    other: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LogFilter {
+    pub since: Option<TimestampMillis>,
+    // Only messages at this level or more severe are included
+    pub level: Option<Level>,
+    pub contains: Option<String>,
+    pub fields: Vec<(String, String)>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, message: &LogMessage) -> bool {
+        self.since.map_or(true, |s| message.timestamp > s)
+            && self.level.map_or(true, |l| {
+                // Messages logged before levels were recorded are always included
+                Level::from_str(&message.level).map_or(true, |ml| ml <= l)
+            })
+            && self.contains.as_ref().map_or(true, |c| message.json.contains(c.as_str()))
+            && self.fields.iter().all(|(k, v)| message.field(k) == Some(v.as_str()))
+    }
+}
+
+pub struct LogPage {
+    pub messages: Vec<LogMessage>,
+    // Set if there are more matching messages, in which case it should be passed as `after` to get
+    // the next page
+    pub next_cursor: Option<u64>,
+}
+
 struct LogWriter {
     messages_container: LogMessagesContainer,
     time_fn: fn() -> TimestampMillis,
@@ -93,10 +241,11 @@ impl std::io::Write for LogWriter {
     fn flush(&mut self) -> std::io::Result<()> {
         let buffer = std::mem::take(&mut self.buffer);
 
-        self.messages_container.push(LogMessage {
-            timestamp: (self.time_fn)(),
-            json: String::from_utf8(buffer).unwrap(),
-        });
+        self.messages_container.push(LogMessage::new(
+            (self.time_fn)(),
+            String::from_utf8(buffer).unwrap(),
+            correlation::current_correlation_id(),
+        ));
         Ok(())
     }
 
@@ -118,24 +267,42 @@ impl LogMessagesContainer {
         }
     }
 
+    fn with_stable_buffer(ring: RingBuffer) -> LogMessagesContainer {
+        LogMessagesContainer {
+            container: Arc::new(Mutex::new(LogMessages::with_stable_buffer(ring))),
+        }
+    }
+
     pub fn get(&self, since: TimestampMillis) -> Vec<LogMessage> {
         self.container.deref().lock().unwrap().get(since)
     }
 
+    pub fn query(&self, filter: &LogFilter, after: Option<u64>, limit: usize) -> LogPage {
+        self.container.deref().lock().unwrap().query(filter, after, limit)
+    }
+
     pub fn push(&self, message: LogMessage) {
         self.container.deref().lock().unwrap().push(message);
     }
 
+    // Messages held in stable memory are left where they are since they already survive upgrades
     pub fn drain_messages(&self) -> Vec<LogMessage> {
         let messages = &mut self.container.deref().lock().unwrap().messages;
         Vec::from_iter(std::mem::take(messages))
     }
 }
 
-#[derive(CandidType, Serialize, Deserialize, Default)]
+// Returns the offset in stable memory from which the canister can store its own data
+pub fn stable_state_offset() -> u64 {
+    stable_buffer::region_end(&StableMemory)
+}
+
+#[derive(Default)]
 struct LogMessages {
     max_messages: usize,
     messages: VecDeque<LogMessage>,
+    next_index: u64,
+    stable_buffer: Option<RingBuffer>,
 }
 
 impl LogMessages {
@@ -143,14 +310,52 @@ impl LogMessages {
         LogMessages {
             max_messages,
             messages: VecDeque::new(),
+            next_index: 0,
+            stable_buffer: None,
+        }
+    }
+
+    fn with_stable_buffer(ring: RingBuffer) -> LogMessages {
+        LogMessages {
+            max_messages: 0,
+            messages: VecDeque::new(),
+            next_index: ring.next_index(),
+            stable_buffer: Some(ring),
         }
     }
 
     pub fn get(&self, since: TimestampMillis) -> Vec<LogMessage> {
-        self.messages.iter().skip_while(|l| l.timestamp <= since).cloned().collect()
+        let filter = LogFilter {
+            since: Some(since),
+            ..Default::default()
+        };
+        self.query(&filter, None, usize::MAX).messages
     }
 
-    pub fn push(&mut self, message: LogMessage) {
+    // The stable buffer is scanned lazily so that only the messages up to the end of the page are read
+    pub fn query(&self, filter: &LogFilter, after: Option<u64>, limit: usize) -> LogPage {
+        if let Some(ring) = &self.stable_buffer {
+            take_page(ring.messages_after(after).filter(|m| filter.matches(m)), limit)
+        } else {
+            let messages = self
+                .messages
+                .iter()
+                .filter(|m| after.map_or(true, |a| m.index > a) && filter.matches(m))
+                .cloned();
+            take_page(messages, limit)
+        }
+    }
+
+    pub fn push(&mut self, mut message: LogMessage) {
+        // Messages rehydrated after an upgrade keep their original index
+        message.index = max(message.index, self.next_index);
+        self.next_index = message.index + 1;
+
+        if let Some(ring) = self.stable_buffer.as_mut() {
+            ring.push(&message);
+            return;
+        }
+
         while self.messages.len() >= self.max_messages {
             self.messages.pop_front();
         }
@@ -158,6 +363,20 @@ impl LogMessages {
     }
 }
 
+// Takes up to `limit` messages, only reading one more than that to determine whether there are more
+fn take_page(messages: impl Iterator<Item = LogMessage>, limit: usize) -> LogPage {
+    let mut messages: Vec<_> = messages.take(limit.saturating_add(1)).collect();
+
+    let next_cursor = if messages.len() > limit {
+        messages.truncate(limit);
+        messages.last().map(|m| m.index)
+    } else {
+        None
+    };
+
+    LogPage { messages, next_cursor }
+}
+
 #[derive(Clone)]
 struct Timer {
     time_fn: fn() -> TimestampMillis,
@@ -178,7 +397,7 @@ mod tests {
 
     #[test]
     fn log_messages_can_be_accessed_outside_of_logger() {
-        let log_messages = init_logger(true, None, || 1);
+        let log_messages = init_logger(true, &LogSettings::default(), None, None, || 1);
 
         info!("test!");
 
@@ -191,6 +410,51 @@ mod tests {
         assert_eq!(2, log_messages.traces.drain_messages().len());
     }
 
+    #[test]
+    fn query_filters_and_pages_messages() {
+        let mut log_messages = LogMessages::new(10);
+        let lines = [
+            r#"{"level":"INFO","target":"index","fields":{"message":"User added","user_id":"abc"}}"#,
+            r#"{"level":"ERROR","target":"index","fields":{"message":"Sync failed","user_id":"abc"}}"#,
+            r#"{"level":"WARN","target":"index","fields":{"message":"Sync retried","user_id":"def"}}"#,
+            r#"{"level":"ERROR","target":"index","fields":{"message":"Sync failed","user_id":"def"}}"#,
+        ];
+        for line in lines {
+            log_messages.push(LogMessage::new(1, line.to_string(), None));
+        }
+
+        let filter = LogFilter {
+            level: Some(Level::WARN),
+            contains: Some("Sync".to_string()),
+            ..Default::default()
+        };
+        let page = log_messages.query(&filter, None, 2);
+        assert_eq!(page.messages.iter().map(|m| m.index).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(page.next_cursor, Some(2));
+
+        let page = log_messages.query(&filter, page.next_cursor, 2);
+        assert_eq!(page.messages.len(), 1);
+        assert_eq!(page.messages[0].message, "Sync failed");
+        assert!(page.next_cursor.is_none());
+
+        let filter = LogFilter {
+            fields: vec![("user_id".to_string(), "def".to_string())],
+            ..Default::default()
+        };
+        assert_eq!(log_messages.query(&filter, None, 10).messages.len(), 2);
+    }
+
+    #[test]
+    fn correlation_id_added_to_fields() {
+        let line = r#"{"timestamp":1,"level":"INFO","fields":{"message":"File added"},"target":"bucket"}"#;
+        let message = LogMessage::new(1, line.to_string(), Some("abc".to_string()));
+
+        assert_eq!(message.field("correlation_id"), Some("abc"));
+        assert_eq!(message.message, "File added");
+        assert!(message.json.contains(r#""correlation_id":"abc""#));
+        assert!(message.json.contains(r#""timestamp":1"#));
+    }
+
     #[instrument(level = "trace")]
     fn add_one(value: u32) -> u32 {
         info!("abc");
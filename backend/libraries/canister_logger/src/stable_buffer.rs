@@ -0,0 +1,333 @@
+use crate::LogMessage;
+use std::cmp::min;
+use utils::stable_memory::Memory;
+
+// The log region sits at the start of stable memory and is laid out as -
+// [region header][ring 0 header][ring 0 data][ring 1 header][ring 1 data]...
+// Anything written to stable memory by the canister itself (eg. its serialized state on upgrade)
+// must come after the region, starting at `region_end`.
+const MAGIC: &[u8; 4] = b"CLOG";
+const LAYOUT_VERSION: u8 = 1;
+const REGION_HEADER_SIZE: u64 = 64;
+const RING_HEADER_SIZE: u64 = 24;
+// Each message is prefixed with its length and its index, so that messages can be skipped over without
+// being decoded
+const RECORD_HEADER_SIZE: u64 = 12;
+
+// Returns the offset at which the log region ends, or 0 if there is no log region
+pub fn region_end(memory: &dyn Memory) -> u64 {
+    read_region_header(memory).map_or(0, |h| h.region_size)
+}
+
+// Opens the rings within the existing log region if it has the same layout, otherwise the region is
+// recreated. If only the ring count has changed the messages held by each old ring are copied into the
+// new ring at the same position, but if the region size has changed any messages it held are dropped.
+pub fn init_region(make_memory: impl Fn() -> Box<dyn Memory>, region_size: u64, ring_count: u8) -> Vec<RingBuffer> {
+    let mut memory = make_memory();
+    let header = RegionHeader { region_size, ring_count };
+    let existing_header = read_region_header(memory.as_ref());
+    let is_existing_region = existing_header == Some(header);
+
+    let messages_to_migrate: Vec<Vec<LogMessage>> = match existing_header {
+        Some(h) if !is_existing_region && h.region_size == region_size => {
+            (0..h.ring_count).map(|i| open_ring(make_memory(), h, i).messages()).collect()
+        }
+        _ => Vec::new(),
+    };
+
+    if !is_existing_region {
+        memory.grow_to(region_size);
+    }
+
+    let mut rings: Vec<_> = (0..ring_count)
+        .map(|i| {
+            if is_existing_region {
+                open_ring(make_memory(), header, i)
+            } else {
+                let (start, capacity) = ring_bounds(header, i);
+                RingBuffer::create(make_memory(), start, capacity)
+            }
+        })
+        .collect();
+
+    for (ring, messages) in rings.iter_mut().zip(messages_to_migrate) {
+        for message in messages {
+            ring.push(&message);
+        }
+    }
+
+    if !is_existing_region {
+        write_region_header(memory.as_mut(), header);
+    }
+    rings
+}
+
+fn open_ring(memory: Box<dyn Memory>, header: RegionHeader, index: u8) -> RingBuffer {
+    let (start, capacity) = ring_bounds(header, index);
+    RingBuffer::open(memory, start, capacity)
+}
+
+// Returns the start offset and the data capacity of the ring at the given index
+fn ring_bounds(header: RegionHeader, index: u8) -> (u64, u64) {
+    let ring_size = (header.region_size - REGION_HEADER_SIZE) / header.ring_count as u64;
+    let start = REGION_HEADER_SIZE + index as u64 * ring_size;
+    (start, ring_size - RING_HEADER_SIZE)
+}
+
+// Marks the log region as no longer in use so that stable memory is once again free from offset 0
+pub fn clear_region(memory: &mut dyn Memory) {
+    if read_region_header(memory).is_some() {
+        memory.write(0, &[0; 4]);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct RegionHeader {
+    region_size: u64,
+    ring_count: u8,
+}
+
+fn read_region_header(memory: &dyn Memory) -> Option<RegionHeader> {
+    if memory.size_bytes() < REGION_HEADER_SIZE {
+        return None;
+    }
+
+    let mut bytes = [0; 16];
+    memory.read(0, &mut bytes);
+    if &bytes[0..4] != MAGIC || bytes[4] != LAYOUT_VERSION {
+        return None;
+    }
+
+    Some(RegionHeader {
+        ring_count: bytes[5],
+        region_size: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+    })
+}
+
+fn write_region_header(memory: &mut dyn Memory, header: RegionHeader) {
+    let mut bytes = [0; 16];
+    bytes[0..4].copy_from_slice(MAGIC);
+    bytes[4] = LAYOUT_VERSION;
+    bytes[5] = header.ring_count;
+    bytes[8..16].copy_from_slice(&header.region_size.to_le_bytes());
+    memory.write(0, &bytes);
+}
+
+// A ring of messages, each prefixed by a record header. `head` and `tail` are positions in the stream of bytes written
+// so far, so only ever increase, and are mapped onto the ring's capacity when reading or writing.
+// The oldest messages are dropped to make space for new ones.
+pub struct RingBuffer {
+    memory: Box<dyn Memory>,
+    start: u64,
+    capacity: u64,
+    head: u64,
+    tail: u64,
+    next_index: u64,
+}
+
+impl RingBuffer {
+    fn create(memory: Box<dyn Memory>, start: u64, capacity: u64) -> RingBuffer {
+        let mut ring = RingBuffer {
+            memory,
+            start,
+            capacity,
+            head: 0,
+            tail: 0,
+            next_index: 0,
+        };
+        ring.write_header();
+        ring
+    }
+
+    fn open(memory: Box<dyn Memory>, start: u64, capacity: u64) -> RingBuffer {
+        let mut bytes = [0; RING_HEADER_SIZE as usize];
+        memory.read(start, &mut bytes);
+
+        RingBuffer {
+            memory,
+            start,
+            capacity,
+            head: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            tail: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+            next_index: u64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+        }
+    }
+
+    pub fn next_index(&self) -> u64 {
+        self.next_index
+    }
+
+    pub fn push(&mut self, message: &LogMessage) {
+        let bytes = serde_json::to_vec(message).unwrap();
+        let length = RECORD_HEADER_SIZE + bytes.len() as u64;
+        if length > self.capacity {
+            return;
+        }
+
+        while self.tail + length - self.head > self.capacity {
+            let (oldest_length, _) = self.read_record_header(self.head);
+            self.head += RECORD_HEADER_SIZE + oldest_length;
+        }
+
+        let mut header = [0; RECORD_HEADER_SIZE as usize];
+        header[0..4].copy_from_slice(&(bytes.len() as u32).to_le_bytes());
+        header[4..12].copy_from_slice(&message.index.to_le_bytes());
+        self.write_at(self.tail, &header);
+        self.write_at(self.tail + RECORD_HEADER_SIZE, &bytes);
+        self.tail += length;
+        self.next_index = message.index + 1;
+        self.write_header();
+    }
+
+    // Returns every message held, oldest first
+    pub fn messages(&self) -> Vec<LogMessage> {
+        self.messages_after(None).collect()
+    }
+
+    // Returns the messages with an index greater than `after`, oldest first. Messages are only read
+    // and decoded as the iterator is advanced, and those up to `after` are skipped without decoding.
+    pub fn messages_after(&self, after: Option<u64>) -> impl Iterator<Item = LogMessage> + '_ {
+        let mut position = self.head;
+        std::iter::from_fn(move || {
+            while position < self.tail {
+                let (length, index) = self.read_record_header(position);
+                let data_position = position + RECORD_HEADER_SIZE;
+                position = data_position + length;
+
+                if after.map_or(true, |a| index > a) {
+                    let mut bytes = vec![0; length as usize];
+                    self.read_at(data_position, &mut bytes);
+                    if let Ok(message) = serde_json::from_slice(&bytes) {
+                        return Some(message);
+                    }
+                }
+            }
+            None
+        })
+    }
+
+    // Returns the length of the message at the given position along with its index
+    fn read_record_header(&self, position: u64) -> (u64, u64) {
+        let mut bytes = [0; RECORD_HEADER_SIZE as usize];
+        self.read_at(position, &mut bytes);
+        let length = u32::from_le_bytes(bytes[0..4].try_into().unwrap()) as u64;
+        let index = u64::from_le_bytes(bytes[4..12].try_into().unwrap());
+        (length, index)
+    }
+
+    fn read_at(&self, position: u64, buf: &mut [u8]) {
+        let data_start = self.start + RING_HEADER_SIZE;
+        let offset = position % self.capacity;
+        let first_part = min(buf.len() as u64, self.capacity - offset) as usize;
+        self.memory.read(data_start + offset, &mut buf[..first_part]);
+        if first_part < buf.len() {
+            self.memory.read(data_start, &mut buf[first_part..]);
+        }
+    }
+
+    fn write_at(&mut self, position: u64, buf: &[u8]) {
+        let data_start = self.start + RING_HEADER_SIZE;
+        let offset = position % self.capacity;
+        let first_part = min(buf.len() as u64, self.capacity - offset) as usize;
+        self.memory.write(data_start + offset, &buf[..first_part]);
+        if first_part < buf.len() {
+            self.memory.write(data_start, &buf[first_part..]);
+        }
+    }
+
+    fn write_header(&mut self) {
+        let mut bytes = [0; RING_HEADER_SIZE as usize];
+        bytes[0..8].copy_from_slice(&self.head.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.tail.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.next_index.to_le_bytes());
+        self.memory.write(self.start, &bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::stable_memory::VecMemory;
+
+    fn message(index: u64) -> LogMessage {
+        LogMessage {
+            index,
+            timestamp: index,
+            level: "INFO".to_string(),
+            target: String::new(),
+            message: format!("message {}", index),
+            fields: Vec::new(),
+            json: String::new(),
+        }
+    }
+
+    #[test]
+    fn oldest_messages_dropped_when_ring_is_full() {
+        let memory = VecMemory::default();
+        let mut rings = init_region(|| Box::new(memory.clone()), 1024, 2);
+        assert_eq!(region_end(&memory), 1024);
+
+        for index in 0..100 {
+            rings[0].push(&message(index));
+        }
+
+        let messages = rings[0].messages();
+        assert!(messages.len() < 100);
+        assert_eq!(messages.last().unwrap().index, 99);
+        assert!(messages.windows(2).all(|w| w[1].index == w[0].index + 1));
+        assert!(rings[1].messages().is_empty());
+    }
+
+    #[test]
+    fn messages_after_cursor_returned_lazily() {
+        let memory = VecMemory::default();
+        let mut rings = init_region(|| Box::new(memory.clone()), 4096, 1);
+        for index in 0..10 {
+            rings[0].push(&message(index));
+        }
+
+        let indexes: Vec<_> = rings[0].messages_after(Some(6)).map(|m| m.index).collect();
+        assert_eq!(indexes, vec![7, 8, 9]);
+
+        let first_two: Vec<_> = rings[0].messages_after(None).take(2).map(|m| m.index).collect();
+        assert_eq!(first_two, vec![0, 1]);
+    }
+
+    #[test]
+    fn messages_survive_reopening_region() {
+        let memory = VecMemory::default();
+        let mut rings = init_region(|| Box::new(memory.clone()), 1024, 1);
+        rings[0].push(&message(0));
+        rings[0].push(&message(1));
+
+        let rings = init_region(|| Box::new(memory.clone()), 1024, 1);
+        assert_eq!(rings[0].messages().len(), 2);
+        assert_eq!(rings[0].next_index(), 2);
+
+        // Changing the region size resets the region
+        let rings = init_region(|| Box::new(memory.clone()), 2048, 1);
+        assert!(rings[0].messages().is_empty());
+
+        clear_region(&mut memory.clone());
+        assert_eq!(region_end(&memory), 0);
+    }
+
+    #[test]
+    fn messages_migrated_when_ring_count_changes() {
+        let memory = VecMemory::default();
+        let mut rings = init_region(|| Box::new(memory.clone()), 1024, 1);
+        for index in 0..3 {
+            rings[0].push(&message(index));
+        }
+
+        let rings = init_region(|| Box::new(memory.clone()), 1024, 2);
+        let indexes: Vec<_> = rings[0].messages().into_iter().map(|m| m.index).collect();
+        assert_eq!(indexes, vec![0, 1, 2]);
+        assert_eq!(rings[0].next_index(), 3);
+        assert!(rings[1].messages().is_empty());
+
+        // The new layout is persisted so the messages are not migrated again
+        let rings = init_region(|| Box::new(memory.clone()), 1024, 2);
+        assert_eq!(rings[0].messages().len(), 3);
+    }
+}
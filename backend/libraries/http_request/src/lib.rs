@@ -5,10 +5,12 @@ use std::borrow::Cow;
 
 mod logs_handler;
 mod metrics_handler;
+mod prometheus;
 mod router;
 
 pub use logs_handler::*;
 pub use metrics_handler::*;
+pub use prometheus::*;
 pub use router::*;
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
@@ -71,6 +73,15 @@ impl HttpResponse {
         }
     }
 
+    pub fn bad_request(message: &str) -> HttpResponse {
+        HttpResponse {
+            status_code: 400,
+            headers: vec![HeaderField("Content-Type".to_string(), "text/plain".to_string())],
+            body: Cow::Owned(ByteBuf::from(message.as_bytes().to_vec())),
+            streaming_strategy: None,
+        }
+    }
+
     pub fn gone() -> HttpResponse {
         HttpResponse::status_code(410)
     }
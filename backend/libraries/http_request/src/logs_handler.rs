@@ -1,22 +1,28 @@
 use crate::{HeaderField, HttpResponse};
-use canister_logger::LogMessage;
+use canister_logger::LogPage;
 use serde_bytes::ByteBuf;
 use std::borrow::Cow;
 use std::io::Write;
 
-pub fn encode_logs(messages: Vec<LogMessage>) -> HttpResponse {
+pub fn encode_logs(page: LogPage) -> HttpResponse {
     let mut body = Vec::new();
 
-    for message in messages.into_iter() {
+    for message in page.messages.into_iter() {
         writeln!(&mut body, "{}", message.json).unwrap();
     }
 
+    let mut headers = vec![
+        HeaderField("Content-Type".to_string(), "text/plain".to_string()),
+        HeaderField("Content-Length".to_string(), body.len().to_string()),
+    ];
+    // Pass this back as `after` to get the next page of messages
+    if let Some(next_cursor) = page.next_cursor {
+        headers.push(HeaderField("X-Next-Cursor".to_string(), next_cursor.to_string()));
+    }
+
     HttpResponse {
         status_code: 200,
-        headers: vec![
-            HeaderField("Content-Type".to_string(), "text/plain".to_string()),
-            HeaderField("Content-Length".to_string(), body.len().to_string()),
-        ],
+        headers,
         body: Cow::Owned(ByteBuf::from(body)),
         streaming_strategy: None,
     }
@@ -0,0 +1,189 @@
+use crate::{HeaderField, HttpRequest, HttpResponse};
+use serde_bytes::ByteBuf;
+use std::borrow::Cow;
+use std::fmt::Write;
+
+// Implemented by each canister's `Metrics` to map its fields onto Prometheus metrics
+pub trait PrometheusMetrics {
+    fn encode(&self, encoder: &mut PrometheusEncoder);
+}
+
+// Builds a response in the Prometheus text exposition format. Samples are grouped by metric so that
+// labelled samples can be added in any order, eg. while iterating over a list of buckets.
+pub struct PrometheusEncoder {
+    namespace: String,
+    metrics: Vec<Metric>,
+}
+
+struct Metric {
+    name: String,
+    help: String,
+    metric_type: &'static str,
+    // Each sample's name suffix and labels, eg. `_bucket{le="10"}`, along with its value
+    samples: Vec<(String, f64)>,
+}
+
+impl PrometheusEncoder {
+    pub fn new(namespace: &str) -> PrometheusEncoder {
+        PrometheusEncoder {
+            namespace: namespace.to_string(),
+            metrics: Vec::new(),
+        }
+    }
+
+    pub fn gauge(&mut self, name: &str, help: &str, value: f64) -> &mut Self {
+        self.gauge_with_labels(name, help, &[], value)
+    }
+
+    pub fn gauge_with_labels(&mut self, name: &str, help: &str, labels: &[(&str, &str)], value: f64) -> &mut Self {
+        self.add_sample(name, help, "gauge", labels, value)
+    }
+
+    pub fn counter(&mut self, name: &str, help: &str, value: f64) -> &mut Self {
+        self.counter_with_labels(name, help, &[], value)
+    }
+
+    pub fn counter_with_labels(&mut self, name: &str, help: &str, labels: &[(&str, &str)], value: f64) -> &mut Self {
+        self.add_sample(name, help, "counter", labels, value)
+    }
+
+    // `bucket_counts` holds the count within each bucket (not cumulative), with one more entry than
+    // `bucket_bounds` for values above the last bound
+    pub fn histogram(&mut self, name: &str, help: &str, bucket_bounds: &[u64], bucket_counts: &[u64], sum: f64) -> &mut Self {
+        let index = self.metric_index(name, help, "histogram");
+        let samples = &mut self.metrics[index].samples;
+
+        let mut cumulative_count = 0;
+        for (i, count) in bucket_counts.iter().enumerate() {
+            cumulative_count += count;
+            let le = bucket_bounds.get(i).map_or("+Inf".to_string(), |b| b.to_string());
+            samples.push((format!("_bucket{}", format_labels(&[("le", &le)])), cumulative_count as f64));
+        }
+        samples.push(("_sum".to_string(), sum));
+        samples.push(("_count".to_string(), cumulative_count as f64));
+        self
+    }
+
+    pub fn encode(&self) -> String {
+        let mut output = String::new();
+        for metric in self.metrics.iter() {
+            writeln!(output, "# HELP {} {}", metric.name, metric.help).unwrap();
+            writeln!(output, "# TYPE {} {}", metric.name, metric.metric_type).unwrap();
+            for (labels, value) in metric.samples.iter() {
+                writeln!(output, "{}{} {}", metric.name, labels, value).unwrap();
+            }
+        }
+        output
+    }
+
+    fn add_sample(
+        &mut self,
+        name: &str,
+        help: &str,
+        metric_type: &'static str,
+        labels: &[(&str, &str)],
+        value: f64,
+    ) -> &mut Self {
+        let index = self.metric_index(name, help, metric_type);
+        self.metrics[index].samples.push((format_labels(labels), value));
+        self
+    }
+
+    fn metric_index(&mut self, name: &str, help: &str, metric_type: &'static str) -> usize {
+        let name = format!("{}_{}", self.namespace, name);
+        match self.metrics.iter().position(|m| m.name == name) {
+            Some(index) => index,
+            None => {
+                self.metrics.push(Metric {
+                    name,
+                    help: help.to_string(),
+                    metric_type,
+                    samples: Vec::new(),
+                });
+                self.metrics.len() - 1
+            }
+        }
+    }
+}
+
+// Prometheus scrapers ask for the text format via the `Accept` header, whereas browsers and other
+// clients get JSON
+pub fn accepts_prometheus_metrics(request: &HttpRequest) -> bool {
+    request.header("Accept").map_or(false, |a| {
+        a.contains("text/plain") || a.contains("application/openmetrics-text")
+    })
+}
+
+pub fn get_prometheus_metrics<T: PrometheusMetrics>(metrics: &T, namespace: &str) -> HttpResponse {
+    let mut encoder = PrometheusEncoder::new(namespace);
+    metrics.encode(&mut encoder);
+    let body = encoder.encode().into_bytes();
+
+    HttpResponse {
+        status_code: 200,
+        headers: vec![
+            HeaderField("Content-Type".to_string(), "text/plain; version=0.0.4".to_string()),
+            HeaderField("Content-Length".to_string(), body.len().to_string()),
+        ],
+        body: Cow::Owned(ByteBuf::from(body)),
+        streaming_strategy: None,
+    }
+}
+
+fn format_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+
+    let labels: Vec<_> = labels
+        .iter()
+        .map(|(name, value)| {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+
+    format!("{{{}}}", labels.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_grouped_by_metric() {
+        let mut encoder = PrometheusEncoder::new("index");
+        encoder
+            .gauge_with_labels("bucket_bytes_used", "Bytes used", &[("canister_id", "a")], 1.0)
+            .counter("events_total", "Events", 5.0)
+            .gauge_with_labels("bucket_bytes_used", "Bytes used", &[("canister_id", "b\"")], 2.0);
+
+        let expected = "\
+# HELP index_bucket_bytes_used Bytes used
+# TYPE index_bucket_bytes_used gauge
+index_bucket_bytes_used{canister_id=\"a\"} 1
+index_bucket_bytes_used{canister_id=\"b\\\"\"} 2
+# HELP index_events_total Events
+# TYPE index_events_total counter
+index_events_total 5
+";
+        assert_eq!(encoder.encode(), expected);
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let mut encoder = PrometheusEncoder::new("bucket");
+        encoder.histogram("latency_ms", "Latency", &[10, 100], &[2, 1, 1], 1026.0);
+
+        let expected = "\
+# HELP bucket_latency_ms Latency
+# TYPE bucket_latency_ms histogram
+bucket_latency_ms_bucket{le=\"10\"} 2
+bucket_latency_ms_bucket{le=\"100\"} 3
+bucket_latency_ms_bucket{le=\"+Inf\"} 4
+bucket_latency_ms_sum 1026
+bucket_latency_ms_count 4
+";
+        assert_eq!(encoder.encode(), expected);
+    }
+}
@@ -1,15 +1,63 @@
+use canister_logger::LogFilter;
+use std::cmp::min;
 use std::str::FromStr;
+use tracing::Level;
 use types::{FileId, TimestampMillis};
 
+const MAX_LOGS_PAGE_SIZE: usize = 1000;
+
 pub enum Route {
     File(u128),
-    Logs(Option<TimestampMillis>),
-    Traces(Option<TimestampMillis>),
+    Logs(LogQuery),
+    Traces(LogQuery),
     Metrics,
+    PrometheusMetrics,
+    BadRequest(String),
     Other,
 }
 
+// Built from the path and query string of a logs request, eg.
+// `/logs/1633649663014?level=warn&contains=failed&field.user_id=abc&limit=50&after=1200`
+#[derive(Default)]
+pub struct LogQuery {
+    pub filter: LogFilter,
+    pub after: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+impl LogQuery {
+    pub fn limit(&self) -> usize {
+        min(self.limit.unwrap_or(MAX_LOGS_PAGE_SIZE), MAX_LOGS_PAGE_SIZE)
+    }
+
+    // Returns an error if a filter can't be parsed, since ignoring it would return unfiltered logs
+    fn parse(since: Option<TimestampMillis>, query_string: &str) -> Result<LogQuery, String> {
+        let mut query = LogQuery::default();
+        query.filter.since = since;
+
+        for (key, value) in query_string.split('&').filter_map(|p| p.split_once('=')) {
+            let value = percent_decode(value);
+            match key {
+                "level" => match Level::from_str(&value) {
+                    Ok(level) => query.filter.level = Some(level),
+                    Err(_) => return Err(format!("Invalid level: {}", value)),
+                },
+                "contains" => query.filter.contains = Some(value),
+                "after" => query.after = u64::from_str(&value).ok(),
+                "limit" => query.limit = usize::from_str(&value).ok(),
+                _ => {
+                    if let Some(field) = key.strip_prefix("field.") {
+                        query.filter.fields.push((field.to_string(), value));
+                    }
+                }
+            }
+        }
+        Ok(query)
+    }
+}
+
 pub fn extract_route(path: &str) -> Route {
+    let (path, query_string) = path.split_once('?').unwrap_or((path, ""));
     let path = path.trim_start_matches('/').trim_end_matches('/').to_lowercase();
 
     if path.is_empty() {
@@ -27,17 +75,42 @@ pub fn extract_route(path: &str) -> Route {
         }
         "logs" => {
             let since = parts.get(1).and_then(|p| TimestampMillis::from_str(p).ok());
-            Route::Logs(since)
+            LogQuery::parse(since, query_string).map_or_else(Route::BadRequest, Route::Logs)
         }
         "trace" => {
             let since = parts.get(1).and_then(|p| TimestampMillis::from_str(p).ok());
-            Route::Traces(since)
+            LogQuery::parse(since, query_string).map_or_else(Route::BadRequest, Route::Traces)
         }
+        "metrics" if parts.get(1) == Some(&"prometheus") => Route::PrometheusMetrics,
         "metrics" => Route::Metrics,
         _ => Route::Other,
     }
 }
 
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = if bytes[i] == b'%' && i + 2 < bytes.len() {
+            std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+
+        if let Some(byte) = escaped {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(if bytes[i] == b'+' { b' ' } else { bytes[i] });
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -52,6 +125,33 @@ mod tests {
         assert!(matches!(extract_route("/logs/1633649663014109000"), Route::Logs(_)));
     }
 
+    #[test]
+    fn logs_with_query() {
+        let route = extract_route("/logs/100?level=WARN&contains=sync%20failed&field.user_id=abc&after=5&limit=20");
+        if let Route::Logs(query) = route {
+            assert_eq!(query.filter.since, Some(100));
+            assert_eq!(query.filter.level, Some(Level::WARN));
+            assert_eq!(query.filter.contains.as_deref(), Some("sync failed"));
+            assert_eq!(query.filter.fields, vec![("user_id".to_string(), "abc".to_string())]);
+            assert_eq!(query.after, Some(5));
+            assert_eq!(query.limit(), 20);
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn logs_with_invalid_level() {
+        assert!(matches!(extract_route("/logs?level=loud"), Route::BadRequest(_)));
+        assert!(matches!(extract_route("/trace?level=loud"), Route::BadRequest(_)));
+    }
+
+    #[test]
+    fn prometheus_metrics() {
+        assert!(matches!(extract_route("/metrics/prometheus"), Route::PrometheusMetrics));
+        assert!(matches!(extract_route("/metrics"), Route::Metrics));
+    }
+
     #[test]
     fn other() {
         assert!(matches!(extract_route("blah"), Route::Other));
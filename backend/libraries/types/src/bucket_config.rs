@@ -0,0 +1,23 @@
+use crate::Cycles;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+// The subset of the index canister's config which is pushed out to each bucket
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct BucketConfig {
+    pub data_limit_bytes: u64,
+    pub max_blob_size_bytes: u64,
+    pub max_events_to_sync_per_batch: u32,
+    pub min_cycles_balance: Cycles,
+}
+
+impl Default for BucketConfig {
+    fn default() -> Self {
+        BucketConfig {
+            data_limit_bytes: 1 << 30,            // 1Gb
+            max_blob_size_bytes: 100 * (1 << 20), // 100Mb
+            max_events_to_sync_per_batch: 1000,
+            min_cycles_balance: 2_000_000_000_000, // 2T
+        }
+    }
+}
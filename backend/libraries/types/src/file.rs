@@ -1,31 +1,51 @@
-use crate::{FileId, Hash, UserId};
+use crate::{AccessorId, FileId, Hash, UserId};
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
 
-#[derive(CandidType, Serialize, Deserialize, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct FileAdded {
     pub file_id: FileId,
     pub owner: UserId,
     pub hash: Hash,
     pub size: u64,
+    #[serde(default)]
+    pub accessors: Vec<AccessorId>,
+    // The correlation id of the call which added the file
+    #[serde(default)]
+    pub correlation_id: Option<String>,
+    #[serde(default)]
+    pub mime_type: Option<String>,
 }
 
-#[derive(CandidType, Serialize, Deserialize, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct FileRemoved {
     pub file_id: FileId,
     pub owner: UserId,
     pub hash: Hash,
     pub blob_deleted: bool,
+    // The correlation id of the call which removed the file
+    #[serde(default)]
+    pub correlation_id: Option<String>,
+    // The user who deleted the file, or None if it was removed for another reason, eg. because its
+    // last accessor was removed
+    #[serde(default)]
+    pub removed_by: Option<UserId>,
 }
 
-#[derive(CandidType, Serialize, Deserialize, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct FileRejected {
     pub file_id: FileId,
+    // Files rejected before the owner was recorded have no owner
+    #[serde(default)]
+    pub owner: Option<UserId>,
     pub reason: FileRejectedReason,
 }
 
-#[derive(CandidType, Serialize, Deserialize, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub enum FileRejectedReason {
     AllowanceExceeded,
     UserNotFound,
+    FileCountExceeded,
+    FileTooBig,
+    MimeTypeNotAllowed,
 }
@@ -14,6 +14,9 @@ pub enum RejectedReason {
     UserNotFound,
     AllowanceExceeded,
     HashMismatch,
+    FileCountExceeded,
+    FileTooBig,
+    MimeTypeNotAllowed,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -44,6 +47,9 @@ impl From<FileRejectedReason> for RejectedReason {
         match reason {
             FileRejectedReason::AllowanceExceeded => RejectedReason::AllowanceExceeded,
             FileRejectedReason::UserNotFound => RejectedReason::UserNotFound,
+            FileRejectedReason::FileCountExceeded => RejectedReason::FileCountExceeded,
+            FileRejectedReason::FileTooBig => RejectedReason::FileTooBig,
+            FileRejectedReason::MimeTypeNotAllowed => RejectedReason::MimeTypeNotAllowed,
         }
     }
 }
@@ -1,17 +1,23 @@
 use candid::Principal;
 
+mod bucket_config;
 mod canister_wasm;
 mod cycles;
 mod file;
 mod file_status;
+mod log_settings;
 mod timestamped;
+mod user_limits;
 mod version;
 
+pub use bucket_config::*;
 pub use canister_wasm::*;
 pub use cycles::*;
 pub use file::*;
 pub use file_status::*;
+pub use log_settings::*;
 pub use timestamped::*;
+pub use user_limits::*;
 pub use version::*;
 
 pub type AccessorId = Principal;
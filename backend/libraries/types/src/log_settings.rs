@@ -0,0 +1,35 @@
+use crate::TimestampMillis;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+// Controls what each canister logs and traces, can be changed at runtime
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct LogSettings {
+    pub level: LogLevel,
+    // Every trace is captured until this time
+    pub trace_until: Option<TimestampMillis>,
+    // Outside of the trace window, traces of these methods are still captured for a sample of calls
+    pub sampled_methods: Vec<SampledMethod>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Default for LogLevel {
+    fn default() -> Self {
+        LogLevel::Info
+    }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct SampledMethod {
+    pub method_name: String,
+    // Captures the trace of 1 in every `sample_every` calls
+    pub sample_every: u32,
+}
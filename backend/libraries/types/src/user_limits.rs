@@ -0,0 +1,88 @@
+use crate::FileRejectedReason;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+// Optional limits on the files each user can upload, on top of their byte limit. These are set via the
+// index and pushed out to each bucket which knows about the user.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct UserLimits {
+    pub max_files: Option<u32>,
+    pub max_file_size: Option<u64>,
+    // Either exact MIME types or wildcards such as "image/*"
+    pub allowed_mime_types: Option<Vec<String>>,
+}
+
+impl UserLimits {
+    // Checks whether the user, who currently owns `files_owned` files, can add a new file
+    pub fn check_new_file(&self, files_owned: u64, size: u64, mime_type: Option<&str>) -> Result<(), FileRejectedReason> {
+        if self.max_file_size.map_or(false, |m| size > m) {
+            Err(FileRejectedReason::FileTooBig)
+        } else if self.max_files.map_or(false, |m| files_owned >= m as u64) {
+            Err(FileRejectedReason::FileCountExceeded)
+        } else if !self.is_mime_type_of_new_file_allowed(mime_type) {
+            Err(FileRejectedReason::MimeTypeNotAllowed)
+        } else {
+            Ok(())
+        }
+    }
+
+    // A file without a MIME type can't be checked against the allowed MIME types, so is only allowed if
+    // the user's MIME types aren't restricted
+    fn is_mime_type_of_new_file_allowed(&self, mime_type: Option<&str>) -> bool {
+        match mime_type {
+            Some(m) => self.is_mime_type_allowed(m),
+            None => self.allowed_mime_types.is_none(),
+        }
+    }
+
+    pub fn is_mime_type_allowed(&self, mime_type: &str) -> bool {
+        // Ignore any parameters, eg. "text/plain; charset=utf-8"
+        let mime_type = mime_type.split(';').next().unwrap_or_default().trim();
+
+        self.allowed_mime_types.as_ref().map_or(true, |allowed| {
+            allowed.iter().any(|a| match a.strip_suffix("/*") {
+                Some(prefix) => mime_type
+                    .split_once('/')
+                    .map_or(false, |(t, _)| t.eq_ignore_ascii_case(prefix)),
+                None => a.eq_ignore_ascii_case(mime_type),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_files_checked_against_limits() {
+        let limits = UserLimits {
+            max_files: Some(2),
+            max_file_size: Some(100),
+            allowed_mime_types: Some(vec!["image/*".to_string(), "application/pdf".to_string()]),
+        };
+
+        assert!(limits.check_new_file(1, 100, Some("image/png")).is_ok());
+        assert!(limits.check_new_file(1, 100, Some("Application/PDF; foo=bar")).is_ok());
+        assert!(matches!(
+            limits.check_new_file(1, 100, None),
+            Err(FileRejectedReason::MimeTypeNotAllowed)
+        ));
+        assert!(matches!(
+            limits.check_new_file(1, 101, Some("image/png")),
+            Err(FileRejectedReason::FileTooBig)
+        ));
+        assert!(matches!(
+            limits.check_new_file(2, 100, Some("image/png")),
+            Err(FileRejectedReason::FileCountExceeded)
+        ));
+        assert!(matches!(
+            limits.check_new_file(1, 100, Some("imagery/png")),
+            Err(FileRejectedReason::MimeTypeNotAllowed)
+        ));
+        assert!(UserLimits::default()
+            .check_new_file(u64::MAX, u64::MAX, Some("text/plain"))
+            .is_ok());
+        assert!(UserLimits::default().check_new_file(0, 0, None).is_ok());
+    }
+}
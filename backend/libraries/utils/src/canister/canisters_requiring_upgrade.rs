@@ -16,14 +16,25 @@ pub struct CanistersRequiringUpgrade {
     pending: VecDeque<CanisterId>,
     in_progress: HashSet<CanisterId>,
     failed: VecDeque<FailedUpgrade>,
+    #[serde(default)]
+    paused: bool,
 }
 
 impl CanistersRequiringUpgrade {
-    pub fn enqueue(&mut self, canister_id: CanisterId) {
-        self.pending.push_back(canister_id);
+    // Canisters which are already pending or whose upgrades are in progress aren't queued again
+    pub fn enqueue(&mut self, canister_id: CanisterId) -> bool {
+        if self.pending.contains(&canister_id) || self.in_progress.contains(&canister_id) {
+            false
+        } else {
+            self.pending.push_back(canister_id);
+            true
+        }
     }
 
     pub fn try_take_next(&mut self) -> Option<CanisterId> {
+        if self.paused {
+            return None;
+        }
         let canister_id = self.pending.pop_front()?;
         self.in_progress.insert(canister_id);
         Some(canister_id)
@@ -38,6 +49,36 @@ impl CanistersRequiringUpgrade {
         self.failed.push_back(failed_upgrade);
     }
 
+    // Upgrades which are already in progress will complete, but no new upgrades will be started
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Moves all failed upgrades back into the pending queue, returning the number requeued
+    pub fn retry_failed(&mut self) -> usize {
+        let mut count = 0;
+        while let Some(failed_upgrade) = self.failed.pop_front() {
+            if self.enqueue(failed_upgrade.canister_id) {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    // Clears the pending and failed upgrades, leaving any which are in progress to complete
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.failed.clear();
+    }
+
     pub fn is_in_progress(&self, canister_id: &CanisterId) -> bool {
         self.in_progress.contains(canister_id)
     }
@@ -67,6 +108,7 @@ impl CanistersRequiringUpgrade {
             pending: self.pending.len(),
             in_progress: self.in_progress.len(),
             failed,
+            paused: self.paused,
         }
     }
 }
@@ -75,6 +117,7 @@ pub struct Metrics {
     pub pending: usize,
     pub in_progress: usize,
     pub failed: Vec<FailedUpgradeCount>,
+    pub paused: bool,
 }
 
 #[derive(CandidType, Serialize, Debug)]
@@ -83,3 +126,45 @@ pub struct FailedUpgradeCount {
     pub to_version: Version,
     pub count: usize,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+
+    fn failed_upgrade(canister_id: CanisterId) -> FailedUpgrade {
+        FailedUpgrade {
+            canister_id,
+            from_version: Version::new(1, 0, 0),
+            to_version: Version::new(2, 0, 0),
+        }
+    }
+
+    #[test]
+    fn retry_failed_only_counts_canisters_requeued() {
+        let canister_id1 = Principal::from_slice(&[1]);
+        let canister_id2 = Principal::from_slice(&[2]);
+        let mut canisters_requiring_upgrade = CanistersRequiringUpgrade::default();
+        canisters_requiring_upgrade.mark_failure(failed_upgrade(canister_id1));
+        canisters_requiring_upgrade.mark_failure(failed_upgrade(canister_id1));
+        canisters_requiring_upgrade.mark_failure(failed_upgrade(canister_id2));
+        assert!(canisters_requiring_upgrade.enqueue(canister_id2));
+
+        assert_eq!(canisters_requiring_upgrade.retry_failed(), 1);
+        assert_eq!(canisters_requiring_upgrade.metrics().pending, 2);
+    }
+
+    #[test]
+    fn enqueue_skips_canisters_pending_or_in_progress() {
+        let canister_id = Principal::from_slice(&[1]);
+        let mut canisters_requiring_upgrade = CanistersRequiringUpgrade::default();
+
+        assert!(canisters_requiring_upgrade.enqueue(canister_id));
+        assert!(!canisters_requiring_upgrade.enqueue(canister_id));
+        assert_eq!(canisters_requiring_upgrade.try_take_next(), Some(canister_id));
+        assert!(!canisters_requiring_upgrade.enqueue(canister_id));
+
+        canisters_requiring_upgrade.mark_success(&canister_id);
+        assert!(canisters_requiring_upgrade.enqueue(canister_id));
+    }
+}
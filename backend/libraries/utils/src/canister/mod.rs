@@ -2,6 +2,7 @@ mod canisters_requiring_upgrade;
 mod create;
 mod delete;
 mod error;
+mod module_hash;
 mod pool;
 mod stop;
 mod upgrade;
@@ -10,6 +11,7 @@ pub use canisters_requiring_upgrade::*;
 pub use create::*;
 pub use delete::*;
 pub use error::*;
+pub use module_hash::*;
 pub use pool::*;
 pub use stop::*;
 pub use upgrade::*;
@@ -0,0 +1,41 @@
+use crate::canister;
+use candid::{CandidType, Principal};
+use ic_cdk::api;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use tracing::error;
+use types::{CanisterId, Hash};
+
+// Returns the sha256 of the wasm module currently installed in the canister, or None if the canister
+// is empty. The caller must be a controller of the canister.
+pub async fn get_module_hash(canister_id: CanisterId) -> Result<Option<Hash>, canister::Error> {
+    #[derive(CandidType, Serialize, Deserialize)]
+    struct CanisterStatusArgs {
+        canister_id: Principal,
+    }
+
+    // Only the fields we need are decoded, the rest of the response is ignored
+    #[derive(CandidType, Deserialize)]
+    struct CanisterStatusResponse {
+        module_hash: Option<ByteBuf>,
+    }
+
+    let args = CanisterStatusArgs { canister_id };
+
+    let (response,): (CanisterStatusResponse,) =
+        match api::call::call(Principal::management_canister(), "canister_status", (args,)).await {
+            Ok(x) => x,
+            Err((code, msg)) => {
+                let code = code as u8;
+                error!(
+                    canister_id = canister_id.to_string().as_str(),
+                    error_code = code,
+                    error_message = msg.as_str(),
+                    "Error calling 'canister_status'"
+                );
+                return Err(canister::Error { code, msg });
+            }
+        };
+
+    Ok(response.module_hash.and_then(|h| h.into_vec().try_into().ok()))
+}
@@ -1,39 +1,42 @@
-use candid::CandidType;
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use types::CanisterId;
 
-#[derive(CandidType, Serialize, Deserialize)]
-pub struct Pool {
-    target_size: u16,
-    canister_ids: VecDeque<CanisterId>,
+#[derive(Serialize, Deserialize)]
+pub struct Pool<T = CanisterId> {
+    canisters: VecDeque<T>,
 }
 
-impl Pool {
-    pub fn new(target_size: u16) -> Pool {
+impl<T> Default for Pool<T> {
+    fn default() -> Self {
         Pool {
-            target_size,
-            canister_ids: VecDeque::default(),
+            canisters: VecDeque::default(),
         }
     }
+}
 
+impl<T> Pool<T> {
     pub fn len(&self) -> usize {
-        self.canister_ids.len()
+        self.canisters.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.canister_ids.is_empty()
+        self.canisters.is_empty()
+    }
+
+    pub fn push(&mut self, canister: T) {
+        self.canisters.push_back(canister);
     }
 
-    pub fn is_full(&self) -> bool {
-        self.canister_ids.len() >= self.target_size as usize
+    pub fn pop(&mut self) -> Option<T> {
+        self.canisters.pop_front()
     }
 
-    pub fn push(&mut self, canister_id: CanisterId) {
-        self.canister_ids.push_back(canister_id);
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.canisters.iter()
     }
 
-    pub fn pop(&mut self) -> Option<CanisterId> {
-        self.canister_ids.pop_front()
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.canisters.iter_mut()
     }
 }
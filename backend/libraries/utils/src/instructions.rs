@@ -0,0 +1,13 @@
+// Returns the number of instructions executed so far in the current message
+pub fn count() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        ic_cdk::api::performance_counter(0)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        // This branch won't actually ever be taken
+        0
+    }
+}
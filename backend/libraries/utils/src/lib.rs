@@ -3,5 +3,10 @@ pub mod consts;
 pub mod cycles;
 pub mod env;
 pub mod hasher;
+pub mod instructions;
 pub mod memory;
+pub mod metrics;
+pub mod scheduler;
+pub mod stable_memory;
+pub mod sync_receiver;
 pub mod time;
@@ -0,0 +1,162 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use types::Milliseconds;
+
+// Upper bounds of the buckets used for histograms of c2c call latencies
+pub const LATENCY_BUCKETS_MS: &[u64] = &[100, 250, 500, 1_000, 2_000, 5_000, 10_000, 30_000, 60_000];
+
+// Upper bounds of the buckets used for histograms of file sizes
+pub const SIZE_BUCKETS_BYTES: &[u64] = &[
+    1 << 10,   // 1KB
+    10 << 10,  // 10KB
+    100 << 10, // 100KB
+    1 << 20,   // 1MB
+    10 << 20,  // 10MB
+    100 << 20, // 100MB
+];
+
+// The names of the metrics recorded for each call of a particular kind to another canister
+pub struct CallMetricNames {
+    pub calls: &'static str,
+    pub failures: &'static str,
+    pub latency_ms: &'static str,
+}
+
+// Counters and histograms which are updated as operations happen. These are held as part of each
+// canister's data so that they are persisted across upgrades.
+#[derive(CandidType, Serialize, Deserialize, Clone, Default, Debug)]
+pub struct MetricsRegistry {
+    counters: BTreeMap<String, u64>,
+    histograms: BTreeMap<String, Histogram>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Histogram {
+    // The upper bound of each bucket, with an implicit final bucket for values above the last bound
+    pub bucket_bounds: Vec<u64>,
+    pub bucket_counts: Vec<u64>,
+    pub sum: u64,
+    pub count: u64,
+}
+
+impl MetricsRegistry {
+    pub fn increment(&mut self, name: &str) {
+        self.increment_by(name, 1);
+    }
+
+    pub fn increment_by(&mut self, name: &str, value: u64) {
+        if let Some(counter) = self.counters.get_mut(name) {
+            *counter = counter.saturating_add(value);
+        } else {
+            self.counters.insert(name.to_string(), value);
+        }
+    }
+
+    pub fn observe(&mut self, name: &str, bucket_bounds: &[u64], value: u64) {
+        // If the bounds have changed the old histogram can't be added to so it is started afresh
+        let histogram = match self.histograms.get_mut(name) {
+            Some(h) if h.bucket_bounds == bucket_bounds => h,
+            _ => {
+                self.histograms.insert(name.to_string(), Histogram::new(bucket_bounds));
+                self.histograms.get_mut(name).unwrap()
+            }
+        };
+        histogram.observe(value);
+    }
+
+    // Counts the call, and the failure if it failed, then records how long the call took
+    pub fn record_call(&mut self, names: &CallMetricNames, success: bool, latency: Milliseconds) {
+        self.increment(names.calls);
+        if !success {
+            self.increment(names.failures);
+        }
+        self.observe(names.latency_ms, LATENCY_BUCKETS_MS, latency);
+    }
+
+    pub fn counter(&self, name: &str) -> u64 {
+        self.counters.get(name).copied().unwrap_or_default()
+    }
+
+    pub fn counters(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.counters.iter().map(|(n, v)| (n.as_str(), *v))
+    }
+
+    pub fn histograms(&self) -> impl Iterator<Item = (&str, &Histogram)> {
+        self.histograms.iter().map(|(n, h)| (n.as_str(), h))
+    }
+}
+
+impl Histogram {
+    fn new(bucket_bounds: &[u64]) -> Histogram {
+        Histogram {
+            bucket_bounds: bucket_bounds.to_vec(),
+            bucket_counts: vec![0; bucket_bounds.len() + 1],
+            sum: 0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: u64) {
+        let index = self
+            .bucket_bounds
+            .iter()
+            .position(|b| value <= *b)
+            .unwrap_or(self.bucket_bounds.len());
+        self.bucket_counts[index] += 1;
+        self.sum = self.sum.saturating_add(value);
+        self.count += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters() {
+        let mut registry = MetricsRegistry::default();
+        registry.increment("uploads");
+        registry.increment_by("uploads", 2);
+
+        assert_eq!(registry.counter("uploads"), 3);
+        assert_eq!(registry.counter("deletes"), 0);
+    }
+
+    #[test]
+    fn histogram_values_assigned_to_buckets() {
+        let mut registry = MetricsRegistry::default();
+        for value in [5, 10, 11, 1000] {
+            registry.observe("latency", &[10, 100], value);
+        }
+
+        let (_, histogram) = registry.histograms().next().unwrap();
+        assert_eq!(histogram.bucket_counts, vec![2, 1, 1]);
+        assert_eq!(histogram.sum, 1026);
+        assert_eq!(histogram.count, 4);
+
+        // Changing the bounds resets the histogram
+        registry.observe("latency", &[10], 5);
+        let (_, histogram) = registry.histograms().next().unwrap();
+        assert_eq!(histogram.bucket_counts, vec![1, 0]);
+        assert_eq!(histogram.count, 1);
+    }
+
+    #[test]
+    fn calls_recorded() {
+        let names = CallMetricNames {
+            calls: "syncs",
+            failures: "syncs_failed",
+            latency_ms: "sync_latency_ms",
+        };
+        let mut registry = MetricsRegistry::default();
+        registry.record_call(&names, true, 50);
+        registry.record_call(&names, false, 200);
+
+        assert_eq!(registry.counter("syncs"), 2);
+        assert_eq!(registry.counter("syncs_failed"), 1);
+        let (name, histogram) = registry.histograms().next().unwrap();
+        assert_eq!(name, "sync_latency_ms");
+        assert_eq!(histogram.count, 2);
+    }
+}
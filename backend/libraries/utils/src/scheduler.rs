@@ -0,0 +1,142 @@
+use crate::time;
+use std::cell::RefCell;
+use types::{Milliseconds, TimestampMillis};
+
+thread_local! {
+    static SCHEDULER: RefCell<Scheduler> = RefCell::default();
+}
+
+// Runs background jobs off the canister's global timer rather than on every heartbeat. Jobs either
+// run at a fixed interval or only when triggered, eg. when work is added to a queue.
+//
+// The global timer is cleared when a canister is upgraded and function pointers can't be persisted,
+// so jobs must be registered in both `init` and `post_upgrade`. Every job is run once straight after
+// being registered so that any work which was pending before an upgrade is picked up again.
+//
+// The canister must export `canister_global_timer` and call `run_due_jobs` from it.
+pub fn register(name: &'static str, interval: Option<Milliseconds>, run: fn()) {
+    let now = time::now_millis();
+    let next = SCHEDULER.with(|s| s.borrow_mut().register(name, interval, run, now));
+    set_global_timer(next);
+}
+
+pub fn run_now(name: &'static str) {
+    run_after(name, 0);
+}
+
+pub fn run_after(name: &'static str, delay: Milliseconds) {
+    let now = time::now_millis();
+    let next = SCHEDULER.with(|s| s.borrow_mut().schedule(name, now + delay));
+    set_global_timer(next);
+}
+
+pub fn run_due_jobs() {
+    let now = time::now_millis();
+
+    // The jobs are taken before running them since they may trigger other jobs. The timer is set for
+    // the next run before any jobs are run so that a job which traps doesn't stop the timer being set.
+    let (due, next) = SCHEDULER.with(|s| {
+        let mut scheduler = s.borrow_mut();
+        let due = scheduler.take_due(now);
+        (due, scheduler.next_run())
+    });
+    set_global_timer(next);
+
+    for run in due {
+        run();
+    }
+}
+
+#[derive(Default)]
+struct Scheduler {
+    jobs: Vec<Job>,
+}
+
+struct Job {
+    name: &'static str,
+    interval: Option<Milliseconds>,
+    next_run: Option<TimestampMillis>,
+    run: fn(),
+}
+
+impl Scheduler {
+    fn register(
+        &mut self,
+        name: &'static str,
+        interval: Option<Milliseconds>,
+        run: fn(),
+        now: TimestampMillis,
+    ) -> Option<TimestampMillis> {
+        self.jobs.retain(|j| j.name != name);
+        self.jobs.push(Job {
+            name,
+            interval,
+            next_run: Some(now),
+            run,
+        });
+        self.next_run()
+    }
+
+    // Brings the job's next run forward to `at` if it isn't already due sooner
+    fn schedule(&mut self, name: &'static str, at: TimestampMillis) -> Option<TimestampMillis> {
+        if let Some(job) = self.jobs.iter_mut().find(|j| j.name == name) {
+            job.next_run = Some(job.next_run.map_or(at, |n| n.min(at)));
+        }
+        self.next_run()
+    }
+
+    fn take_due(&mut self, now: TimestampMillis) -> Vec<fn()> {
+        let mut due = Vec::new();
+        for job in self.jobs.iter_mut().filter(|j| j.next_run.map_or(false, |n| n <= now)) {
+            job.next_run = job.interval.map(|i| now + i);
+            due.push(job.run);
+        }
+        due
+    }
+
+    fn next_run(&self) -> Option<TimestampMillis> {
+        self.jobs.iter().filter_map(|j| j.next_run).min()
+    }
+}
+
+fn set_global_timer(next: Option<TimestampMillis>) {
+    // A timestamp of 0 deactivates the timer
+    let timestamp_nanos = next.map_or(0, |n| n.max(1) * 1_000_000);
+
+    ic_cdk::api::set_global_timer(timestamp_nanos);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop() {}
+
+    #[test]
+    fn jobs_run_once_registered_then_at_their_interval() {
+        let mut scheduler = Scheduler::default();
+        scheduler.register("interval", Some(100), noop, 0);
+        scheduler.register("on_demand", None, noop, 0);
+
+        assert_eq!(scheduler.take_due(0).len(), 2);
+        assert_eq!(scheduler.next_run(), Some(100));
+        assert!(scheduler.take_due(99).is_empty());
+        assert_eq!(scheduler.take_due(100).len(), 1);
+    }
+
+    #[test]
+    fn triggered_jobs_run_at_earliest_requested_time() {
+        let mut scheduler = Scheduler::default();
+        scheduler.register("on_demand", None, noop, 0);
+        scheduler.take_due(0);
+
+        assert_eq!(scheduler.next_run(), None);
+
+        scheduler.schedule("on_demand", 50);
+        scheduler.schedule("on_demand", 80);
+
+        assert_eq!(scheduler.next_run(), Some(50));
+        assert_eq!(scheduler.take_due(50).len(), 1);
+        assert_eq!(scheduler.next_run(), None);
+    }
+}
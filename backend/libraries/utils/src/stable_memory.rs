@@ -0,0 +1,129 @@
+use ic_cdk::api::stable::{stable64_grow, stable64_read, stable64_size, stable64_write};
+use std::cmp::min;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::sync::{Arc, Mutex};
+
+const WASM_PAGE_SIZE_BYTES: u64 = 64 * 1024;
+
+// Allows structures held in stable memory to be tested against memory held on the heap
+pub trait Memory: Send {
+    fn size_bytes(&self) -> u64;
+    fn grow_to(&mut self, size_bytes: u64);
+    fn read(&self, offset: u64, buf: &mut [u8]);
+    fn write(&mut self, offset: u64, buf: &[u8]);
+}
+
+pub struct StableMemory;
+
+impl Memory for StableMemory {
+    fn size_bytes(&self) -> u64 {
+        #[cfg(target_arch = "wasm32")]
+        {
+            stable64_size() * WASM_PAGE_SIZE_BYTES
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            // This branch won't actually ever be taken
+            0
+        }
+    }
+
+    fn grow_to(&mut self, size_bytes: u64) {
+        let current_size = self.size_bytes();
+        if size_bytes > current_size {
+            let pages = (size_bytes - current_size + WASM_PAGE_SIZE_BYTES - 1) / WASM_PAGE_SIZE_BYTES;
+            stable64_grow(pages).expect("Failed to grow stable memory");
+        }
+    }
+
+    fn read(&self, offset: u64, buf: &mut [u8]) {
+        stable64_read(offset, buf);
+    }
+
+    fn write(&mut self, offset: u64, buf: &[u8]) {
+        stable64_write(offset, buf);
+    }
+}
+
+// Memory held on the heap, clones of which share the same underlying bytes
+#[derive(Clone, Default)]
+pub struct VecMemory(Arc<Mutex<Vec<u8>>>);
+
+impl Memory for VecMemory {
+    fn size_bytes(&self) -> u64 {
+        self.0.lock().unwrap().len() as u64
+    }
+
+    fn grow_to(&mut self, size_bytes: u64) {
+        let mut bytes = self.0.lock().unwrap();
+        if size_bytes as usize > bytes.len() {
+            bytes.resize(size_bytes as usize, 0);
+        }
+    }
+
+    fn read(&self, offset: u64, buf: &mut [u8]) {
+        let offset = offset as usize;
+        buf.copy_from_slice(&self.0.lock().unwrap()[offset..offset + buf.len()]);
+    }
+
+    fn write(&mut self, offset: u64, buf: &[u8]) {
+        let offset = offset as usize;
+        self.0.lock().unwrap()[offset..offset + buf.len()].copy_from_slice(buf);
+    }
+}
+
+// Writes to stable memory starting from the given offset, growing stable memory as required
+pub struct StableWriter {
+    offset: u64,
+}
+
+impl StableWriter {
+    pub fn new(offset: u64) -> StableWriter {
+        StableWriter { offset }
+    }
+}
+
+impl Write for StableWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let end = self.offset + buf.len() as u64;
+        let capacity = stable64_size() * WASM_PAGE_SIZE_BYTES;
+        if end > capacity {
+            let pages = (end - capacity + WASM_PAGE_SIZE_BYTES - 1) / WASM_PAGE_SIZE_BYTES;
+            stable64_grow(pages).map_err(|e| Error::new(ErrorKind::Other, format!("{:?}", e)))?;
+        }
+
+        stable64_write(self.offset, buf);
+        self.offset = end;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// Reads from stable memory starting from the given offset
+pub struct StableReader {
+    offset: u64,
+}
+
+impl StableReader {
+    pub fn new(offset: u64) -> StableReader {
+        StableReader { offset }
+    }
+}
+
+impl Read for StableReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let capacity = stable64_size() * WASM_PAGE_SIZE_BYTES;
+        if self.offset >= capacity {
+            return Ok(0);
+        }
+
+        let length = min(buf.len() as u64, capacity - self.offset) as usize;
+        stable64_read(self.offset, &mut buf[..length]);
+        self.offset += length as u64;
+        Ok(length)
+    }
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+// Remembers the last batch applied from a sender, so that if a sender retries a batch which was
+// actually applied (eg. because the reply was lost) the original result is returned rather than the
+// batch being applied a second time.
+//
+// Senders number their batches consecutively, starting from 1. Any other batch (eg. one which is
+// older than the last batch applied, or one after a gap) is rejected along with the last sequence
+// number applied, so that the sender can resync by resending the events from the sequence number
+// which follows it. Batches with a sequence number of 0 come from senders which predate sequence
+// numbers and are always applied.
+#[derive(Serialize, Deserialize)]
+pub struct SyncReceiver<T> {
+    last_applied: Option<(u64, T)>,
+}
+
+pub enum SyncCheckResult<T> {
+    Apply,
+    Duplicate(T),
+    OutOfOrder(u64),
+}
+
+impl<T> Default for SyncReceiver<T> {
+    fn default() -> Self {
+        SyncReceiver { last_applied: None }
+    }
+}
+
+impl<T: Clone> SyncReceiver<T> {
+    pub fn check(&self, sequence_number: u64) -> SyncCheckResult<T> {
+        let last_sequence_number = self.last_sequence_number();
+        if sequence_number == 0 || sequence_number == last_sequence_number + 1 {
+            return SyncCheckResult::Apply;
+        }
+        match &self.last_applied {
+            Some((last, result)) if sequence_number == *last => SyncCheckResult::Duplicate(result.clone()),
+            _ => SyncCheckResult::OutOfOrder(last_sequence_number),
+        }
+    }
+
+    pub fn record(&mut self, sequence_number: u64, result: T) {
+        if sequence_number != 0 {
+            self.last_applied = Some((sequence_number, result));
+        }
+    }
+
+    pub fn last_sequence_number(&self) -> u64 {
+        self.last_applied.as_ref().map_or(0, |(s, _)| *s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicates_return_cached_result() {
+        let mut receiver = SyncReceiver::default();
+
+        assert!(matches!(receiver.check(1), SyncCheckResult::Apply));
+        receiver.record(1, "result");
+
+        assert!(matches!(receiver.check(1), SyncCheckResult::Duplicate("result")));
+    }
+
+    #[test]
+    fn older_batches_and_gaps_rejected() {
+        let mut receiver = SyncReceiver::default();
+
+        assert!(matches!(receiver.check(2), SyncCheckResult::OutOfOrder(0)));
+        receiver.record(5, ());
+
+        assert!(matches!(receiver.check(4), SyncCheckResult::OutOfOrder(5)));
+        assert!(matches!(receiver.check(7), SyncCheckResult::OutOfOrder(5)));
+        assert!(matches!(receiver.check(6), SyncCheckResult::Apply));
+    }
+
+    #[test]
+    fn unsequenced_batches_always_applied() {
+        let mut receiver = SyncReceiver::default();
+        receiver.record(5, ());
+        receiver.record(0, ());
+
+        assert!(matches!(receiver.check(0), SyncCheckResult::Apply));
+        assert_eq!(receiver.last_sequence_number(), 5);
+    }
+}
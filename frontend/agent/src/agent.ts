@@ -39,7 +39,8 @@ export class OpenStorageAgent {
 
         const allocatedBucketResponse = await this.indexClient.allocatedBucket(
             hash,
-            BigInt(fileSize)
+            BigInt(fileSize),
+            mimeType
         );
 
         if (allocatedBucketResponse.kind !== "success") {
@@ -124,6 +125,7 @@ export class OpenStorageAgent {
 
             case "not_authorized":
             case "file_not_found":
+            case "try_again_later":
                 return forwardFileResponse;
         }
     }
@@ -139,6 +141,7 @@ export type ForwardFileResponse =
     | UserNotFound
     | { kind: "not_authorized" }
     | { kind: "file_not_found" }
+    | { kind: "try_again_later" }
 
 export type ForwardFileSuccess = {
     kind: "success",
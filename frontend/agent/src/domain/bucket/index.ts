@@ -8,12 +8,16 @@ export type UploadChunkResponse =
     | "allowance_exceeded"
     | "user_not_found"
     | "hash_mismatch"
-    | "full";
+    | "full"
+    | "try_again_later"
+    | "file_count_exceeded"
+    | "mime_type_not_allowed";
 
 export type ForwardFileResponse =
     | { kind: "success", newFileId: bigint }
     | { kind: "not_authorized" }
-    | { kind: "file_not_found" };
+    | { kind: "file_not_found" }
+    | { kind: "try_again_later" };
 
 export type DeleteFileResponse = "success" | "not_authorized" | "file_not_found";
 
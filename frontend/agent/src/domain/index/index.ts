@@ -3,6 +3,9 @@ import type { Principal } from "@dfinity/principal";
 export type AllocatedBucketResponse =
     | AllocatedBucketSuccess
     | AllocatedBucketBucketUnavailable
+    | AllocatedBucketFileTooBig
+    | AllocatedBucketFileCountExceeded
+    | AllocatedBucketMimeTypeNotAllowed
     | AllowanceExceeded
     | UserNotFound;
 
@@ -17,6 +20,20 @@ export type AllocatedBucketBucketUnavailable = {
     kind: "bucket_unavailable";
 };
 
+export type AllocatedBucketFileTooBig = {
+    kind: "file_too_big";
+    maxFileSize: bigint;
+};
+
+export type AllocatedBucketFileCountExceeded = {
+    kind: "file_count_exceeded";
+    maxFiles: number;
+};
+
+export type AllocatedBucketMimeTypeNotAllowed = {
+    kind: "mime_type_not_allowed";
+};
+
 export type CanForwardResponse =
     | CanForwardSuccess
     | AllowanceExceeded
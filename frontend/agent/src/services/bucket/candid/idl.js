@@ -36,6 +36,7 @@ export const idlFactory = ({ IDL }) => {
     'file_id' : FileId,
   });
   const ForwardFileResponse = IDL.Variant({
+    'TryAgainLater' : IDL.Null,
     'NotFound' : IDL.Null,
     'NotAuthorized' : IDL.Null,
     'Success' : FileId,
@@ -61,6 +62,9 @@ export const idlFactory = ({ IDL }) => {
     'FileAlreadyExists' : IDL.Null,
     'AllowanceExceeded' : IDL.Null,
     'UserNotFound' : IDL.Null,
+    'TryAgainLater' : IDL.Null,
+    'FileCountExceeded' : IDL.Null,
+    'MimeTypeNotAllowed' : IDL.Null,
   });
   return IDL.Service({
     'delete_file' : IDL.Func([DeleteFileArgs], [DeleteFileResponse], []),
@@ -30,7 +30,8 @@ export interface ForwardFileArgs {
   'accessors' : Array<AccessorId>,
   'file_id' : FileId,
 }
-export type ForwardFileResponse = { 'NotFound' : null } |
+export type ForwardFileResponse = { 'TryAgainLater' : null } |
+  { 'NotFound' : null } |
   { 'NotAuthorized' : null } |
   { 'Success' : FileId };
 export type Hash = Array<number>;
@@ -56,7 +57,10 @@ export type UploadChunkResponse = { 'ChunkAlreadyExists' : null } |
   { 'HashMismatch' : null } |
   { 'FileAlreadyExists' : null } |
   { 'AllowanceExceeded' : null } |
-  { 'UserNotFound' : null };
+  { 'UserNotFound' : null } |
+  { 'TryAgainLater' : null } |
+  { 'FileCountExceeded' : null } |
+  { 'MimeTypeNotAllowed' : null };
 export type UserId = Principal;
 export interface Version {
   'major' : number,
@@ -33,6 +33,15 @@ export function uploadChunkResponse(candid: CandidUploadChunkResponse): UploadCh
     if ("Full" in candid) {
         return "full";
     }
+    if ("TryAgainLater" in candid) {
+        return "try_again_later";
+    }
+    if ("FileCountExceeded" in candid) {
+        return "file_count_exceeded";
+    }
+    if ("MimeTypeNotAllowed" in candid) {
+        return "mime_type_not_allowed";
+    }
     throw new UnsupportedValueError("Unknown Bucket.CandidUploadChunkResponse type received", candid);
 }
 
@@ -49,6 +58,9 @@ export function forwardFileResponse(candid: CandidForwardFileResponse): ForwardF
     if ("NotFound" in candid) {
         return { kind: "file_not_found" };
     }
+    if ("TryAgainLater" in candid) {
+        return { kind: "try_again_later" };
+    }
     throw new UnsupportedValueError("Unknown Bucket.CandidForwardFileResponse type received", candid);
 }
 
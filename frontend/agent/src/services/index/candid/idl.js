@@ -1,13 +1,17 @@
 export const idlFactory = ({ IDL }) => {
   const UserId = IDL.Principal;
   const UserConfig = IDL.Record({
+    'max_file_size' : IDL.Opt(IDL.Nat64),
     'byte_limit' : IDL.Nat64,
+    'allowed_mime_types' : IDL.Opt(IDL.Vec(IDL.Text)),
     'user_id' : UserId,
+    'max_files' : IDL.Opt(IDL.Nat32),
   });
   const AddOrUpdateUsersArgs = IDL.Record({ 'users' : IDL.Vec(UserConfig) });
   const AddOrUpdateUsersResponse = IDL.Variant({ 'Success' : IDL.Null });
   const Hash = IDL.Vec(IDL.Nat8);
   const AllocatedBucketArgs = IDL.Record({
+    'mime_type' : IDL.Opt(IDL.Text),
     'file_hash' : Hash,
     'file_size' : IDL.Nat64,
   });
@@ -31,6 +35,9 @@ export const idlFactory = ({ IDL }) => {
     'AllowanceExceeded' : ProjectedAllowance,
     'UserNotFound' : IDL.Null,
     'BucketUnavailable' : IDL.Null,
+    'FileTooBig' : IDL.Nat64,
+    'FileCountExceeded' : IDL.Nat32,
+    'MimeTypeNotAllowed' : IDL.Null,
   });
   const CanForwardArgs = IDL.Record({
     'file_hash' : Hash,
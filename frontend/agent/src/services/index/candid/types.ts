@@ -3,6 +3,7 @@ export type AccessorId = Principal;
 export interface AddOrUpdateUsersArgs { 'users' : Array<UserConfig> }
 export type AddOrUpdateUsersResponse = { 'Success' : null };
 export interface AllocatedBucketArgs {
+  'mime_type' : [] | [string],
   'file_hash' : Hash,
   'file_size' : bigint,
 }
@@ -11,7 +12,10 @@ export type AllocatedBucketResponse = {
   } |
   { 'AllowanceExceeded' : ProjectedAllowance } |
   { 'UserNotFound' : null } |
-  { 'BucketUnavailable' : null };
+  { 'BucketUnavailable' : null } |
+  { 'FileTooBig' : bigint } |
+  { 'FileCountExceeded' : number } |
+  { 'MimeTypeNotAllowed' : null };
 export interface AllocatedBucketSuccessResult {
   'byte_limit' : bigint,
   'canister_id' : CanisterId,
@@ -42,7 +46,13 @@ export type RemoveUserResponse = { 'Success' : null };
 export type TimestampMillis = bigint;
 export type TimestampNanos = bigint;
 export type UserArgs = {};
-export interface UserConfig { 'byte_limit' : bigint, 'user_id' : UserId }
+export interface UserConfig {
+  'max_file_size' : [] | [bigint],
+  'byte_limit' : bigint,
+  'allowed_mime_types' : [] | [Array<string>],
+  'user_id' : UserId,
+  'max_files' : [] | [number],
+}
 export type UserId = Principal;
 export interface UserRecord { 'byte_limit' : bigint, 'bytes_used' : bigint }
 export type UserResponse = { 'Success' : UserRecord } |
@@ -2,6 +2,6 @@ import type { AllocatedBucketResponse, CanForwardResponse, UserResponse } from "
 
 export interface IIndexClient {
     user(): Promise<UserResponse>;
-    allocatedBucket(fileHash: Array<number>, fileSize: bigint): Promise<AllocatedBucketResponse>;
+    allocatedBucket(fileHash: Array<number>, fileSize: bigint, mimeType: string): Promise<AllocatedBucketResponse>;
     canForward(fileHash: Array<number>, fileSize: bigint): Promise<CanForwardResponse>;
 }
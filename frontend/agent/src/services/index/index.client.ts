@@ -15,9 +15,9 @@ export class IndexClient extends CandidService<IndexService> implements IIndexCl
         return this.handleResponse(this.service.user({}), userResponse);
     }
 
-    allocatedBucket(fileHash: Array<number>, fileSize: bigint): Promise<AllocatedBucketResponse> {
+    allocatedBucket(fileHash: Array<number>, fileSize: bigint, mimeType: string): Promise<AllocatedBucketResponse> {
         return this.handleResponse(
-            this.service.allocated_bucket_v2({ file_hash: fileHash, file_size: fileSize }),
+            this.service.allocated_bucket_v2({ file_hash: fileHash, file_size: fileSize, mime_type: [mimeType] }),
             allocatedBucketResponse
         );
     }
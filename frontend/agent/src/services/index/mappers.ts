@@ -31,6 +31,23 @@ export function allocatedBucketResponse(
             kind: "bucket_unavailable",
         };
     }
+    if ("FileTooBig" in candid) {
+        return {
+            kind: "file_too_big",
+            maxFileSize: candid.FileTooBig,
+        };
+    }
+    if ("FileCountExceeded" in candid) {
+        return {
+            kind: "file_count_exceeded",
+            maxFiles: candid.FileCountExceeded,
+        };
+    }
+    if ("MimeTypeNotAllowed" in candid) {
+        return {
+            kind: "mime_type_not_allowed",
+        };
+    }
     throw new UnsupportedValueError(
         "Unknown Index.CandidAllocatedBucketResponse type received",
         candid